@@ -0,0 +1,96 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::{distributions::WeightedIndex, rngs::StdRng, Rng, SeedableRng};
+use rand_distr::Distribution;
+use sonicat::mutator::Mutator;
+use sonicat::protein::ProteinMutator;
+
+const LENGTHS: [usize; 2] = [1_000, 100_000];
+
+fn sequence(len: usize) -> Vec<u8> {
+    let mut rng = StdRng::seed_from_u64(0);
+    (0..len).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect()
+}
+
+fn mutate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mutate");
+    for len in LENGTHS {
+        let seq = sequence(len);
+        group.throughput(Throughput::Bytes(len as u64));
+
+        group.bench_with_input(BenchmarkId::new("uniform", len), &seq, |b, seq| {
+            let mut mutator = Mutator::new(0.01, 0.001, 0.001, StdRng::seed_from_u64(1)).unwrap();
+            b.iter(|| {
+                for &x in seq {
+                    black_box(mutator.mutate(x));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("insert_gc", len), &seq, |b, seq| {
+            let mut mutator = Mutator::new(0.01, 0.001, 0.001, StdRng::seed_from_u64(1)).unwrap();
+            mutator.set_insert_gc(0.6).unwrap();
+            b.iter(|| {
+                for &x in seq {
+                    black_box(mutator.mutate(x));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("mutate_seq", len), &seq, |b, seq| {
+            let mut mutator = Mutator::new(0.01, 0.001, 0.001, StdRng::seed_from_u64(1)).unwrap();
+            b.iter(|| black_box(mutator.mutate_seq(seq)))
+        });
+        group.bench_with_input(BenchmarkId::new("protein", len), &seq, |b, seq| {
+            let mut mutator =
+                ProteinMutator::new(0.01, 0.001, 0.001, StdRng::seed_from_u64(1)).unwrap();
+            b.iter(|| black_box(mutator.mutate_seq(seq)))
+        });
+    }
+    group.finish();
+}
+
+// Drawing inserted bases with the weighted distribution built on every
+// draw, as the mutators did, against building it once, as they do now.
+fn insert_distribution(c: &mut Criterion) {
+    let gc = 0.6;
+    let at = (1.0 - gc) / 2.0;
+    let weights = [at, gc / 2.0, gc / 2.0, at];
+
+    let mut group = c.benchmark_group("insert_distribution");
+    group.bench_function("per_call", |b| {
+        let mut rng = StdRng::seed_from_u64(1);
+        b.iter(|| {
+            let dist = WeightedIndex::new(black_box(weights)).unwrap();
+            black_box(dist.sample(&mut rng))
+        })
+    });
+    group.bench_function("precomputed", |b| {
+        let mut rng = StdRng::seed_from_u64(1);
+        let dist = WeightedIndex::new(weights).unwrap();
+        b.iter(|| black_box(dist.sample(&mut rng)))
+    });
+    group.finish();
+}
+
+// Zero rates copying the sequence verbatim, against the per-base sampling
+// loop the copy skips.
+fn passthrough(c: &mut Criterion) {
+    let seq = sequence(100_000);
+
+    let mut group = c.benchmark_group("passthrough");
+    group.throughput(Throughput::Bytes(seq.len() as u64));
+    group.bench_function("verbatim", |b| {
+        let mut mutator = Mutator::new(0.0, 0.0, 0.0, StdRng::seed_from_u64(1)).unwrap();
+        b.iter(|| black_box(mutator.mutate_seq(&seq)))
+    });
+    group.bench_function("per_base", |b| {
+        let mut mutator = Mutator::new(0.0, 0.0, 0.0, StdRng::seed_from_u64(1)).unwrap();
+        b.iter(|| {
+            for &x in &seq {
+                black_box(mutator.mutate(x));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, mutate, insert_distribution, passthrough);
+criterion_main!(benches);
@@ -0,0 +1,81 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::{Distribution, Poisson};
+use sonicat::coverage::Coverage;
+use sonicat::sonicator::{window, Sonicator, Tail};
+
+const LENGTH: usize = 150;
+const DEPTH: f64 = 0.1;
+
+fn sequence(len: usize) -> Vec<u8> {
+    let mut rng = StdRng::seed_from_u64(0);
+    (0..len).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect()
+}
+
+fn sonicate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sonicate");
+    for len in [10_000, 1_000_000] {
+        let seq = sequence(len);
+        group.throughput(Throughput::Bytes(len as u64));
+
+        group.bench_with_input(BenchmarkId::new("poisson", len), &seq, |b, seq| {
+            let coverage = Coverage::poisson(DEPTH).unwrap();
+            b.iter(|| {
+                let sonicator = Sonicator::new(seq, LENGTH, &coverage, StdRng::seed_from_u64(1));
+                black_box(sonicator.count())
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("negative_binomial", len),
+            &seq,
+            |b, seq| {
+                let coverage = Coverage::negative_binomial(DEPTH, 1.0).unwrap();
+                b.iter(|| {
+                    let sonicator =
+                        Sonicator::new(seq, LENGTH, &coverage, StdRng::seed_from_u64(1));
+                    black_box(sonicator.count())
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+// Windowing with the Poisson distribution built for every start against
+// building it once per sequence.
+fn poisson(c: &mut Criterion) {
+    let seq = sequence(100_000);
+
+    let mut group = c.benchmark_group("poisson");
+    group.throughput(Throughput::Bytes(seq.len() as u64));
+    group.bench_function("per_call", |b| {
+        let mut rng = StdRng::seed_from_u64(1);
+        b.iter(|| {
+            let mut n = 0;
+            for start in 0..=seq.len() - LENGTH {
+                let dist = Poisson::new(black_box(DEPTH)).unwrap();
+                for _ in 0..dist.sample(&mut rng) as u64 {
+                    n += window(&seq, start, LENGTH, Tail::Drop).len();
+                }
+            }
+            black_box(n)
+        })
+    });
+    group.bench_function("precomputed", |b| {
+        let mut rng = StdRng::seed_from_u64(1);
+        let dist = Poisson::new(DEPTH).unwrap();
+        b.iter(|| {
+            let mut n = 0;
+            for start in 0..=seq.len() - LENGTH {
+                for _ in 0..dist.sample(&mut rng) as u64 {
+                    n += window(&seq, start, LENGTH, Tail::Drop).len();
+                }
+            }
+            black_box(n)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, sonicate, poisson);
+criterion_main!(benches);
@@ -0,0 +1,121 @@
+//! Arbitrary bytes through the FASTA reader and the mutators, as muta runs
+//! them, shared by the fuzz target and the replay of its corpus by the
+//! tests of sonicat.
+//!
+//! The first bytes of the input set the parameters of the mutator, the rest
+//! is read as FASTA: rates, inserted GC content, composition window, the
+//! options of the mutator and the way it mutates, then the records.
+
+use bio::io::fasta;
+use rand::{rngs::StdRng, SeedableRng};
+use sonicat::alphabet;
+use sonicat::coords::CoordinateMap;
+use sonicat::mutator::{DeletionMode, Mutator};
+use sonicat::protein::ProteinMutator;
+use sonicat::vcf;
+
+pub const HEADER: usize = 7;
+
+pub fn run(data: &[u8]) {
+    if data.len() < HEADER {
+        return;
+    }
+    let (header, fasta) = data.split_at(HEADER);
+    // each rate within [0, 1/3] so that they never sum to more than 1
+    let [s, i, d] = [header[0], header[1], header[2]].map(|x| f64::from(x) / 255.0 / 3.0);
+    let gc = f64::from(header[3]) / 255.0;
+    let window = usize::from(header[4]);
+    let options = header[5];
+    let protein = options & 1 != 0;
+    // the low bits pick the path of muta, the next one the alignment mode
+    // and the rest the spacing of the positions of --num-mutations
+    let path = header[6] & 3;
+    let alignment_mode = header[6] & 4 != 0;
+    let spacing = usize::from(header[6] >> 3) + 1;
+
+    for record in fasta::Reader::new(fasta).records() {
+        // malformed records are reported by the reader, not fuzzed further
+        let record = match record {
+            Ok(x) => x,
+            Err(_) => return,
+        };
+        let valid = if protein {
+            alphabet::is_valid_protein
+        } else {
+            alphabet::is_valid
+        };
+        let seq: Vec<u8> = record.seq().iter().copied().filter(|&x| valid(x)).collect();
+
+        let rng = StdRng::seed_from_u64(u64::from(options));
+        let mode = if options & 2 != 0 {
+            DeletionMode::Mask
+        } else {
+            DeletionMode::Remove
+        };
+        let (mutated, edits) = if protein {
+            let mut m = ProteinMutator::new(s, i, d, rng).unwrap();
+            m.set_saturate(options & 4 != 0);
+            m.set_preserve_case(options & 8 != 0);
+            m.set_deletion_mode(mode);
+            m.set_alignment_mode(alignment_mode);
+            m.set_record_edits(true);
+            (m.mutate_seq(&seq), m.take_edits())
+        } else {
+            let mut m = Mutator::new(s, i, d, rng).unwrap();
+            m.set_saturate(options & 4 != 0);
+            m.set_preserve_case(options & 8 != 0);
+            m.set_deletion_mode(mode);
+            m.set_alignment_mode(alignment_mode);
+            if options & 16 != 0 {
+                m.set_insert_gc(gc).unwrap();
+            }
+            if options & 32 != 0 && window > 0 {
+                m.set_insert_window(window).unwrap();
+            }
+            m.set_record_edits(true);
+            let mutated = match path {
+                // a rate track and a mask of the bases to mutate, as
+                // --density-track and --gff give, drawn from the bases
+                1 => {
+                    let track: Vec<f64> = seq.iter().map(|&x| f64::from(x % 5) / 2.0).collect();
+                    let within: Vec<bool> = seq.iter().map(|&x| x % 3 != 0).collect();
+                    m.mutate_seq_annotated(&seq, Some(&track), Some(&within), None)
+                }
+                // an error with all rates zero, which muta reports, and
+                // otherwise an edit at every position
+                2 => {
+                    let at: Vec<usize> = (0..seq.len())
+                        .step_by(spacing)
+                        .filter(|&x| !alphabet::is_gap(seq[x]))
+                        .collect();
+                    match m.mutate_seq_at(&seq, &at) {
+                        Ok(x) => {
+                            let stats = m.stats();
+                            let made = stats.substitutions + stats.insertions + stats.deletions;
+                            assert_eq!(made, at.len() as u64);
+                            x
+                        }
+                        Err(_) => {
+                            assert_eq!(s + i + d, 0.0);
+                            continue;
+                        }
+                    }
+                }
+                _ => m.mutate_seq(&seq),
+            };
+            (mutated, m.take_edits())
+        };
+
+        // replaying the edits must reproduce the mutated sequence, as
+        // muta --verify checks
+        let (replayed, applied) = vcf::apply(&seq, &edits);
+        assert_eq!(applied, edits.len());
+        assert_eq!(replayed, mutated);
+        let map = CoordinateMap::from_edits(&edits);
+        for pos in 0..seq.len() {
+            if let Some(x) = map.map_original_to_mutated(pos) {
+                assert!(x < mutated.len());
+            }
+        }
+    }
+}
@@ -0,0 +1,11 @@
+//! Arbitrary bytes through the FASTA reader and the mutators, see
+//! `harness.rs`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "harness.rs"]
+mod harness;
+
+fuzz_target!(|data: &[u8]| harness::run(data));
@@ -0,0 +1,281 @@
+//! Nucleotide alphabet shared by the tools.
+//!
+//! Symbols are accepted in either case; `U` is read as `T` so that RNA
+//! records go through the same tables as DNA.
+
+use crate::error::SonicatError;
+use std::str::FromStr;
+
+/// Alphabet of the records of an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alphabet {
+    /// Nucleotides, DNA or RNA.
+    Dna,
+    Protein,
+}
+
+impl FromStr for Alphabet {
+    type Err = SonicatError;
+
+    fn from_str(s: &str) -> Result<Alphabet, SonicatError> {
+        match s {
+            "dna" => Ok(Alphabet::Dna),
+            "protein" => Ok(Alphabet::Protein),
+            _ => Err(SonicatError::Parameter(format!("unknown alphabet {}", s))),
+        }
+    }
+}
+
+/// A concrete nucleotide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Base {
+    A,
+    C,
+    G,
+    T,
+}
+
+impl Base {
+    pub const ALL: [Base; 4] = [Base::A, Base::C, Base::G, Base::T];
+
+    /// Parse a concrete nucleotide, `None` for ambiguity codes, gaps and
+    /// anything else.
+    pub fn from_u8(b: u8) -> Option<Base> {
+        match normalize(b) {
+            b'A' => Some(Base::A),
+            b'C' => Some(Base::C),
+            b'G' => Some(Base::G),
+            b'T' => Some(Base::T),
+            _ => None,
+        }
+    }
+
+    /// Uppercase DNA symbol of the base.
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Base::A => b'A',
+            Base::C => b'C',
+            Base::G => b'G',
+            Base::T => b'T',
+        }
+    }
+
+    pub fn complement(self) -> Base {
+        match self {
+            Base::A => Base::T,
+            Base::C => Base::G,
+            Base::G => Base::C,
+            Base::T => Base::A,
+        }
+    }
+}
+
+const fn normalize_table() -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let b = (i as u8).to_ascii_uppercase();
+        table[i] = if b == b'U' { b'T' } else { b };
+        i += 1;
+    }
+    table
+}
+
+// uppercase, with U read as T
+static NORMALIZE: [u8; 256] = normalize_table();
+
+/// Uppercase a symbol and read `U` as `T`.
+pub fn normalize(b: u8) -> u8 {
+    NORMALIZE[b as usize]
+}
+
+/// Concrete bases an IUPAC nucleotide code stands for, empty if `b` is not
+/// a nucleotide code.
+pub fn iupac_bases(b: u8) -> &'static [Base] {
+    use Base::*;
+    match normalize(b) {
+        b'A' => &[A],
+        b'C' => &[C],
+        b'G' => &[G],
+        b'T' => &[T],
+        b'R' => &[A, G],
+        b'Y' => &[C, T],
+        b'S' => &[C, G],
+        b'W' => &[A, T],
+        b'K' => &[G, T],
+        b'M' => &[A, C],
+        b'B' => &[C, G, T],
+        b'D' => &[A, G, T],
+        b'H' => &[A, C, T],
+        b'V' => &[A, C, G],
+        b'N' => &[A, C, G, T],
+        _ => &[],
+    }
+}
+
+/// IUPAC nucleotide code standing for more than one base.
+pub fn is_ambiguous(b: u8) -> bool {
+    iupac_bases(b).len() > 1
+}
+
+pub fn is_n(b: u8) -> bool {
+    normalize(b) == b'N'
+}
+
+pub fn is_gap(b: u8) -> bool {
+    b == b'-'
+}
+
+/// Symbol allowed in an input sequence: an IUPAC nucleotide code, in
+/// either case and including `U`, or a gap.
+pub fn is_valid(b: u8) -> bool {
+    !iupac_bases(b).is_empty() || is_gap(b)
+}
+
+/// Symbol allowed in a protein sequence: an IUPAC amino acid code, in
+/// either case, a stop or a gap.
+pub fn is_valid_protein(b: u8) -> bool {
+    b"ABCDEFGHIKLMNPQRSTVWXYZ".contains(&b.to_ascii_uppercase()) || b == b'*' || is_gap(b)
+}
+
+/// Sequence written with `U` instead of `T`.
+pub fn is_rna(seq: &[u8]) -> bool {
+    let mut rna = false;
+    for b in seq {
+        match b {
+            b'T' | b't' => return false,
+            b'U' | b'u' => rna = true,
+            _ => (),
+        }
+    }
+    rna
+}
+
+/// Rewrite `T` as `U` in place, keeping the case.
+pub fn to_rna(seq: &mut [u8]) {
+    for b in seq.iter_mut() {
+        match b {
+            b'T' => *b = b'U',
+            b't' => *b = b'u',
+            _ => (),
+        }
+    }
+}
+
+const fn complement_table() -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = i as u8;
+        i += 1;
+    }
+    let from = b"ACGTURYSWKMBDHVN";
+    let to = b"TGCAAYRSWMKVHDBN";
+    let mut i = 0;
+    while i < from.len() {
+        table[from[i] as usize] = to[i];
+        table[from[i].to_ascii_lowercase() as usize] = to[i].to_ascii_lowercase();
+        i += 1;
+    }
+    table
+}
+
+// IUPAC complements in either case, other symbols unchanged
+static COMPLEMENT: [u8; 256] = complement_table();
+
+/// Complement of a nucleotide symbol, keeping the case. `U` pairs with
+/// `A`, whose complement is `T`; IUPAC codes map to the codes of the
+/// complementary bases, `N`, gaps and other symbols map to themselves.
+pub fn complement(b: u8) -> u8 {
+    COMPLEMENT[b as usize]
+}
+
+/// Reverse complement of a nucleotide sequence, see [`complement`].
+pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter().rev().map(|&b| complement(b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bases_convert_in_either_case() {
+        for b in Base::ALL {
+            assert_eq!(Base::from_u8(b.to_u8()), Some(b));
+            assert_eq!(Base::from_u8(b.to_u8().to_ascii_lowercase()), Some(b));
+            assert_eq!(b.complement().complement(), b);
+        }
+        assert_eq!(Base::from_u8(b'u'), Some(Base::T));
+        assert_eq!(Base::from_u8(b'N'), None);
+        assert_eq!(Base::from_u8(b'-'), None);
+        assert_eq!(normalize(b'u'), b'T');
+        assert_eq!(normalize(b'r'), b'R');
+    }
+
+    #[test]
+    fn iupac_complements_stand_for_the_complementary_bases() {
+        for &b in b"ACGTRYSWKMBDHVN" {
+            let mut bases: Vec<Base> = iupac_bases(b).iter().map(|x| x.complement()).collect();
+            bases.sort_by_key(|x| x.to_u8());
+            assert_eq!(iupac_bases(complement(b)), bases, "{}", b as char);
+            assert_eq!(
+                complement(b.to_ascii_lowercase()),
+                complement(b).to_ascii_lowercase()
+            );
+        }
+        assert_eq!(complement(b'U'), b'A');
+        assert_eq!(complement(b'-'), b'-');
+        assert_eq!(complement(b'*'), b'*');
+    }
+
+    #[test]
+    fn reverse_complement_keeps_case_and_gaps() {
+        assert_eq!(reverse_complement(b"AcGT-Nry"), b"ryN-ACgT");
+        assert_eq!(
+            reverse_complement(&reverse_complement(b"ACGTRYKM")),
+            b"ACGTRYKM"
+        );
+    }
+
+    #[test]
+    fn symbols_are_classified() {
+        assert!(b"ACGTUNRYacgtun-".iter().all(|&x| is_valid(x)));
+        assert!(b" *EX.".iter().all(|&x| !is_valid(x)));
+        assert!(b"MKVLW*x-".iter().all(|&x| is_valid_protein(x)));
+        assert!(!is_valid_protein(b'J'));
+        assert!(is_ambiguous(b'n') && is_ambiguous(b'R') && !is_ambiguous(b'A'));
+        assert!(is_n(b'n') && !is_n(b'A'));
+    }
+
+    #[test]
+    fn rna_is_detected_and_written() {
+        assert!(is_rna(b"ACGU"));
+        assert!(!is_rna(b"ACGT"));
+        assert!(!is_rna(b"ACGN"));
+        let mut seq = b"ACGTacgt".to_vec();
+        to_rna(&mut seq);
+        assert_eq!(seq, b"ACGUacgu");
+    }
+
+    #[test]
+    fn alphabets_parse() {
+        assert_eq!("dna".parse::<Alphabet>().unwrap(), Alphabet::Dna);
+        assert_eq!("protein".parse::<Alphabet>().unwrap(), Alphabet::Protein);
+        assert!("rna".parse::<Alphabet>().is_err());
+    }
+
+    #[test]
+    fn every_symbol_has_its_complement() {
+        let pairs: [(&[u8], &[u8]); 2] = [
+            (b"ACGTUNRYSWKMBDHV-", b"TGCAANYRSWMKVHDB-"),
+            (b"acgtunryswkmbdhv", b"tgcaanyrswmkvhdb"),
+        ];
+        for (symbols, complements) in pairs {
+            let found: Vec<u8> = symbols.iter().map(|&b| complement(b)).collect();
+            assert_eq!(found, complements);
+        }
+        assert_eq!(reverse_complement(b"AACGU"), b"ACGTT");
+        assert!(reverse_complement(b"").is_empty());
+    }
+}
@@ -1,89 +1,269 @@
 use bio::io::fasta;
-use clap::{Arg, Command};
-use rand::{
-    distributions::{Uniform, WeightedIndex},
-    rngs::ThreadRng,
-    thread_rng, Rng,
+use clap::{value_parser, Arg, Command};
+use log::{debug, error, info, warn};
+use rand::{rngs::StdRng, seq::index, thread_rng, Rng, SeedableRng};
+use rayon::prelude::*;
+use serde_json::json;
+use sonicat::alphabet::{self, Alphabet};
+use sonicat::codon::{codon_sites, CodonSite};
+use sonicat::config::{self, flag, param, MutationConfig};
+use sonicat::coords::CoordinateMap;
+use sonicat::cpg::cpg_islands;
+use sonicat::error::{OrExit, SonicatError};
+use sonicat::expected::expected_mutations;
+use sonicat::file::{
+    create_output, open_input, open_inputs, read_list, record_error, Compression, SharedOutput,
 };
-use std::fs::File;
-use std::io;
+use sonicat::gff;
+use sonicat::logging;
+use sonicat::mutator::{DeletionMode, MutationStats, Mutator, INDEL_BINS};
+use sonicat::protein::ProteinMutator;
+use sonicat::provenance;
+use sonicat::rng::{record_rng, replicate_seed};
+use sonicat::signal;
+use sonicat::validate::validate;
+use sonicat::vcf::{self, Variant};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+use std::time::Instant;
 
 // from Brodin et al. 2013, doi:10.1371/journal.pone.0070388
 const DEFAULT_SUBSTITUTION: f64 = 0.000057;
 const DEFAULT_INSERTION: f64 = 0.000069;
 const DEFAULT_DELETION: f64 = 0.0016;
 
-struct Mutator {
-    weights: [f64; 4],
-    rng: ThreadRng,
+const DEFAULT_DENSITY_WINDOW: usize = 1000;
+const DEFAULT_COMPOSITION_WINDOW: usize = 100;
+// bases mutated at a time by --streaming
+const STREAM_BLOCK: usize = 1 << 16;
+
+/// Mutator of the selected alphabet.
+enum AnyMutator {
+    Nucleotide(Box<Mutator>),
+    Protein(Box<ProteinMutator>),
 }
 
-impl Mutator {
-    fn new(s: f64, i: f64, d: f64) -> Mutator {
-        Mutator {
-            weights: [s, i, d, 1.0 - s - i - d],
-            rng: thread_rng(),
-        }
-    }
-    fn mutate(&mut self, b: u8) -> Option<u8> {
-        let dist = WeightedIndex::new(self.weights).unwrap();
-        let fate = self.rng.sample(dist);
-        match (fate, b) {
-            (0, _) => Some({
-                let dist = Uniform::from(0..3);
-                let nuc = self.rng.sample(dist);
-                match nuc {
-                    0 => b'A',
-                    1 => b'C',
-                    2 => b'G',
-                    3 => b'T',
-                    _ => b,
-                }
-            }),
-            (1, _) => None,
-            (2, b'A') => Some({
-                let dist = Uniform::from(0..2);
-                let nuc = self.rng.sample(dist);
-                match nuc {
-                    0 => b'C',
-                    1 => b'G',
-                    2 => b'T',
-                    _ => b,
-                }
-            }),
-            (2, b'C') => Some({
-                let dist = Uniform::from(0..2);
-                let nuc = self.rng.sample(dist);
-                match nuc {
-                    0 => b'A',
-                    1 => b'G',
-                    2 => b'T',
-                    _ => b,
-                }
-            }),
-            (2, b'G') => Some({
-                let dist = Uniform::from(0..2);
-                let nuc = self.rng.sample(dist);
-                match nuc {
-                    0 => b'A',
-                    1 => b'C',
-                    2 => b'T',
-                    _ => b,
-                }
-            }),
-            (2, b'T') => Some({
-                let dist = Uniform::from(0..2);
-                let nuc = self.rng.sample(dist);
-                match nuc {
-                    0 => b'A',
-                    1 => b'C',
-                    2 => b'G',
-                    _ => b,
-                }
-            }),
-            _ => Some(b),
+impl AnyMutator {
+    fn mutate_seq(
+        &mut self,
+        seq: &[u8],
+        track: Option<&[f64]>,
+        within: Option<&[bool]>,
+        sites: Option<&[Option<CodonSite>]>,
+        at: Option<&[usize]>,
+    ) -> Vec<u8> {
+        match self {
+            AnyMutator::Nucleotide(m) if at.is_some() => {
+                m.mutate_seq_at(seq, at.unwrap()).or_exit()
+            }
+            AnyMutator::Nucleotide(m) if within.is_some() || sites.is_some() => {
+                m.mutate_seq_annotated(seq, track, within, sites)
+            }
+            AnyMutator::Nucleotide(m) => match track {
+                Some(track) => m.mutate_seq_tracked(seq, track),
+                None => m.mutate_seq(seq),
+            },
+            AnyMutator::Protein(m) => m.mutate_seq(seq),
+        }
+    }
+
+    fn set_record_edits(&mut self, record: bool) {
+        match self {
+            AnyMutator::Nucleotide(m) => m.set_record_edits(record),
+            AnyMutator::Protein(m) => m.set_record_edits(record),
+        }
+    }
+
+    fn take_edits(&mut self) -> Vec<Variant> {
+        match self {
+            AnyMutator::Nucleotide(m) => m.take_edits(),
+            AnyMutator::Protein(m) => m.take_edits(),
+        }
+    }
+
+    fn start_blocks(&mut self) {
+        match self {
+            AnyMutator::Nucleotide(m) => m.start_blocks(),
+            AnyMutator::Protein(m) => m.start_blocks(),
+        }
+    }
+
+    fn end_blocks(&mut self) {
+        match self {
+            AnyMutator::Nucleotide(m) => m.end_blocks(),
+            AnyMutator::Protein(m) => m.end_blocks(),
+        }
+    }
+
+    fn stats(&self) -> &MutationStats {
+        match self {
+            AnyMutator::Nucleotide(m) => m.stats(),
+            AnyMutator::Protein(m) => m.stats(),
+        }
+    }
+}
+
+/// FASTA writer of sequences given in pieces, wrapped at `width` columns,
+/// or on a single line if 0.
+struct WrappedWriter<W: Write> {
+    inner: io::BufWriter<W>,
+    width: usize,
+    // symbols on the line in progress
+    column: usize,
+}
+
+impl<W: Write> WrappedWriter<W> {
+    fn new(inner: W, width: usize) -> WrappedWriter<W> {
+        WrappedWriter {
+            inner: io::BufWriter::new(inner),
+            width,
+            column: 0,
         }
     }
+
+    /// Start a record, writing its header.
+    fn start(&mut self, id: &str, desc: Option<&str>) -> io::Result<()> {
+        self.column = 0;
+        match desc {
+            Some(desc) => writeln!(self.inner, ">{} {}", id, desc),
+            None => writeln!(self.inner, ">{}", id),
+        }
+    }
+
+    /// Append `seq` to the sequence of the record.
+    fn write_seq(&mut self, mut seq: &[u8]) -> io::Result<()> {
+        if self.width == 0 {
+            return self.inner.write_all(seq);
+        }
+        while !seq.is_empty() {
+            if self.column == self.width {
+                self.inner.write_all(b"\n")?;
+                self.column = 0;
+            }
+            let n = seq.len().min(self.width - self.column);
+            self.inner.write_all(&seq[..n])?;
+            self.column += n;
+            seq = &seq[n..];
+        }
+        Ok(())
+    }
+
+    /// End the record.
+    fn finish(&mut self) -> io::Result<()> {
+        self.inner.write_all(b"\n")
+    }
+
+    /// Write a whole record.
+    fn write(&mut self, id: &str, desc: Option<&str>, seq: &[u8]) -> io::Result<()> {
+        self.start(id, desc)?;
+        self.write_seq(seq)?;
+        self.finish()
+    }
+
+    /// Flush the records written and return the writer under them.
+    fn into_inner(self) -> io::Result<W> {
+        self.inner
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)
+    }
+}
+
+/// Mutate `part`, the bases of the record from `start` on, given the
+/// annotations and edit positions `at` of the whole record. Returns the
+/// mutated part and its edits, at positions within the part.
+fn mutate_part(
+    mutator: &mut AnyMutator,
+    part: &[u8],
+    start: usize,
+    track: Option<&[f64]>,
+    within: Option<&[bool]>,
+    sites: Option<&[Option<CodonSite>]>,
+    at: Option<&[usize]>,
+) -> (Vec<u8>, Vec<Variant>) {
+    let end = start + part.len();
+    let track = track.map(|x| &x[start..end]);
+    let within = within.map(|x| &x[start..end]);
+    let sites = sites.map(|x| &x[start..end]);
+    let at: Option<Vec<usize>> = at.map(|x| {
+        x[x.partition_point(|&p| p < start)..x.partition_point(|&p| p < end)]
+            .iter()
+            .map(|p| p - start)
+            .collect()
+    });
+    let buf = mutator.mutate_seq(part, track, within, sites, at.as_deref());
+    (buf, mutator.take_edits())
+}
+
+/// Exit unless replaying `edits` on `seq` reproduces `mutated`, the part
+/// of copy `copy` of record `id`.
+fn verify_edits(seq: &[u8], edits: &[Variant], mutated: &[u8], id: &str, copy: usize) {
+    if !vcf::replays(seq, edits, mutated) {
+        match vcf::first_unreplayed(seq, edits, mutated) {
+            Some(i) => error!(
+                "edits of {} copy {} do not reproduce the mutated sequence, from the edit at {}",
+                id,
+                copy,
+                edits[i].pos + 1
+            ),
+            None => error!(
+                "edits of {} copy {} do not reproduce the mutated sequence, an edit is missing",
+                id, copy
+            ),
+        }
+        process::exit(1);
+    }
+}
+
+/// Nonzero bins of an indel length histogram, as `length:events` pairs.
+fn histogram(bins: &[u64; INDEL_BINS]) -> String {
+    bins.iter()
+        .enumerate()
+        .filter(|(_, &n)| n > 0)
+        .map(|(i, n)| {
+            if i + 1 == INDEL_BINS {
+                format!("{}+:{}", i + 1, n)
+            } else {
+                format!("{}:{}", i + 1, n)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Write the number of edits changing the sequence in each window of
+/// `window` bases of the mutated copy of a record of `len` bases as bedGraph
+/// lines, in the coordinates of the copy. A deletion counts in the window
+/// its neighbours are in; a copy left without bases has no window.
+fn write_density<W: Write>(
+    out: &mut W,
+    id: &str,
+    len: usize,
+    window: usize,
+    edits: &[Variant],
+) -> io::Result<()> {
+    let map = CoordinateMap::from_edits(edits);
+    let len = map.mutated_before(len);
+    let mut counts = vec![0u64; len.div_ceil(window)];
+    for edit in edits {
+        let changed = edit.alt.len() != 1
+            || alphabet::normalize(edit.alt[0]) != alphabet::normalize(edit.reference[0]);
+        if changed && len > 0 {
+            counts[map.mutated_before(edit.pos).min(len - 1) / window] += 1;
+        }
+    }
+    for (i, n) in counts.iter().enumerate() {
+        let start = i * window;
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            id,
+            start,
+            (start + window).min(len),
+            n
+        )?;
+    }
+    Ok(())
 }
 
 fn main() {
@@ -94,15 +274,77 @@ fn main() {
                 .short('i')
                 .long("in")
                 .value_name("INPUT")
-                .help("Input FASTA file, default to stdin")
+                .help("Input FASTA file, optionally gzipped, \"-\" or default to stdin")
+                .value_parser(value_parser!(PathBuf))
                 .takes_value(true),
         )
+        .arg(
+            Arg::new("input-list")
+                .long("input-list")
+                .value_name("LIST")
+                .help("File of input paths, one per line, read in order as a single input; blank lines and lines starting with # are skipped, and relative paths are relative to the directory of LIST")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true)
+                .conflicts_with("in"),
+        )
         .arg(
             Arg::new("out")
                 .short('o')
                 .long("out")
                 .value_name("OUTPUT")
-                .help("Output FASTA file, default to stdout")
+                .help("Output FASTA file, \"-\" or default to stdout")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("control")
+                .long("control")
+                .value_name("CONTROL")
+                .help("Also write the unmodified input records to CONTROL")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("density")
+                .long("density")
+                .value_name("BEDGRAPH")
+                .help("Write the number of mutations per window of every output record, in its coordinates, as a bedGraph")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("density-window")
+                .long("density-window")
+                .value_name("BASES")
+                .help(
+                    format!(
+                        "Window size of the mutation density, default to {}",
+                        DEFAULT_DENSITY_WINDOW
+                    )
+                    .as_str(),
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("record-command")
+                .long("record-command")
+                .help("Record the command line in OUTPUT.cmd")
+                .requires("out"),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .alias("overwrite")
+                .help("Overwrite existing output files"),
+        )
+        .arg(
+            Arg::new("compress")
+                .long("compress")
+                .value_name("COMPRESSION")
+                .help(
+                    "Output compression, default to the output extension (.gz, .zst, .bz2, .bgz) or none",
+                )
+                .possible_values(["none", "gzip", "zstd", "bzip2", "bgzf"])
                 .takes_value(true),
         )
         .arg(
@@ -147,42 +389,756 @@ fn main() {
                 )
                 .takes_value(true),
         )
+        .arg(
+            Arg::new("insert-gc")
+                .long("insert-gc")
+                .value_name("FRACTION")
+                .help("GC content of inserted bases, default to uniform over A/C/G/T")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("deletion-mode")
+                .long("deletion-mode")
+                .value_name("MODE")
+                .help("Deleted bases are removed, or masked by N (X for proteins) keeping the length, default to remove")
+                .possible_values(["remove", "mask"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("insert-local-composition")
+                .long("insert-local-composition")
+                .help("Draw inserted bases with the base composition around each insertion"),
+        )
+        .arg(
+            Arg::new("composition-window")
+                .long("composition-window")
+                .value_name("BASES")
+                .help(
+                    format!(
+                        "Window size of the local composition of inserted bases, default to {}",
+                        DEFAULT_COMPOSITION_WINDOW
+                    )
+                    .as_str(),
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("cpg-boost")
+                .long("cpg-boost")
+                .value_name("FACTOR")
+                .help("Factor of the substitution rate within CpG islands, default to 1")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("gff")
+                .long("gff")
+                .value_name("GFF")
+                .help("GFF3 annotation of the nucleotide input, for --only-cds and --dnds; the records annotated must hold no characters to strip, which would shift the features")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("only-cds")
+                .long("only-cds")
+                .help("Mutate only the CDS features of the GFF annotation, keeping the rest as is"),
+        )
+        .arg(
+            Arg::new("dnds")
+                .long("dnds")
+                .value_name("RATIO")
+                .help("Bias substitutions in the CDS features of the GFF annotation towards this dN/dS ratio")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("alphabet")
+                .short('a')
+                .long("alphabet")
+                .value_name("ALPHABET")
+                .help("Alphabet of the input, default to dna")
+                .possible_values(["dna", "protein"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("revcomp")
+                .long("revcomp")
+                .help("Mutate the reverse complement of every record instead of the record")
+                .conflicts_with("gff"),
+        )
+        .arg(
+            Arg::new("copies")
+                .short('c')
+                .long("copies")
+                .value_name("COPIES")
+                .help("Number of independently mutated copies per record, default to 1")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("alignment-mode")
+                .long("alignment-mode")
+                .help("Skip the gaps of aligned records, mutating their bases as they would be without the gaps; gaps are kept and excluded from the rates in any case"),
+        )
+        .arg(
+            Arg::new("num-mutations")
+                .long("num-mutations")
+                .value_name("COUNT")
+                .help("Make exactly this many edits per copy at positions drawn uniformly over the input, split between substitution, insertion and deletion by the rates; reads the whole input first")
+                .takes_value(true)
+                .conflicts_with_all(&["chunk-size", "saturate", "only-cds", "dnds", "cpg-boost"]),
+        )
+        .arg(
+            Arg::new("chunk-size")
+                .long("chunk-size")
+                .value_name("BASES")
+                .help("Mutate records in chunks of this many bases in parallel, each chunk with its own RNG")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("streaming")
+                .long("streaming")
+                .help("Mutate each copy of a record a block at a time, writing the blocks as they are mutated, so that the mutated copy is never held whole; input records are still held whole in memory, with their --cpg-boost, --only-cds and --dnds annotations, and so are the edits of a copy under --density")
+                .conflicts_with_all(&["chunk-size", "insert-local-composition"]),
+        )
+        .arg(
+            Arg::new("line-width")
+                .long("line-width")
+                .value_name("COLUMNS")
+                .help("Wrap sequences at this many columns, default to a single line per sequence")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("threads")
+                .long("threads")
+                .value_name("THREADS")
+                .help("Number of threads mutating chunks, default to the number of CPUs")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("replicate")
+                .long("replicate")
+                .value_name("INDEX")
+                .help("Replicate index, combined with the seed into the seed of the replicate")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_name("SEED")
+                .help("Master random seed, default to a random seed")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("saturate")
+                .long("saturate")
+                .help("Substitute every base by another, ignoring the mutation rates"),
+        )
+        .arg(
+            Arg::new("preserve-case")
+                .long("preserve-case")
+                .help("Substitute lowercase bases by lowercase bases"),
+        )
+        .arg(
+            Arg::new("verify")
+                .long("verify")
+                .help("Check that replaying the recorded edits reproduces every mutated sequence"),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Error on non-alphabet characters instead of stripping them"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("CONFIG")
+                .help("TOML or JSON file of parameters, overridden by command line options")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("validate")
+                .long("validate")
+                .help("Check the input for malformed records, non-alphabet characters and duplicate ids, print its length statistics and exit")
+                .conflicts_with("list-records"),
+        )
+        .arg(
+            Arg::new("list-records")
+                .long("list-records")
+                .help("Print the id and length of every input record, tab separated, and exit"),
+        )
+        .arg(
+            Arg::new("timing")
+                .long("timing")
+                .help("Print the seconds taken and bases per second of every record and in total to stderr"),
+        )
+        .arg(
+            Arg::new("stats")
+                .long("stats")
+                .help("Print requested and realized mutation rates to stderr"),
+        )
+        .arg(
+            Arg::new("stats-json")
+                .long("stats-json")
+                .value_name("FILE")
+                .help("Write the parameters, mutation counts, rates and seconds taken to FILE as JSON")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .help("Suppress diagnostics on stderr, errors are still reported"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .multiple_occurrences(true)
+                .help("Increase the verbosity of diagnostics on stderr"),
+        )
         .get_matches();
 
-    let fin: Box<dyn io::Read> = match matches.value_of("in") {
-        Some(f) => Box::new(File::open(f).unwrap()),
-        None => Box::new(io::stdin()),
+    logging::init(
+        matches.occurrences_of("verbose"),
+        matches.is_present("quiet"),
+    );
+    signal::install();
+
+    let config: MutationConfig = matches
+        .get_one::<PathBuf>("config")
+        .map_or_else(MutationConfig::default, |x| config::load(x).or_exit());
+
+    let fin = match matches.get_one::<PathBuf>("input-list") {
+        Some(list) => open_inputs(read_list(list).or_exit()),
+        None => open_input(matches.get_one::<PathBuf>("in").map(PathBuf::as_path)),
+    }
+    .or_exit();
+    let reader = fasta::Reader::from_bufread(fin);
+
+    if matches.is_present("list-records") {
+        for record in reader.records() {
+            let record = record.map_err(record_error).or_exit();
+            println!("{}\t{}", record.id(), record.seq().len());
+        }
+        return;
+    }
+
+    let alphabet = param::<String>(&matches, "alphabet", config.alphabet)
+        .map_or(Alphabet::Dna, |x| x.parse().or_exit());
+    let protein = alphabet == Alphabet::Protein;
+
+    if matches.is_present("validate") {
+        let validation = if protein {
+            validate(reader, alphabet::is_valid_protein)
+        } else {
+            validate(reader, alphabet::is_valid)
+        };
+        for problem in &validation.problems {
+            error!("{}", problem);
+        }
+        print!("{}", validation);
+        if !validation.problems.is_empty() {
+            process::exit(1);
+        }
+        return;
+    }
+
+    let compress = matches.value_of("compress").map_or_else(
+        || Compression::from_path(matches.get_one::<PathBuf>("out").map(PathBuf::as_path)),
+        |x| x.parse().or_exit(),
+    );
+    let density_window: usize =
+        param(&matches, "density-window", config.density_window).unwrap_or(DEFAULT_DENSITY_WINDOW);
+    config::positive("density window", density_window as f64).or_exit();
+
+    let substitution =
+        param(&matches, "substitution", config.substitution).unwrap_or(DEFAULT_SUBSTITUTION);
+    let insertion = param(&matches, "insertion", config.insertion).unwrap_or(DEFAULT_INSERTION);
+    let deletion = param(&matches, "deletion", config.deletion).unwrap_or(DEFAULT_DELETION);
+
+    let insert_gc: Option<f64> = param(&matches, "insert-gc", config.insert_gc);
+    let deletion_mode = param::<String>(&matches, "deletion-mode", config.deletion_mode)
+        .map_or(DeletionMode::Remove, |x| x.parse().or_exit());
+    let local_composition = flag(
+        &matches,
+        "insert-local-composition",
+        config.insert_local_composition,
+    );
+    let composition_window: usize =
+        param(&matches, "composition-window", config.composition_window)
+            .unwrap_or(DEFAULT_COMPOSITION_WINDOW);
+    let cpg_boost: Option<f64> = param(&matches, "cpg-boost", config.cpg_boost)
+        .map(|x| config::non_negative("CpG boost", x).or_exit());
+    let only_cds = flag(&matches, "only-cds", config.only_cds);
+    let dnds: Option<f64> = param(&matches, "dnds", config.dnds);
+    let gff: Option<PathBuf> = matches.get_one::<PathBuf>("gff").cloned().or(config.gff);
+    // options of nucleotide records alone
+    for (name, set) in [
+        ("gff", gff.is_some()),
+        ("only-cds", only_cds),
+        ("dnds", dnds.is_some()),
+        ("cpg-boost", cpg_boost.is_some()),
+    ] {
+        if protein && set {
+            Err(SonicatError::Parameter(format!(
+                "--{} requires nucleotide records",
+                name
+            )))
+            .or_exit()
+        }
+    }
+    let cds = (only_cds || dnds.is_some()).then(|| {
+        let gff = gff
+            .as_ref()
+            .ok_or_else(|| {
+                SonicatError::Parameter(
+                    "--only-cds and --dnds require a GFF annotation given by --gff".to_string(),
+                )
+            })
+            .or_exit();
+        gff::read_cds(open_input(Some(gff)).or_exit()).or_exit()
+    });
+    let copies: usize = param(&matches, "copies", config.copies).unwrap_or(1);
+    let revcomp = flag(&matches, "revcomp", config.revcomp);
+    config::exclusive(("revcomp", revcomp), ("gff", gff.is_some())).or_exit();
+    let strict = flag(&matches, "strict", config.strict);
+    let verify = flag(&matches, "verify", config.verify);
+    let saturate = flag(&matches, "saturate", config.saturate);
+    let preserve_case = flag(&matches, "preserve-case", config.preserve_case);
+    let chunk_size: Option<usize> = param(&matches, "chunk-size", config.chunk_size);
+    let streaming = flag(&matches, "streaming", config.streaming);
+    for other in [
+        ("chunk-size", chunk_size.is_some()),
+        // the composition around an insertion may lie in another block
+        ("insert-local-composition", local_composition),
+    ] {
+        config::exclusive(("streaming", streaming), other).or_exit();
+    }
+    let alignment_mode = flag(&matches, "alignment-mode", config.alignment_mode);
+    let num_mutations: Option<usize> = param(&matches, "num-mutations", config.num_mutations);
+    for other in [
+        ("chunk-size", chunk_size.is_some()),
+        ("saturate", saturate),
+        ("only-cds", only_cds),
+        ("dnds", dnds.is_some()),
+        ("cpg-boost", cpg_boost.is_some()),
+    ] {
+        config::exclusive(("num-mutations", num_mutations.is_some()), other).or_exit();
+    }
+    if num_mutations.is_some() && (protein || substitution + insertion + deletion == 0.0) {
+        Err(SonicatError::Parameter(
+            "--num-mutations requires nucleotide records and a positive mutation rate".to_string(),
+        ))
+        .or_exit()
+    }
+    if let Some(threads) = param(&matches, "threads", config.threads) {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .unwrap();
+    }
+    let seed = param(&matches, "seed", config.seed).unwrap_or_else(|| thread_rng().gen());
+    let seed = match param(&matches, "replicate", config.replicate) {
+        Some(replicate) => replicate_seed(seed, replicate),
+        None => seed,
     };
-    let reader = fasta::Reader::new(fin);
 
-    let fout: Box<dyn io::Write> = match matches.value_of("out") {
-        Some(f) => Box::new(File::create(f).unwrap()),
-        None => Box::new(io::stdout()),
+    // edits are recorded to be verified or counted
+    let record_edits = verify || matches.get_one::<PathBuf>("density").is_some();
+    let new_mutator = |rng| -> Result<AnyMutator, SonicatError> {
+        let mut mutator = if protein {
+            let mut m = ProteinMutator::new(substitution, insertion, deletion, rng)?;
+            m.set_saturate(saturate);
+            m.set_preserve_case(preserve_case);
+            m.set_deletion_mode(deletion_mode);
+            m.set_alignment_mode(alignment_mode);
+            AnyMutator::Protein(Box::new(m))
+        } else {
+            let mut m = Mutator::new(substitution, insertion, deletion, rng)?;
+            m.set_saturate(saturate);
+            m.set_preserve_case(preserve_case);
+            m.set_deletion_mode(deletion_mode);
+            m.set_alignment_mode(alignment_mode);
+            if let Some(gc) = insert_gc {
+                m.set_insert_gc(gc)?;
+            }
+            if local_composition {
+                m.set_insert_window(composition_window)?;
+            }
+            if let Some(ratio) = dnds {
+                m.set_dnds(ratio)?;
+            }
+            AnyMutator::Nucleotide(Box::new(m))
+        };
+        mutator.set_record_edits(record_edits);
+        Ok(mutator)
     };
-    let mut writer = fasta::Writer::new(fout);
+    // the parameters are checked once, before any record is read
+    new_mutator(StdRng::seed_from_u64(seed)).or_exit();
 
-    let substitution = matches
-        .value_of("substitution")
-        .map_or(DEFAULT_SUBSTITUTION, |x| x.parse().unwrap());
-    let insertion = matches
-        .value_of("insertion")
-        .map_or(DEFAULT_INSERTION, |x| x.parse().unwrap());
-    let deletion = matches
-        .value_of("deletion")
-        .map_or(DEFAULT_DELETION, |x| x.parse().unwrap());
+    let line_width: usize = param(&matches, "line-width", config.line_width).unwrap_or(0);
 
-    let mut mutator = Mutator::new(substitution, insertion, deletion);
+    // outputs are created once all parameters are checked, leaving no file
+    // behind on a bad one
+    let fout = create_output(
+        matches.get_one::<PathBuf>("out").map(PathBuf::as_path),
+        compress,
+        matches.is_present("force"),
+    )
+    .or_exit();
+    let mut writer = WrappedWriter::new(fout, line_width);
+    if matches.is_present("record-command") {
+        let out = matches.get_one::<PathBuf>("out").unwrap();
+        if out.as_os_str() == "-" {
+            warn!("no output file to record the command line next to");
+        } else {
+            let mut path = out.clone().into_os_string();
+            path.push(".cmd");
+            provenance::write_command(&PathBuf::from(path), "muta", matches.is_present("force"))
+                .or_exit();
+        }
+    }
+    let mut control = matches.get_one::<PathBuf>("control").map(|x| {
+        let fout = create_output(
+            Some(x),
+            Compression::from_path(Some(x)),
+            matches.is_present("force"),
+        );
+        // the writer takes its output, kept to finish it
+        let fout = SharedOutput::new(fout.or_exit());
+        (fasta::Writer::new(fout.clone()), fout)
+    });
+    let mut density = matches.get_one::<PathBuf>("density").map(|x| {
+        let mut fout = create_output(
+            Some(x),
+            Compression::from_path(Some(x)),
+            matches.is_present("force"),
+        )
+        .or_exit();
+        writeln!(fout, "track type=bedGraph").or_exit();
+        fout
+    });
 
-    for record in reader.records() {
-        let record = record.unwrap();
+    let timing = matches.is_present("timing") && !matches.is_present("quiet");
+    if timing {
+        eprintln!("record\tbases\tseconds\tbases/s");
+    }
+    // records and bases processed
+    let mut records: u64 = 0;
+    let mut input_bases: u64 = 0;
+    let started = Instant::now();
+    let mut stripped = 0;
+    let mut stats = MutationStats::default();
+
+    // positions of the edits of each copy, over the input stripped to the
+    // alphabet and read in full first
+    let (records_in, targets): (Box<dyn Iterator<Item = io::Result<fasta::Record>>>, _) =
+        match num_mutations {
+            Some(n) => {
+                let input: Vec<fasta::Record> = reader
+                    .records()
+                    .map(|x| x.map_err(record_error).or_exit())
+                    .collect();
+                let total = input
+                    .iter()
+                    .map(|x| {
+                        x.seq()
+                            .iter()
+                            .filter(|&&b| alphabet::is_valid(b) && !alphabet::is_gap(b))
+                            .count()
+                    })
+                    .sum();
+                if n > total {
+                    Err(SonicatError::Parameter(format!(
+                        "{} mutations requested in {} bases",
+                        n, total
+                    )))
+                    .or_exit()
+                }
+                let mut rng = StdRng::seed_from_u64(seed);
+                let targets: Vec<Vec<usize>> = (0..copies)
+                    .map(|_| {
+                        let mut x = index::sample(&mut rng, total, n).into_vec();
+                        x.sort_unstable();
+                        x
+                    })
+                    .collect();
+                (Box::new(input.into_iter().map(Ok)), Some(targets))
+            }
+            None => (Box::new(reader.records()), None),
+        };
+    // first position of the record among the bases of the stripped input
+    let mut offset = 0;
 
-        let mut buf = Vec::with_capacity(record.seq().len() * 2);
+    for record in records_in {
+        if signal::interrupted() {
+            break;
+        }
+        let record = record.map_err(record_error).or_exit();
+        let record_started = Instant::now();
+        if let Some((control, _)) = &mut control {
+            control.write_record(&record).or_exit();
+        }
+        let mut seq = Vec::with_capacity(record.seq().len());
+        let stripped_before = stripped;
 
         for r in record.seq().iter() {
-            if let Some(x) = mutator.mutate(*r) {
-                buf.push(x);
+            let valid = if protein {
+                alphabet::is_valid_protein(*r)
+            } else {
+                alphabet::is_valid(*r)
+            };
+            if !valid {
+                if strict {
+                    Err(SonicatError::Alphabet {
+                        id: record.id().to_string(),
+                        symbol: *r,
+                    })
+                    .or_exit()
+                }
+                stripped += 1;
+                continue;
             }
+            seq.push(*r);
+        }
+
+        let rna = !protein && alphabet::is_rna(&seq);
+        if revcomp && !protein {
+            seq = alphabet::reverse_complement(&seq);
+        }
+        // per-base substitution factors
+        let track: Option<Vec<f64>> = cpg_boost.map(|f| {
+            cpg_islands(&seq)
+                .into_iter()
+                .map(|x| if x { f } else { 1.0 })
+                .collect()
+        });
+        // CDS features of the record
+        let features = cds
+            .as_ref()
+            .map(|x| x.get(record.id()).map_or(&[][..], Vec::as_slice));
+        // the features are placed on the record as read
+        if features.is_some_and(|x| !x.is_empty()) && stripped > stripped_before {
+            Err(SonicatError::Parse(format!(
+                "{} non-alphabet characters stripped from {} would shift its GFF features",
+                stripped - stripped_before,
+                record.id()
+            )))
+            .or_exit()
         }
-        writer.write(record.id(), record.desc(), &buf).unwrap();
+        // bases mutated, all unless restricted to coding regions
+        let within: Option<Vec<bool>> = features
+            .filter(|_| only_cds)
+            .map(|x| gff::mask(seq.len(), x));
+        let sites: Option<Vec<Option<CodonSite>>> = features
+            .filter(|_| dnds.is_some())
+            .map(|x| codon_sites(&seq, x));
+
+        // positions of the bases, gaps aside, edited by --num-mutations
+        let bases: Vec<usize> = if targets.is_some() {
+            (0..seq.len())
+                .filter(|&i| !alphabet::is_gap(seq[i]))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        // chunks mutated in parallel, each by its own mutator
+        let chunk = chunk_size.unwrap_or(seq.len()).max(1);
+        let mut mutators: Vec<AnyMutator> = if chunk_size.is_some() {
+            (0..seq.len().div_ceil(chunk))
+                .map(|i| {
+                    new_mutator(record_rng(seed, &format!("{}\t{}", record.id(), i))).or_exit()
+                })
+                .collect()
+        } else {
+            vec![new_mutator(record_rng(seed, record.id())).or_exit()]
+        };
+
+        // copies share the RNGs of the record, each drawing from where the last stopped
+        for copy in 1..=copies {
+            // positions of the edits within the record
+            let at: Option<Vec<usize>> = targets.as_ref().map(|x| {
+                let x = &x[copy - 1];
+                let range = x.partition_point(|&p| p < offset)
+                    ..x.partition_point(|&p| p < offset + bases.len());
+                x[range].iter().map(|p| bases[p - offset]).collect()
+            });
+            let id = if copies == 1 {
+                record.id().to_string()
+            } else {
+                format!("{}_copy{}", record.id(), copy)
+            };
+            let mut edits = Vec::new();
+
+            if streaming {
+                // each block written once mutated, from the same mutator
+                // and random stream as the whole record would be, with the
+                // deletions across blocks counted as the whole record's
+                writer.start(&id, record.desc()).or_exit();
+                mutators[0].start_blocks();
+                for (i, part) in seq.chunks(STREAM_BLOCK).enumerate() {
+                    let start = i * STREAM_BLOCK;
+                    let (mut buf, mut e) = mutate_part(
+                        &mut mutators[0],
+                        part,
+                        start,
+                        track.as_deref(),
+                        within.as_deref(),
+                        sites.as_deref(),
+                        at.as_deref(),
+                    );
+                    if verify {
+                        verify_edits(part, &e, &buf, record.id(), copy);
+                    }
+                    if rna {
+                        alphabet::to_rna(&mut buf);
+                    }
+                    writer.write_seq(&buf).or_exit();
+                    // the edits of the whole copy are only kept to be counted
+                    if density.is_some() {
+                        for edit in e.iter_mut() {
+                            edit.pos += start;
+                        }
+                        edits.extend(e);
+                    }
+                }
+                mutators[0].end_blocks();
+                writer.finish().or_exit();
+            } else {
+                let chunks: Vec<(Vec<u8>, Vec<Variant>)> = mutators
+                    .par_iter_mut()
+                    .zip(seq.par_chunks(chunk))
+                    .enumerate()
+                    .map(|(i, (mutator, part))| {
+                        let start = i * chunk;
+                        let (buf, mut edits) = mutate_part(
+                            mutator,
+                            part,
+                            start,
+                            track.as_deref(),
+                            within.as_deref(),
+                            sites.as_deref(),
+                            at.as_deref(),
+                        );
+                        for edit in edits.iter_mut() {
+                            edit.pos += start;
+                        }
+                        (buf, edits)
+                    })
+                    .collect();
+                let mut buf = Vec::with_capacity(seq.len());
+                for (b, e) in chunks {
+                    buf.extend(b);
+                    edits.extend(e);
+                }
+
+                if verify {
+                    // replaying the edits on the original must reproduce the copy
+                    verify_edits(&seq, &edits, &buf, record.id(), copy);
+                }
+                if rna {
+                    alphabet::to_rna(&mut buf);
+                }
+                writer.write(&id, record.desc(), &buf).or_exit();
+            }
+            if let Some(density) = &mut density {
+                write_density(density, &id, seq.len(), density_window, &edits).or_exit();
+            }
+        }
+
+        for mutator in &mutators {
+            stats.merge(mutator.stats());
+        }
+        debug!(
+            "mutated {} ({} bases) in {:.2?}",
+            record.id(),
+            seq.len(),
+            record_started.elapsed()
+        );
+        if timing {
+            logging::report_timing(record.id(), seq.len() as u64, record_started.elapsed());
+        }
+        records += 1;
+        input_bases += seq.len() as u64;
+        offset += bases.len();
+    }
+
+    writer.into_inner().and_then(|mut x| x.finish()).or_exit();
+    if let Some((mut w, fout)) = control {
+        w.flush().and_then(|_| fout.finish()).or_exit();
+    }
+    if let Some(mut fout) = density {
+        fout.finish().or_exit();
+    }
+
+    if stripped > 0 {
+        warn!("stripped {} non-alphabet characters", stripped);
+    }
+    info!("mutated {} bases in {:.2?}", stats.bases, started.elapsed());
+    if timing {
+        logging::report_timing("total", input_bases, started.elapsed());
+    }
+
+    if matches.is_present("stats") && !matches.is_present("quiet") {
+        let realized = stats.rates();
+        eprintln!("\trequested\trealized");
+        eprintln!("substitution\t{}\t{}", substitution, realized[0]);
+        eprintln!("insertion\t{}\t{}", insertion, realized[1]);
+        eprintln!("deletion\t{}\t{}", deletion, realized[2]);
+        // set by the rates alone, not where only some bases are mutated or
+        // at other rates
+        if !(saturate || only_cds || cpg_boost.is_some() || dnds.is_some()) {
+            let requested = match num_mutations {
+                Some(n) => (n * copies) as f64,
+                None => {
+                    expected_mutations(stats.bases as usize, substitution + insertion + deletion)
+                }
+            };
+            eprintln!(
+                "mutations\t{:.1}\t{}",
+                requested,
+                stats.substitutions + stats.insertions + stats.deletions
+            );
+        }
+        eprintln!("insertion lengths\t{}", histogram(&stats.insertion_lengths));
+        eprintln!("deletion lengths\t{}", histogram(&stats.deletion_lengths));
+    }
+    if let Some(path) = matches.get_one::<PathBuf>("stats-json") {
+        let realized = stats.rates();
+        let summary = json!({
+            "parameters": {
+                "substitution": substitution,
+                "insertion": insertion,
+                "deletion": deletion,
+                "insert_gc": insert_gc,
+                "cpg_boost": cpg_boost,
+                "dnds": dnds,
+                "num_mutations": num_mutations,
+                "copies": copies,
+                "seed": seed,
+            },
+            "counts": {
+                "records": records,
+                "bases": stats.bases,
+                "substitutions": stats.substitutions,
+                "insertions": stats.insertions,
+                "deletions": stats.deletions,
+                "stripped": stripped,
+                "insertion_lengths": stats.insertion_lengths,
+                "deletion_lengths": stats.deletion_lengths,
+            },
+            "rates": {
+                "substitution": realized[0],
+                "insertion": realized[1],
+                "deletion": realized[2],
+            },
+            "seconds": started.elapsed().as_secs_f64(),
+        });
+        provenance::write_report(path, "muta", summary, matches.is_present("force")).or_exit();
+    }
+
+    if signal::interrupted() {
+        warn!("interrupted, output holds the records mutated so far");
+        process::exit(signal::INTERRUPTED_STATUS);
     }
 }
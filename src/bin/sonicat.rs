@@ -1,12 +1,435 @@
-use bio::io::fasta;
-use clap::{Arg, Command};
-use rand::{thread_rng, Rng};
-use rand_distr::Poisson;
-use std::fs::File;
-use std::io;
+use bio::io::{fasta, fastq};
+use clap::{value_parser, Arg, Command};
+use log::{debug, info, warn};
+#[cfg(feature = "bam")]
+use noodles_sam::{
+    self as sam,
+    alignment::{io::Write as _, record::Flags, RecordBuf},
+    header::record::value::{
+        map::{program, Program},
+        Map,
+    },
+};
+use rand::{distributions::WeightedIndex, thread_rng, Rng};
+use serde_json::json;
+use sonicat::alphabet;
+use sonicat::config::{self, flag, fraction, param, SonicationConfig};
+use sonicat::coverage::{Coverage, CoverageDist};
+use sonicat::difficulty::{KmerIndex, DIFFICULTY_K};
+use sonicat::error::{OrExit, SonicatError};
+use sonicat::error_model::ErrorModel;
+use sonicat::expected::expected_reads;
+use sonicat::file::{
+    self as file, create_output, finish_buffered, open_input, open_inputs, read_list, record_error,
+    Compression, Format, SharedOutput,
+};
+use sonicat::logging;
+use sonicat::mutator;
+use sonicat::provenance;
+use sonicat::quality::{QualityDist, QualityModel, MAX_PHRED};
+use sonicat::rng::{record_rng, replicate_seed};
+use sonicat::signal;
+use sonicat::sonicator::{next_read, window, Read, Sonicator, Tail};
+use sonicat::tagmentation;
+use sonicat::validate::validate;
+use sonicat::vcf;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead, Write as _};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Instant;
 
 const DEFAULT_DEPTH: f64 = 50.0;
 const DEFAULT_LENGTH: usize = 150;
+const DEFAULT_QUALITY: u8 = 40;
+const DEFAULT_DISPERSION: f64 = 2.0;
+const DEFAULT_QUALITY_SD: f64 = 5.0;
+// id of the record the input is joined into
+const CONCATENATED_ID: &str = "concatenated";
+// attempts to redraw a fragment rejected by the N filter before giving up
+const MAX_RESAMPLE: usize = 100;
+
+/// Read length and sequencing error rate of a platform. The reads are all
+/// of that length, long-read platforms lacking the spread of theirs.
+struct Preset {
+    length: usize,
+    error_rate: f64,
+}
+
+impl FromStr for Preset {
+    type Err = SonicatError;
+
+    fn from_str(platform: &str) -> Result<Preset, SonicatError> {
+        match platform {
+            // short and accurate
+            "illumina" => Ok(Preset {
+                length: 150,
+                error_rate: 0.001,
+            }),
+            // long continuous reads, error-prone
+            "pacbio" => Ok(Preset {
+                length: 10_000,
+                error_rate: 0.1,
+            }),
+            "nanopore" => Ok(Preset {
+                length: 10_000,
+                error_rate: 0.05,
+            }),
+            _ => Err(SonicatError::Parameter(format!(
+                "unknown preset {}",
+                platform
+            ))),
+        }
+    }
+}
+
+/// Expand the `{contig}`, `{start}`, `{end}` and `{strand}` placeholders of
+/// a coordinate template, for the 0-based, half-open window `start..end`.
+/// With a `base` of 1, the window is written 1-based and closed, which
+/// moves the start alone.
+fn format_coords(
+    template: &str,
+    contig: &str,
+    start: usize,
+    end: usize,
+    strand: char,
+    base: usize,
+) -> String {
+    template
+        .replace("{contig}", contig)
+        .replace("{start}", &(start + base).to_string())
+        .replace("{end}", &end.to_string())
+        .replace("{strand}", &strand.to_string())
+}
+
+/// Sequence of `record` with its `variants` applied, warning of those not
+/// matching the reference.
+fn apply_variants<'a>(
+    record: &'a fasta::Record,
+    variants: &HashMap<String, Vec<vcf::Variant>>,
+) -> Cow<'a, [u8]> {
+    match variants.get(record.id()) {
+        Some(v) => {
+            let (seq, applied) = vcf::apply(record.seq(), v);
+            if applied < v.len() {
+                warn!(
+                    "skipped {} variants of {} not matching the reference",
+                    v.len() - applied,
+                    record.id()
+                );
+            }
+            Cow::Owned(seq)
+        }
+        None => Cow::Borrowed(record.seq()),
+    }
+}
+
+/// Join the records of `reader` into a single record, `spacer` Ns between
+/// each and the `variants` of each applied first, along with the span and id
+/// of every record in the joined sequence.
+fn concatenate<B: BufRead>(
+    reader: fasta::Reader<B>,
+    spacer: usize,
+    variants: &HashMap<String, Vec<vcf::Variant>>,
+) -> (fasta::Record, Vec<(usize, usize, String)>) {
+    let mut seq = Vec::new();
+    let mut spans = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(record_error).or_exit();
+        if !spans.is_empty() {
+            seq.resize(seq.len() + spacer, b'N');
+        }
+        let record_seq = apply_variants(&record, variants);
+        spans.push((
+            seq.len(),
+            seq.len() + record_seq.len(),
+            record.id().to_string(),
+        ));
+        seq.extend_from_slice(&record_seq);
+    }
+    (
+        fasta::Record::with_attrs(CONCATENATED_ID, None, &seq),
+        spans,
+    )
+}
+
+/// Contig and coordinates in it of the source from `start` to `end` of the
+/// record `id`: in the input record holding it if `spans` are those of the
+/// records joined into `id`, in `id` if the source crosses a join.
+fn locate<'a>(
+    id: &'a str,
+    spans: &'a [(usize, usize, String)],
+    (start, end): (usize, usize),
+) -> (&'a str, (usize, usize)) {
+    match spans.iter().find(|x| x.0 <= start && end <= x.1) {
+        Some(x) => (&x.2, (start - x.0, end - x.0)),
+        None => (id, (start, end)),
+    }
+}
+
+/// Line of the --truth BED of the read `name`, drawn from `start` to `end`
+/// of `contig` on `strand`, with its annotations in a seventh column, `.`
+/// if none.
+fn truth_line(
+    contig: &str,
+    (start, end): (usize, usize),
+    name: &str,
+    strand: char,
+    marks: &[String],
+) -> String {
+    let marks = if marks.is_empty() {
+        ".".to_string()
+    } else {
+        marks.join(";")
+    };
+    format!(
+        "{}\t{}\t{}\t{}\t0\t{}\t{}",
+        contig, start, end, name, strand, marks
+    )
+}
+
+/// Value of the `key=value` field of the description of `record`, an
+/// error if it does not parse.
+fn desc_param<T: FromStr>(record: &fasta::Record, key: &str) -> Result<Option<T>, SonicatError> {
+    let value = record.desc().and_then(|x| {
+        x.split_whitespace()
+            .find_map(|x| x.strip_prefix(key)?.strip_prefix('='))
+    });
+    value
+        .map(|x| {
+            x.parse().map_err(|_| {
+                SonicatError::Parse(format!(
+                    "invalid {} {:?} in the description of {}",
+                    key,
+                    x,
+                    record.id()
+                ))
+            })
+        })
+        .transpose()
+}
+
+fn n_fraction(seq: &[u8]) -> f64 {
+    let n = seq.iter().filter(|&&b| alphabet::is_n(b)).count();
+    n as f64 / seq.len() as f64
+}
+
+/// Output writer, FASTQ records are always written as unwrapped four-line records.
+///
+/// Each holds the output it writes to, finished once the writer is.
+enum Writer {
+    Fasta(fasta::Writer<SharedOutput>, SharedOutput),
+    Fastq(fastq::Writer<SharedOutput>, SharedOutput),
+    #[cfg(feature = "bam")]
+    Bam(
+        Box<noodles_bam::io::Writer<noodles_bgzf::io::Writer<SharedOutput>>>,
+        Box<sam::Header>,
+        SharedOutput,
+    ),
+}
+
+impl Writer {
+    /// Writer of `format`, with the `command` line in the header of formats
+    /// that have one.
+    fn new(fout: file::Output, format: Format, command: Option<&str>) -> Writer {
+        let fout = SharedOutput::new(fout);
+        match format {
+            Format::Fasta => Writer::Fasta(fasta::Writer::new(fout.clone()), fout),
+            Format::Fastq => Writer::Fastq(fastq::Writer::new(fout.clone()), fout),
+            #[cfg(feature = "bam")]
+            Format::Ubam => {
+                let mut header = sam::Header::default();
+                if let Some(command) = command {
+                    let mut program = Map::<Program>::default();
+                    let fields = program.other_fields_mut();
+                    fields.insert(program::tag::NAME, "sonicat".into());
+                    fields.insert(program::tag::VERSION, provenance::VERSION.into());
+                    fields.insert(program::tag::COMMAND_LINE, command.into());
+                    header.programs_mut().add("sonicat", program).or_exit();
+                }
+                let mut w = noodles_bam::io::Writer::new(fout.clone());
+                w.write_header(&header).or_exit();
+                Writer::Bam(Box::new(w), Box::new(header), fout)
+            }
+            #[cfg(not(feature = "bam"))]
+            Format::Ubam => {
+                let _ = command;
+                unreachable!("ubam output is rejected before any output is created")
+            }
+        }
+    }
+
+    /// Write a read, its qualities unused in FASTA.
+    fn write(&mut self, read: Read) -> io::Result<()> {
+        match self {
+            Writer::Fasta(w, _) => w.write_record(&read.into()),
+            Writer::Fastq(w, _) => w.write_record(&read.into()),
+            #[cfg(feature = "bam")]
+            Writer::Bam(w, header, _) => {
+                let record = RecordBuf::builder()
+                    .set_name(read.id)
+                    .set_flags(Flags::UNMAPPED)
+                    .set_sequence(read.seq.into())
+                    .set_quality_scores(read.qual.unwrap_or_default().into())
+                    .build();
+                w.write_alignment_record(header, &record)
+            }
+        }
+    }
+
+    /// Flush the writer and finish its output.
+    fn finish(&mut self) -> io::Result<()> {
+        match self {
+            Writer::Fasta(w, fout) => {
+                w.flush()?;
+                fout.finish()
+            }
+            Writer::Fastq(w, fout) => {
+                w.flush()?;
+                fout.finish()
+            }
+            #[cfg(feature = "bam")]
+            Writer::Bam(w, _, fout) => {
+                w.try_finish()?;
+                fout.finish()
+            }
+        }
+    }
+}
+
+/// Writer of the reads, rolling over to a new part file every `reads`
+/// reads when written in parts.
+struct Output {
+    writer: Writer,
+    parts: Option<Parts>,
+    // file written to when not in parts, none for stdout
+    path: Option<PathBuf>,
+    // reads written to the current part
+    written: u64,
+    // finished part files, with their reads
+    files: Vec<(PathBuf, u64)>,
+}
+
+/// Numbered part files of an output.
+struct Parts {
+    path: PathBuf,
+    reads: u64,
+    // number of the current part, from 1
+    part: usize,
+    format: Format,
+    command: Option<String>,
+    compress: Compression,
+    force: bool,
+}
+
+impl Parts {
+    fn open(&self) -> Writer {
+        let fout = create_output(
+            Some(&part_path(&self.path, self.part)),
+            self.compress,
+            self.force,
+        )
+        .or_exit();
+        Writer::new(fout, self.format, self.command.as_deref())
+    }
+}
+
+impl Output {
+    /// Output to the file at `path`, or stdout if `None`.
+    fn new(writer: Writer, path: Option<PathBuf>) -> Output {
+        Output {
+            writer,
+            parts: None,
+            path,
+            written: 0,
+            files: Vec::new(),
+        }
+    }
+
+    /// Output in part files of at most `parts.reads` reads, the first
+    /// opened right away.
+    fn in_parts(parts: Parts) -> Output {
+        Output {
+            writer: parts.open(),
+            parts: Some(parts),
+            path: None,
+            written: 0,
+            files: Vec::new(),
+        }
+    }
+
+    fn write(&mut self, read: Read) -> io::Result<()> {
+        if let Some(parts) = &mut self.parts {
+            if self.written == parts.reads {
+                self.writer.finish()?;
+                self.files
+                    .push((part_path(&parts.path, parts.part), self.written));
+                parts.part += 1;
+                self.writer = parts.open();
+                self.written = 0;
+            }
+        }
+        self.written += 1;
+        self.writer.write(read)
+    }
+
+    /// Finish the output, returning the files written with their reads.
+    fn finish(mut self) -> io::Result<Vec<(PathBuf, u64)>> {
+        self.writer.finish()?;
+        let current = match &self.parts {
+            Some(parts) => Some(part_path(&parts.path, parts.part)),
+            None => self.path.take(),
+        };
+        self.files.extend(current.map(|x| (x, self.written)));
+        Ok(self.files)
+    }
+}
+
+/// Path of part `part` of the output `path`, numbered before the format
+/// and compression extensions: `out.fq.gz` becomes `out.part001.fq.gz`.
+fn part_path(path: &Path, part: usize) -> PathBuf {
+    let name = path
+        .file_name()
+        .map_or_else(String::new, |x| x.to_string_lossy().into_owned());
+    let (rest, compression) = Compression::split_extension(&name);
+    let compression = compression.extension();
+    let name = match rest.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => {
+            format!("{}.part{:03}.{}{}", stem, part, ext, compression)
+        }
+        _ => format!("{}.part{:03}{}", rest, part, compression),
+    };
+    path.with_file_name(name)
+}
+
+/// File name of the reads of a record, with characters other than
+/// alphanumerics, '-', '_' and inner dots replaced by '_'. A `number`
+/// above 1 tells apart the records whose names would collide, as `id-2.fa`.
+fn contig_file(id: &str, number: usize, format: Format, compress: Compression) -> String {
+    let mut name: String = id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    // no hidden files, "." or ".."
+    if name.is_empty() || name.starts_with('.') {
+        name.insert(0, '_');
+    }
+    if number > 1 {
+        name.push_str(&format!("-{}", number));
+    }
+    name.push_str(format.extension());
+    name.push_str(compress.extension());
+    name
+}
 
 fn main() {
     let matches = Command::new("Sonicat")
@@ -16,15 +439,136 @@ fn main() {
                 .short('i')
                 .long("in")
                 .value_name("INPUT")
-                .help("Input FASTA file, default to stdin")
+                .help("Input FASTA file, optionally gzipped, \"-\" or default to stdin")
+                .value_parser(value_parser!(PathBuf))
                 .takes_value(true),
         )
+        .arg(
+            Arg::new("input-list")
+                .long("input-list")
+                .value_name("LIST")
+                .help("File of input paths, one per line, read in order as a single input; blank lines and lines starting with # are skipped, and relative paths are relative to the directory of LIST")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true)
+                .conflicts_with("in"),
+        )
         .arg(
             Arg::new("out")
                 .short('o')
                 .long("out")
                 .value_name("OUTPUT")
-                .help("Output FASTA file, default to stdout")
+                .help("Output FASTA/FASTQ file, \"-\" or default to stdout")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("split-by-contig")
+                .long("split-by-contig")
+                .value_name("DIR")
+                .help("Write the reads of each record to its own file in DIR, named after the record and numbered, as ID-2.fa, when names collide")
+                .conflicts_with("out")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("chunk-reads")
+                .long("chunk-reads")
+                .value_name("READS")
+                .help("Write the output in numbered parts of this many reads, OUTPUT.part001.fq, ...")
+                .requires("out")
+                .conflicts_with("split-by-contig")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("record-command")
+                .long("record-command")
+                .help("Record the command line in OUTPUT.cmd, or sonicat.cmd in the split directory, and in the uBAM header"),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .alias("overwrite")
+                .help("Overwrite existing output files"),
+        )
+        .arg(
+            Arg::new("compress")
+                .long("compress")
+                .value_name("COMPRESSION")
+                .help("Output compression, default to the output extension (.gz, .zst, .bz2, .bgz) or none; ubam output takes none")
+                .possible_values(["none", "gzip", "zstd", "bzip2", "bgzf"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("format")
+                .short('f')
+                .long("format")
+                .value_name("FORMAT")
+                .help("Output format, fasta, fastq or ubam, default to fasta; a compression extension sets the compression, as fastq.gz; ubam requires the bam feature")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("quality")
+                .short('q')
+                .long("quality")
+                .value_name("QUALITY")
+                .help(
+                    format!(
+                        "Phred quality of FASTQ bases, default to {}",
+                        DEFAULT_QUALITY
+                    )
+                    .as_str(),
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("quality-dist")
+                .long("quality-dist")
+                .value_name("DIST")
+                .help("Distribution of base qualities around the quality, default to fixed")
+                .possible_values(["fixed", "uniform", "normal"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("quality-sd")
+                .long("quality-sd")
+                .value_name("SD")
+                .help(
+                    format!(
+                        "Standard deviation of uniform and normal base qualities, default to {}",
+                        DEFAULT_QUALITY_SD
+                    )
+                    .as_str(),
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("quality-min")
+                .long("quality-min")
+                .value_name("QUALITY")
+                .help("Lowest Phred quality of FASTQ bases, default to 0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("quality-max")
+                .long("quality-max")
+                .value_name("QUALITY")
+                .help(format!("Highest Phred quality of FASTQ bases, default to {}", MAX_PHRED).as_str())
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("vcf")
+                .long("vcf")
+                .value_name("VCF")
+                .help("VCF file of variants applied to the input before sonication")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("preset")
+                .long("preset")
+                .value_name("PLATFORM")
+                .help("Default read length and error rate of a platform, overridden by explicit options; reads keep a single length, without the spread of pacbio and nanopore lengths")
+                .possible_values(["illumina", "pacbio", "nanopore"])
                 .takes_value(true),
         )
         .arg(
@@ -43,44 +587,1128 @@ fn main() {
                 .help(format!("Average read length, default to {}", DEFAULT_LENGTH).as_str())
                 .takes_value(true),
         )
+        .arg(
+            Arg::new("step")
+                .long("step")
+                .value_name("STEP")
+                .help("Stride between windows reads start from, the depth per base falls with it, default to 1")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("concatenate")
+                .long("concatenate")
+                .help("Join all input records into one named concatenated, marking reads across joins with contigs=ID,..."),
+        )
+        .arg(
+            Arg::new("spacer")
+                .long("spacer")
+                .value_name("BASES")
+                .help("Number of Ns between the records joined by --concatenate, default to 0")
+                .requires("concatenate")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("header-params")
+                .long("header-params")
+                .help("Take the depth and length of each record from depth= and length= fields of its description"),
+        )
+        .arg(
+            Arg::new("tagmentation")
+                .long("tagmentation")
+                .help("Bias read starts towards the insertion preference of Tn5, keeping the mean depth"),
+        )
+        .arg(
+            Arg::new("coverage-dist")
+                .long("coverage-dist")
+                .value_name("DIST")
+                .help("Distribution of reads per position, default to poisson")
+                .possible_values(["poisson", "nbinom"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("dispersion")
+                .long("dispersion")
+                .value_name("DISPERSION")
+                .help(
+                    format!(
+                        "Dispersion of the negative binomial, smaller is more overdispersed, default to {}",
+                        DEFAULT_DISPERSION
+                    )
+                    .as_str(),
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("circular")
+                .long("circular")
+                .help("Treat records as circular, reads longer than a record wrap around it repeatedly; an alias of --tail wrap, any other --tail is rejected"),
+        )
+        .arg(
+            Arg::new("tail")
+                .long("tail")
+                .value_name("POLICY")
+                .help("Windows running past the end of a record are dropped, shrunk or wrapped around, default to drop; --circular stands for wrap and rejects the others")
+                .possible_values(["drop", "shrink", "wrap"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("max-n")
+                .long("max-n")
+                .value_name("FRACTION")
+                .help("Maximum fraction of N in a read, reads above are redrawn from another start, out of order unless --sorted")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("skip-masked")
+                .long("skip-masked")
+                .help("Skip reads overlapping soft-masked (lowercase) bases"),
+        )
+        .arg(
+            Arg::new("chimera-rate")
+                .long("chimera-rate")
+                .value_name("RATE")
+                .help("Fraction of reads spliced from two fragments, default to 0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("dup-rate")
+                .long("dup-rate")
+                .value_name("RATE")
+                .help("Fraction of reads that are PCR duplicates of the read before them, marked duplicate_of=NAME, default to 0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("resolve-ambiguous")
+                .long("resolve-ambiguous")
+                .help("Replace IUPAC ambiguity codes in reads by a random base they stand for, the reference is left as it is"),
+        )
+        .arg(
+            Arg::new("soft-clip")
+                .long("soft-clip")
+                .value_name("BASES")
+                .help("Replace this many bases at both ends of reads by random bases, default to 0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("error-rate")
+                .short('e')
+                .long("error-rate")
+                .value_name("RATE")
+                .help("Sequencing substitution error rate per base, default to 0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("error-rate-forward")
+                .long("error-rate-forward")
+                .value_name("RATE")
+                .help("Error rate of forward strand reads, default to the error rate; draws reads from both strands")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("error-rate-reverse")
+                .long("error-rate-reverse")
+                .value_name("RATE")
+                .help("Error rate of reverse strand reads, default to the error rate; draws reads from both strands")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("only-errored")
+                .long("only-errored")
+                .help("Only emit reads with at least one sequencing error, requires a positive error rate"),
+        )
+        .arg(
+            Arg::new("debug-pairs")
+                .long("debug-pairs")
+                .value_name("FILE")
+                .help("Write the name, forward reference window and emitted sequence of every read to FILE, tab separated; the window of a chimera is the one its 5' half is drawn from")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("error-log")
+                .long("error-log")
+                .value_name("FILE")
+                .help("Write the read name, position in the read in the --coord-base convention, original base and new base of every sequencing error to FILE, tab separated")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("truth")
+                .long("truth")
+                .value_name("BED")
+                .help("Write where every read comes from as BED, 0-based whatever --coord-base and in the input records joined by --concatenate but for reads across joins, annotated in a seventh column with the source of the 3' half of chimeras as chimera=CONTIG:START-END, the bases soft-clipped at each end as clip=BASES, the read a duplicate is of as duplicate_of=NAME and the records a read across joins spans as contigs=ID,...")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("name-prefix")
+                .long("name-prefix")
+                .value_name("PREFIX")
+                .help("Prefix of read names")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("name-suffix")
+                .long("name-suffix")
+                .value_name("SUFFIX")
+                .help("Suffix of read names")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("coord-format")
+                .long("coord-format")
+                .value_name("TEMPLATE")
+                .help("Append the source of reads to descriptions, {contig}, {start}, {end} and {strand} expanded to the window of the read in the --coord-base convention")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("coord-base")
+                .long("coord-base")
+                .value_name("BASE")
+                .help("Coordinates written by --coord-format and --error-log are 0-based with half-open windows, or 1-based with closed windows, default to 0; a window of --length bases spans end - start bases if 0-based, end - start + 1 if 1-based")
+                .possible_values(["0", "1"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("pool")
+                .long("pool")
+                .value_name("ID:FRACTION")
+                .help("Draw reads for a pooled sample at a relative abundance, tagging them sample=ID; repeat for each sample")
+                .multiple_occurrences(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("read-group")
+                .long("read-group")
+                .value_name("ID")
+                .help("Read group, embedded in read descriptions as a RG:Z: tag, the annotations then in a CO:Z: tag")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("platform")
+                .long("platform")
+                .value_name("PLATFORM")
+                .help("Sequencing platform, embedded in read descriptions as a PL:Z: tag, the annotations then in a CO:Z: tag")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("annotate-difficulty")
+                .long("annotate-difficulty")
+                .help("Annotate reads with the fraction of their k-mers repeated in the record"),
+        )
+        .arg(
+            Arg::new("sorted")
+                .long("sorted")
+                .help("Emit the reads of each record in order of their start; all of a record's reads are held in memory, about its length times the depth in bases"),
+        )
+        .arg(
+            Arg::new("max-bases")
+                .long("max-bases")
+                .value_name("BASES")
+                .help("Stop before the emitted bases exceed this many")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Exit with an error on duplicate record ids instead of warning"),
+        )
+        .arg(
+            Arg::new("replicate")
+                .long("replicate")
+                .value_name("INDEX")
+                .help("Replicate index, combined with the seed into the seed of the replicate")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_name("SEED")
+                .help("Master random seed, default to a random seed")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("CONFIG")
+                .help("TOML or JSON file of parameters, overridden by command line options")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("validate")
+                .long("validate")
+                .help("Check the input for malformed records, non-alphabet characters and duplicate ids, print its length statistics and exit")
+                .conflicts_with("list-records"),
+        )
+        .arg(
+            Arg::new("list-records")
+                .long("list-records")
+                .help("Print the id and length of every input record, tab separated, and exit"),
+        )
+        .arg(
+            Arg::new("timing")
+                .long("timing")
+                .help("Print the seconds taken and bases per second of every record and in total to stderr"),
+        )
+        .arg(
+            Arg::new("stats")
+                .long("stats")
+                .help("Print summary statistics to stderr"),
+        )
+        .arg(
+            Arg::new("manifest")
+                .long("manifest")
+                .value_name("FILE")
+                .help("Write the files written with their kinds, the reads of each file of reads, and the parameters to FILE as JSON")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("stats-json")
+                .long("stats-json")
+                .value_name("FILE")
+                .help("Write the parameters, read counts, realized depth and seconds taken to FILE as JSON")
+                .value_parser(value_parser!(PathBuf))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .help("Suppress diagnostics on stderr, errors are still reported"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .multiple_occurrences(true)
+                .help("Increase the verbosity of diagnostics on stderr"),
+        )
         .get_matches();
 
-    let fin: Box<dyn io::Read> = match matches.value_of("in") {
-        Some(f) => Box::new(File::open(f).unwrap()),
-        None => Box::new(io::stdin()),
+    logging::init(
+        matches.occurrences_of("verbose"),
+        matches.is_present("quiet"),
+    );
+    signal::install();
+
+    let config: SonicationConfig = matches
+        .get_one::<PathBuf>("config")
+        .map_or_else(SonicationConfig::default, |x| config::load(x).or_exit());
+
+    let fin = match matches.get_one::<PathBuf>("input-list") {
+        Some(list) => open_inputs(read_list(list).or_exit()),
+        None => open_input(matches.get_one::<PathBuf>("in").map(PathBuf::as_path)),
+    }
+    .or_exit();
+    let reader = fasta::Reader::from_bufread(fin);
+
+    if matches.is_present("list-records") {
+        for record in reader.records() {
+            let record = record.map_err(record_error).or_exit();
+            println!("{}\t{}", record.id(), record.seq().len());
+        }
+        return;
+    }
+
+    if matches.is_present("validate") {
+        let validation = validate(reader, alphabet::is_valid);
+        for problem in &validation.problems {
+            log::error!("{}", problem);
+        }
+        print!("{}", validation);
+        if !validation.problems.is_empty() {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let variants = matches
+        .get_one::<PathBuf>("vcf")
+        .cloned()
+        .or(config.vcf)
+        .map_or_else(HashMap::new, |x| {
+            vcf::read_variants(open_input(Some(&x)).or_exit()).or_exit()
+        });
+    let quiet = matches.is_present("quiet");
+
+    // a compression extension of the format, as in fastq.gz, sets the compression
+    let (format, format_compression) = param::<String>(&matches, "format", config.format)
+        .map_or((Format::Fasta, None), |x| {
+            Format::parse_compressed(&x).or_exit()
+        });
+    if cfg!(not(feature = "bam")) && format == Format::Ubam {
+        Err(SonicatError::Parameter(
+            "ubam output requires sonicat built with the bam feature".to_string(),
+        ))
+        .or_exit()
+    }
+    let compress = match matches.value_of("compress") {
+        Some(x) => x.parse().or_exit(),
+        None => format_compression.unwrap_or_else(|| {
+            Compression::from_path(matches.get_one::<PathBuf>("out").map(PathBuf::as_path))
+        }),
     };
-    let reader = fasta::Reader::new(fin);
+    // BAM is BGZF compressed already
+    if format == Format::Ubam && compress != Compression::None {
+        Err(SonicatError::Parameter(
+            "ubam output takes no further compression".to_string(),
+        ))
+        .or_exit()
+    }
+    let force = matches.is_present("force");
+    let quality = param(&matches, "quality", config.quality).unwrap_or(DEFAULT_QUALITY);
+    let quality_sd = param(&matches, "quality-sd", config.quality_sd).unwrap_or(DEFAULT_QUALITY_SD);
+    let quality_dist = param::<String>(&matches, "quality-dist", config.quality_dist)
+        .map_or(QualityDist::Fixed, |x| x.parse().or_exit());
+    let qualities = QualityModel::new(quality_dist, quality, quality_sd).or_exit();
+    let quality_min: u8 = param(&matches, "quality-min", config.quality_min).unwrap_or(0);
+    let quality_max: u8 = param(&matches, "quality-max", config.quality_max).unwrap_or(MAX_PHRED);
+    if quality_min > quality_max || quality_max > MAX_PHRED {
+        Err(SonicatError::Parameter(format!(
+            "quality range {}..={} out of 0..={}",
+            quality_min, quality_max, MAX_PHRED
+        )))
+        .or_exit()
+    }
 
-    let fout: Box<dyn io::Write> = match matches.value_of("out") {
-        Some(f) => Box::new(File::create(f).unwrap()),
-        None => Box::new(io::stdout()),
+    let depth = param(&matches, "depth", config.depth).unwrap_or(DEFAULT_DEPTH);
+    let preset =
+        param::<String>(&matches, "preset", config.preset).map(|x| x.parse::<Preset>().or_exit());
+    let length = param(&matches, "length", config.length)
+        .or(preset.as_ref().map(|x| x.length))
+        .unwrap_or(DEFAULT_LENGTH);
+    let circular = flag(&matches, "circular", config.circular);
+    let tail: Option<Tail> =
+        param::<String>(&matches, "tail", config.tail).map(|x| x.parse().or_exit());
+    // circular records are an alias of wrapped windows
+    let tail = match (circular, tail) {
+        (true, Some(x)) if x != Tail::Wrap => Err(SonicatError::Parameter(
+            "circular records wrap windows around, at odds with another tail policy".to_string(),
+        ))
+        .or_exit(),
+        (true, _) => Tail::Wrap,
+        (false, x) => x.unwrap_or(Tail::Drop),
+    };
+    let step: usize = param(&matches, "step", config.step).unwrap_or(1);
+    if length == 0 || step == 0 {
+        Err(SonicatError::Parameter(
+            "the read length and step must be positive".to_string(),
+        ))
+        .or_exit()
+    }
+    let max_n: Option<f64> =
+        param(&matches, "max-n", config.max_n).map(|x| fraction("N fraction", x).or_exit());
+    let skip_masked = flag(&matches, "skip-masked", config.skip_masked);
+    let name_prefix: String =
+        param(&matches, "name-prefix", config.name_prefix).unwrap_or_default();
+    let pool: Vec<(String, f64)> = matches
+        .values_of("pool")
+        .map(|x| x.map(String::from).collect())
+        .or(config.pool)
+        .unwrap_or_default()
+        .iter()
+        .map(|x| {
+            let fraction = x.rsplit_once(':').and_then(|(id, fraction)| {
+                let fraction: f64 = fraction.parse().ok()?;
+                Some((id.to_string(), fraction))
+            });
+            fraction
+                .ok_or_else(|| {
+                    SonicatError::Parameter(format!("pool sample {} is not ID:FRACTION", x))
+                })
+                .or_exit()
+        })
+        .collect();
+    let samples = (!pool.is_empty()).then(|| {
+        WeightedIndex::new(pool.iter().map(|(_, x)| *x))
+            .map_err(|e| SonicatError::Parameter(format!("invalid pool fractions: {}", e)))
+            .or_exit()
+    });
+    let coord_format: Option<String> = param(&matches, "coord-format", config.coord_format);
+    let coord_base: usize = param(&matches, "coord-base", config.coord_base).unwrap_or(0);
+    if coord_base > 1 {
+        Err(SonicatError::Parameter(format!(
+            "coordinate base {} is neither 0 nor 1",
+            coord_base
+        )))
+        .or_exit()
+    }
+    let name_suffix: String =
+        param(&matches, "name-suffix", config.name_suffix).unwrap_or_default();
+    // SAM tags of every read, making up its description with the annotations
+    // in a CO:Z: tag so that `bwa mem -C` passes on valid SAM fields
+    let tags: Vec<String> = [
+        param::<String>(&matches, "read-group", config.read_group).map(|x| format!("RG:Z:{}", x)),
+        param::<String>(&matches, "platform", config.platform).map(|x| format!("PL:Z:{}", x)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let resolve_ambiguous = flag(&matches, "resolve-ambiguous", config.resolve_ambiguous);
+    let soft_clip: usize = param(&matches, "soft-clip", config.soft_clip).unwrap_or(0);
+    let error_rate = param(&matches, "error-rate", config.error_rate)
+        .or(preset.as_ref().map(|x| x.error_rate))
+        .unwrap_or(0.0);
+    let forward_rate: Option<f64> =
+        param(&matches, "error-rate-forward", config.error_rate_forward);
+    let reverse_rate: Option<f64> =
+        param(&matches, "error-rate-reverse", config.error_rate_reverse);
+    // reads are drawn from both strands once the strands may differ
+    let stranded = forward_rate.is_some() || reverse_rate.is_some();
+    let [forward_rate, reverse_rate] =
+        [forward_rate, reverse_rate].map(|x| x.unwrap_or(error_rate));
+    // error models of the forward and reverse strands
+    let models = [forward_rate, reverse_rate].map(|x| ErrorModel::new(x).or_exit());
+    let errors = (forward_rate > 0.0 || reverse_rate > 0.0).then_some(models);
+    let only_errored = flag(&matches, "only-errored", config.only_errored);
+    if only_errored && errors.is_none() {
+        Err(SonicatError::Parameter(
+            "--only-errored requires a positive error rate".to_string(),
+        ))
+        .or_exit()
+    }
+    let annotate_difficulty = flag(&matches, "annotate-difficulty", config.annotate_difficulty);
+    let chimera_rate = fraction(
+        "chimera rate",
+        param(&matches, "chimera-rate", config.chimera_rate).unwrap_or(0.0),
+    )
+    .or_exit();
+    let dup_rate = param(&matches, "dup-rate", config.dup_rate).unwrap_or(0.0);
+    if !(0.0..1.0).contains(&dup_rate) {
+        Err(SonicatError::Parameter(format!(
+            "duplicate rate {} out of [0, 1)",
+            dup_rate
+        )))
+        .or_exit()
+    }
+    let strict = flag(&matches, "strict", config.strict);
+    let sorted = flag(&matches, "sorted", config.sorted);
+    let max_bases: Option<u64> = param(&matches, "max-bases", config.max_bases);
+    let seed = param(&matches, "seed", config.seed).unwrap_or_else(|| thread_rng().gen());
+    let seed = match param(&matches, "replicate", config.replicate) {
+        Some(replicate) => replicate_seed(seed, replicate),
+        None => seed,
     };
-    let mut writer = fasta::Writer::new(fout);
 
-    let depth = matches
-        .value_of("depth")
-        .map_or(DEFAULT_DEPTH, |x| x.parse().unwrap());
-    let length = matches
-        .value_of("length")
-        .map_or(DEFAULT_LENGTH, |x| x.parse().unwrap());
+    let dispersion = param(&matches, "dispersion", config.dispersion).unwrap_or(DEFAULT_DISPERSION);
+    let coverage_dist = param::<String>(&matches, "coverage-dist", config.coverage_dist)
+        .map_or(CoverageDist::Poisson, |x| x.parse().or_exit());
+    let new_coverage = |depth| Coverage::new(coverage_dist, depth, dispersion);
+    let coverage = new_coverage(depth).or_exit();
+    let header_params = flag(&matches, "header-params", config.header_params);
+    let tagmentation = flag(&matches, "tagmentation", config.tagmentation);
 
-    let poi = Poisson::new(depth).unwrap();
+    let concatenated = flag(&matches, "concatenate", config.concatenate);
+    let spacer: Option<usize> = param(&matches, "spacer", config.spacer);
+    if spacer.is_some() && !concatenated {
+        Err(SonicatError::Parameter(
+            "--spacer requires --concatenate".to_string(),
+        ))
+        .or_exit()
+    }
 
-    let mut count = 0;
+    let split_dir = matches
+        .get_one::<PathBuf>("split-by-contig")
+        .map(PathBuf::as_path);
+    let chunk_reads: Option<u64> = param(&matches, "chunk-reads", config.chunk_reads);
+    if let Some(reads) = chunk_reads {
+        config::positive("reads per chunk", reads as f64).or_exit();
+    }
+    config::exclusive(
+        ("chunk-reads", chunk_reads.is_some()),
+        ("split-by-contig", split_dir.is_some()),
+    )
+    .or_exit();
+    let chunk_path = chunk_reads.map(|_| {
+        // parts are files, not stdout
+        matches
+            .get_one::<PathBuf>("out")
+            .filter(|x| x.as_os_str() != "-")
+            .cloned()
+            .ok_or_else(|| {
+                SonicatError::Parameter("--chunk-reads requires an output file".to_string())
+            })
+            .or_exit()
+    });
 
-    for record in reader.records() {
-        let record = record.unwrap();
-        let record = record.seq().windows(length);
+    // outputs are created once all parameters are checked, leaving no file
+    // behind on a bad one
+    // the command line, when recorded, and the file it is recorded in
+    let mut command_file = None;
+    let command = matches.is_present("record-command").then(|| {
+        // next to the output file, or in the directory of the split outputs
+        let sidecar = match (split_dir, matches.get_one::<PathBuf>("out")) {
+            (Some(dir), _) => Some(dir.join("sonicat.cmd")),
+            (None, Some(out)) if out.as_os_str() != "-" => {
+                let mut path = out.clone().into_os_string();
+                path.push(".cmd");
+                Some(PathBuf::from(path))
+            }
+            _ => None,
+        };
+        match sidecar {
+            Some(path) => {
+                if let Some(dir) = split_dir {
+                    fs::create_dir_all(dir).or_exit();
+                }
+                provenance::write_command(&path, "sonicat", force).or_exit();
+                command_file = Some(path);
+            }
+            None if format != Format::Ubam => {
+                warn!("no output file to record the command line next to");
+            }
+            None => (),
+        }
+        provenance::command_line()
+    });
+    // writer of the current record when splitting by contig
+    let mut writer = match (split_dir, chunk_reads.zip(chunk_path)) {
+        (Some(dir), _) => {
+            fs::create_dir_all(dir).or_exit();
+            None
+        }
+        (None, Some((reads, path))) => Some(Output::in_parts(Parts {
+            path,
+            reads,
+            part: 1,
+            format,
+            command: command.clone(),
+            compress,
+            force,
+        })),
+        (None, None) => Some(Output::new(
+            Writer::new(
+                create_output(
+                    matches.get_one::<PathBuf>("out").map(PathBuf::as_path),
+                    compress,
+                    force,
+                )
+                .or_exit(),
+                format,
+                command.as_deref(),
+            ),
+            matches
+                .get_one::<PathBuf>("out")
+                .filter(|x| x.as_os_str() != "-")
+                .cloned(),
+        )),
+    };
+    // files of reads written, with their reads
+    let mut written: Vec<(PathBuf, u64)> = Vec::new();
+    // written field by field for every read, so buffered
+    let mut debug_pairs = matches.get_one::<PathBuf>("debug-pairs").map(|x| {
+        io::BufWriter::new(create_output(Some(x), Compression::from_path(Some(x)), force).or_exit())
+    });
+    // written a line per error, so buffered
+    let mut error_log = matches.get_one::<PathBuf>("error-log").map(|x| {
+        io::BufWriter::new(create_output(Some(x), Compression::from_path(Some(x)), force).or_exit())
+    });
+    // written a line per read, so buffered
+    let mut truth = matches.get_one::<PathBuf>("truth").map(|x| {
+        io::BufWriter::new(create_output(Some(x), Compression::from_path(Some(x)), force).or_exit())
+    });
 
-        for r in record {
-            let v = thread_rng().sample(poi) as u64;
-            for _ in 0..v {
-                count += 1;
+    // read names must stay unique, so the count is checked rather than wrapped
+    let mut count: u64 = 0;
+    let mut discarded: u64 = 0;
+    let mut masked: u64 = 0;
+    let mut chimeras: u64 = 0;
+    let mut duplicates: u64 = 0;
+    let mut suppressed: u64 = 0;
+    let mut bases: u64 = 0;
+    // reads the records are expected to yield, before any is filtered
+    let mut expected: f64 = 0.0;
 
-                let name = format!("seq_{}", count);
+    let timing = matches.is_present("timing") && !matches.is_present("quiet");
+    if timing {
+        eprintln!("record\tbases\tseconds\tbases/s");
+    }
+    // bases of the records processed
+    let mut input_bases: u64 = 0;
+    let started = Instant::now();
+    let mut ids = HashSet::new();
+    // file names of the records written when splitting by contig
+    let mut contig_files = HashSet::new();
+
+    // records sonicated, and the spans of the input records if joined
+    let (records, spans): (Box<dyn Iterator<Item = io::Result<fasta::Record>>>, _) = if concatenated
+    {
+        let (record, spans) = concatenate(reader, spacer.unwrap_or(0), &variants);
+        (Box::new(iter::once(Ok(record))), spans)
+    } else {
+        (Box::new(reader.records()), Vec::new())
+    };
+
+    for record in records {
+        let record = record.map_err(record_error).or_exit();
+        let record_started = Instant::now();
+        let record_count = count;
+        if !ids.insert(record.id().to_string()) {
+            if strict {
+                Err(SonicatError::Parse(format!(
+                    "duplicate record id {}",
+                    record.id()
+                )))
+                .or_exit()
+            }
+            // records of the same id share their RNG, and thus their reads
+            warn!(
+                "duplicate record id {}, its records share one random stream",
+                record.id()
+            );
+        }
+        // the variants of joined records are applied as they are joined
+        let seq = if concatenated {
+            Cow::Borrowed(record.seq())
+        } else {
+            apply_variants(&record, &variants)
+        };
+        let seq = seq.as_ref();
+        let rng = record_rng(seed, record.id());
+        // depth and length of the record, from its description if tagged
+        let (depth, length) = if header_params {
+            (
+                desc_param(&record, "depth").or_exit().unwrap_or(depth),
+                desc_param(&record, "length").or_exit().unwrap_or(length),
+            )
+        } else {
+            (depth, length)
+        };
+        if length == 0 {
+            Err(SonicatError::Parameter(format!(
+                "record {} has a read length of 0",
+                record.id()
+            )))
+            .or_exit()
+        }
+        let kmers = annotate_difficulty.then(|| KmerIndex::new(seq, DIFFICULTY_K));
+
+        if seq.is_empty() || (tail == Tail::Drop && seq.len() < length) {
+            warn!(
+                "skipped {}, shorter ({}) than the read length",
+                record.id(),
+                seq.len()
+            );
+            continue;
+        }
+        expected += expected_reads(seq.len(), depth, length, step, tail);
+
+        if let Some(dir) = split_dir {
+            // the first free name, numbered if taken by an earlier record
+            let name = (1..)
+                .map(|n| contig_file(record.id(), n, format, compress))
+                .find(|x| !contig_files.contains(x))
+                .unwrap();
+            contig_files.insert(name.clone());
+            let path = dir.join(name);
+            let fout = create_output(Some(&path), compress, force).or_exit();
+            let output = Output::new(Writer::new(fout, format, command.as_deref()), Some(path));
+            if let Some(w) = writer.replace(output) {
+                written.extend(w.finish().or_exit());
+            }
+        }
+        let writer = writer.as_mut().unwrap();
 
-                writer.write(name.as_str(), Option::None, r).unwrap();
+        // reads of the record held back to be sorted, and whether to stop after them
+        let mut pending = Vec::new();
+        let mut stop = false;
+        let weights = tagmentation.then(|| tagmentation::start_weights(seq));
+        let record_coverage = match &weights {
+            // the depth is raised to make up for the reads dropped by the weights
+            Some(x) => {
+                let mean = x.iter().sum::<f64>() / x.len() as f64;
+                Some(new_coverage(depth / mean).or_exit())
             }
+            None => header_params.then(|| new_coverage(depth).or_exit()),
+        };
+        let mut sonicator = Sonicator::new(
+            seq,
+            length,
+            record_coverage.as_ref().unwrap_or(&coverage),
+            rng,
+        );
+        sonicator.set_tail(tail);
+        sonicator.set_step(step);
+        if let Some(weights) = &weights {
+            sonicator.set_weights(weights);
         }
+        let starts = sonicator.start_distribution();
+
+        while let Some(fragment) = sonicator.next() {
+            if signal::interrupted() {
+                stop = true;
+                break;
+            }
+            let rng = sonicator.rng();
+            let mut r: Cow<[u8]> = Cow::Owned(fragment.seq);
+            // source window of the read, redrawn along with it
+            let mut start = fragment.start;
+
+            if let Some(max_n) = max_n {
+                let mut attempts = 0;
+                while n_fraction(&r) > max_n && attempts < MAX_RESAMPLE {
+                    discarded += 1;
+                    attempts += 1;
+                    start = rng.sample(&starts);
+                    r = window(seq, start, length, tail);
+                }
+                if n_fraction(&r) > max_n {
+                    continue;
+                }
+            }
+
+            if skip_masked && r.iter().any(u8::is_ascii_lowercase) {
+                masked += 1;
+                continue;
+            }
+
+            let end = start + r.len();
+
+            // description fields of the read, and its annotations in the truth
+            let mut desc = Vec::new();
+            let mut marks = Vec::new();
+            // source of the read, of its 5' half alone for a chimera
+            let mut source = (start, end);
+
+            // scored on the forward reference window, before a chimera, the
+            // strand and the artifacts change its k-mers
+            let difficulty = kmers.as_ref().map(|x| x.difficulty(&r));
+            // the forward reference window of the read, taken before them too
+            let reference = debug_pairs.is_some().then(|| r.to_vec());
+
+            let read = if chimera_rate > 0.0 && rng.gen_bool(chimera_rate) {
+                // join the 5' half of the fragment with the 3' half of another
+                let other_start = rng.sample(&starts);
+                let other = window(seq, other_start, length, tail);
+                let half = length / 2;
+                if skip_masked
+                    && other
+                        .get(half..)
+                        .is_some_and(|x| x.iter().any(u8::is_ascii_lowercase))
+                {
+                    masked += 1;
+                    continue;
+                }
+                chimeras += 1;
+                desc.push("chimera".to_string());
+                source.1 = start + half.min(r.len());
+                if other.len() > half {
+                    let (contig, (start, end)) = locate(
+                        record.id(),
+                        &spans,
+                        (other_start + half, other_start + other.len()),
+                    );
+                    marks.push(format!("chimera={}:{}-{}", contig, start, end));
+                }
+                let mut chimera = Vec::with_capacity(length);
+                // either may be cut short at the end of the record
+                chimera.extend_from_slice(&r[..half.min(r.len())]);
+                chimera.extend_from_slice(other.get(half..).unwrap_or_default());
+                Cow::Owned(chimera)
+            } else {
+                r
+            };
+
+            // reads of the reverse strand are the reverse complement of the fragment
+            let reverse = stranded && rng.gen_bool(0.5);
+            let read = if reverse {
+                Cow::Owned(alphabet::reverse_complement(&read))
+            } else {
+                read
+            };
+            let strand = if reverse { '-' } else { '+' };
+            if stranded {
+                desc.push(format!("strand={}", strand));
+            }
+            if let Some(samples) = &samples {
+                desc.push(format!("sample={}", pool[rng.sample(samples)].0));
+            }
+            if let Some(template) = &coord_format {
+                desc.push(format_coords(
+                    template,
+                    record.id(),
+                    start,
+                    end,
+                    strand,
+                    coord_base,
+                ));
+            }
+            // reads not within a single input record cross a join
+            if !spans.is_empty() && !spans.iter().any(|x| x.0 <= start && end <= x.1) {
+                let contigs: Vec<&str> = spans
+                    .iter()
+                    .filter(|x| x.0 < end && start < x.1)
+                    .map(|x| x.2.as_str())
+                    .collect();
+                desc.push(format!("contigs={}", contigs.join(",")));
+                marks.push(format!("contigs={}", contigs.join(",")));
+            }
+
+            let read = if resolve_ambiguous && read.iter().any(|&x| alphabet::is_ambiguous(x)) {
+                Cow::Owned(read.iter().map(|&x| mutator::resolve(rng, x)).collect())
+            } else {
+                read
+            };
+
+            let read = if soft_clip > 0 {
+                // artifacts an aligner would soft-clip, at most half the read each
+                let mut read = read.into_owned();
+                let clip = soft_clip.min(read.len() / 2);
+                let end = read.len() - clip;
+                for i in (0..clip).chain(end..read.len()) {
+                    read[i] = mutator::saturate(rng, read[i]);
+                }
+                desc.push(format!("clip={}", clip));
+                marks.push(format!("clip={}", clip));
+                Cow::Owned(read)
+            } else {
+                read
+            };
+
+            // positions, original and new bases of the sequencing errors
+            let mut edits = Vec::new();
+            let read = match &errors {
+                Some(errors) => {
+                    let mut read = read.into_owned();
+                    let model = &errors[usize::from(reverse)];
+                    let n = if error_log.is_some() {
+                        model.apply_with(&mut read, rng, |i, from, to| edits.push((i, from, to)))
+                    } else {
+                        model.apply(&mut read, rng)
+                    };
+                    if n == 0 && only_errored {
+                        suppressed += 1;
+                        continue;
+                    }
+                    Cow::Owned(read)
+                }
+                None => read,
+            };
+
+            if max_bases.is_some_and(|m| bases + read.len() as u64 > m) {
+                stop = true;
+                break;
+            }
+            bases += read.len() as u64;
+
+            count = next_read(count).or_exit();
+
+            let name = format!("{}seq_{}{}", name_prefix, count, name_suffix);
+
+            if let (Some(w), Some(reference)) = (&mut debug_pairs, &reference) {
+                w.write_all(name.as_bytes()).or_exit();
+                w.write_all(b"\t").or_exit();
+                w.write_all(reference).or_exit();
+                w.write_all(b"\t").or_exit();
+                w.write_all(&read).or_exit();
+                w.write_all(b"\n").or_exit();
+            }
+
+            if let Some(difficulty) = difficulty {
+                desc.push(format!("difficulty={:.3}", difficulty));
+            }
+
+            let qual = qualities.sample_within(rng, read.len(), quality_min, quality_max);
+
+            // PCR duplicates follow the read, identical to it but for their names
+            let mut copies = vec![(name, desc)];
+            while dup_rate > 0.0 && rng.gen_bool(dup_rate) {
+                if max_bases.is_some_and(|m| bases + read.len() as u64 > m) {
+                    stop = true;
+                    break;
+                }
+                bases += read.len() as u64;
+                count = next_read(count).or_exit();
+                duplicates += 1;
+
+                let (original, desc) = &copies[0];
+                let mut desc = desc.clone();
+                desc.push(format!("duplicate_of={}", original));
+                copies.push((format!("{}seq_{}{}", name_prefix, count, name_suffix), desc));
+            }
+
+            let original = copies[0].0.clone();
+            for (i, (name, desc)) in copies.into_iter().enumerate() {
+                if i == 1 {
+                    marks.push(format!("duplicate_of={}", original));
+                }
+                let line = truth.is_some().then(|| {
+                    let (contig, source) = locate(record.id(), &spans, source);
+                    truth_line(contig, source, &name, strand, &marks)
+                });
+                let desc = match (desc.is_empty(), tags.is_empty()) {
+                    (true, true) => None,
+                    (false, true) => Some(desc.join(" ")),
+                    (true, false) => Some(tags.join("\t")),
+                    (false, false) => Some(format!("{}\tCO:Z:{}", tags.join("\t"), desc.join(" "))),
+                };
+                let out = Read {
+                    id: name,
+                    desc,
+                    seq: read.to_vec(),
+                    qual: Some(qual.clone()),
+                    start,
+                    end,
+                };
+                if let Some(w) = &mut error_log {
+                    for (i, from, to) in &edits {
+                        writeln!(
+                            w,
+                            "{}\t{}\t{}\t{}",
+                            out.id,
+                            i + coord_base,
+                            *from as char,
+                            *to as char
+                        )
+                        .or_exit();
+                    }
+                }
+                if sorted {
+                    pending.push((out, line));
+                } else {
+                    writer.write(out).or_exit();
+                    if let (Some(w), Some(line)) = (&mut truth, line) {
+                        writeln!(w, "{}", line).or_exit();
+                    }
+                }
+            }
+            if stop {
+                break;
+            }
+        }
+
+        // a stable sort keeps reads of the same start in the order drawn
+        pending.sort_by_key(|x| x.0.start);
+        for (read, line) in pending.drain(..) {
+            writer.write(read).or_exit();
+            if let (Some(w), Some(line)) = (&mut truth, line) {
+                writeln!(w, "{}", line).or_exit();
+            }
+        }
+        if stop {
+            break;
+        }
+
+        debug!(
+            "sonicated {} ({} bases) into {} reads in {:.2?}",
+            record.id(),
+            seq.len(),
+            count - record_count,
+            record_started.elapsed()
+        );
+        if timing {
+            logging::report_timing(record.id(), seq.len() as u64, record_started.elapsed());
+        }
+        input_bases += seq.len() as u64;
+    }
+
+    if let Some(w) = writer {
+        written.extend(w.finish().or_exit());
+    }
+    for w in [debug_pairs, error_log, truth].into_iter().flatten() {
+        finish_buffered(w).or_exit();
+    }
+    info!("emitted {} reads in {:.2?}", count, started.elapsed());
+
+    if timing {
+        logging::report_timing("total", input_bases, started.elapsed());
+    }
+
+    if matches.is_present("stats") && !quiet {
+        eprintln!("reads\t{}", count);
+        eprintln!("expected reads\t{:.1}", expected);
+        eprintln!("bases\t{}", bases);
+        if max_n.is_some() {
+            eprintln!("discarded\t{}", discarded);
+        }
+        if skip_masked {
+            eprintln!("masked\t{}", masked);
+        }
+        if chimera_rate > 0.0 {
+            eprintln!("chimeras\t{}", chimeras);
+        }
+        if dup_rate > 0.0 {
+            eprintln!("duplicates\t{}", duplicates);
+        }
+        if only_errored {
+            eprintln!(
+                "suppressed\t{}\t{:.4}",
+                suppressed,
+                suppressed as f64 / (count + suppressed).max(1) as f64
+            );
+        }
+    }
+
+    let parameters = json!({
+        "depth": depth,
+        "coverage_dist": coverage_dist.to_string(),
+        "dispersion": dispersion,
+        "length": length,
+        "step": step,
+        "error_rate_forward": forward_rate,
+        "error_rate_reverse": reverse_rate,
+        "chimera_rate": chimera_rate,
+        "dup_rate": dup_rate,
+        "seed": seed,
+    });
+    if let Some(path) = matches.get_one::<PathBuf>("stats-json") {
+        let summary = json!({
+            "parameters": parameters,
+            "counts": {
+                "input_bases": input_bases,
+                "reads": count,
+                "expected_reads": expected,
+                "bases": bases,
+                "discarded": discarded,
+                "masked": masked,
+                "chimeras": chimeras,
+                "duplicates": duplicates,
+                "suppressed": suppressed,
+            },
+            "rates": {
+                "depth": bases as f64 / input_bases.max(1) as f64,
+                "duplicates": duplicates as f64 / count.max(1) as f64,
+                "suppressed": suppressed as f64 / (count + suppressed).max(1) as f64,
+            },
+            "seconds": started.elapsed().as_secs_f64(),
+        });
+        provenance::write_report(path, "sonicat", summary, force).or_exit();
+    }
+
+    if let Some(path) = matches.get_one::<PathBuf>("manifest") {
+        let mut files: Vec<serde_json::Value> = written
+            .iter()
+            .map(|(path, reads)| json!({"path": path, "kind": "reads", "reads": reads}))
+            .collect();
+        // side files, written whole by now
+        let sidecars = [
+            ("debug-pairs", "debug-pairs"),
+            ("error-log", "error-log"),
+            ("truth", "truth"),
+            ("stats-json", "stats"),
+        ];
+        for (arg, kind) in sidecars {
+            if let Some(x) = matches.get_one::<PathBuf>(arg) {
+                files.push(json!({"path": x, "kind": kind}));
+            }
+        }
+        if let Some(x) = &command_file {
+            files.push(json!({"path": x, "kind": "command"}));
+        }
+        let manifest = json!({
+            "parameters": parameters,
+            "reads": count,
+            "files": files,
+        });
+        provenance::write_report(path, "sonicat", manifest, force).or_exit();
+    }
+
+    if signal::interrupted() {
+        warn!(
+            "interrupted, output holds the {} reads emitted so far",
+            count
+        );
+        std::process::exit(signal::INTERRUPTED_STATUS);
     }
 }
@@ -0,0 +1,167 @@
+//! Codons of coding sequences under the standard genetic code.
+
+use crate::alphabet::{self, Base};
+use crate::gff::Cds;
+
+// amino acids of the codons in TCAG order
+const STANDARD_CODE: &[u8; 64] =
+    b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG";
+
+fn index(b: u8) -> Option<usize> {
+    match Base::from_u8(b)? {
+        Base::T => Some(0),
+        Base::C => Some(1),
+        Base::A => Some(2),
+        Base::G => Some(3),
+    }
+}
+
+/// Amino acid of a codon, `*` for stops; `None` if a base is not concrete.
+pub fn translate(codon: &[u8; 3]) -> Option<u8> {
+    let mut i = 0;
+    for &b in codon {
+        i = i * 4 + index(b)?;
+    }
+    Some(STANDARD_CODE[i])
+}
+
+/// Codon of a coding base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodonSite {
+    /// Codon read on the coding strand.
+    pub codon: [u8; 3],
+    /// Position of the base in the codon.
+    pub offset: usize,
+    /// The coding strand is the reverse strand.
+    pub reverse: bool,
+}
+
+impl CodonSite {
+    /// Whether substituting the base by `b`, read on the forward strand,
+    /// keeps the amino acid of the codon. `None` if the codon or `b` is not
+    /// made of concrete bases.
+    pub fn is_synonymous(&self, b: u8) -> Option<bool> {
+        let b = Base::from_u8(b)?;
+        let b = if self.reverse { b.complement() } else { b };
+        let mut codon = self.codon;
+        codon[self.offset] = b.to_u8();
+        Some(translate(&self.codon)? == translate(&codon)?)
+    }
+}
+
+/// Codon of each base of `seq` within the complete codons of `cds`, in
+/// the frame given by their phase. Codons split between features are not
+/// resolved.
+pub fn codon_sites(seq: &[u8], cds: &[Cds]) -> Vec<Option<CodonSite>> {
+    let mut sites = vec![None; seq.len()];
+    for x in cds {
+        let end = x.end.min(seq.len());
+        if x.start + x.phase + 3 > end {
+            continue;
+        }
+        let n = (end - x.start - x.phase) / 3;
+        for k in 0..n {
+            // lowest position of the codon on the forward strand
+            let first = if x.reverse {
+                end - x.phase - 3 * (k + 1)
+            } else {
+                x.start + x.phase + 3 * k
+            };
+            let mut codon = [0; 3];
+            codon.copy_from_slice(&seq[first..first + 3]);
+            if x.reverse {
+                codon = [codon[2], codon[1], codon[0]].map(alphabet::complement);
+            }
+            for i in 0..3 {
+                sites[first + i] = Some(CodonSite {
+                    codon,
+                    offset: if x.reverse { 2 - i } else { i },
+                    reverse: x.reverse,
+                });
+            }
+        }
+    }
+    sites
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codons_translate_under_the_standard_code() {
+        assert_eq!(translate(b"ATG"), Some(b'M'));
+        assert_eq!(translate(b"TGG"), Some(b'W'));
+        assert_eq!(translate(b"taa"), Some(b'*'));
+        assert_eq!(translate(b"GGN"), None);
+    }
+
+    #[test]
+    fn synonymy_is_read_on_the_coding_strand() {
+        // third base of CTG, leucine under any base
+        let site = CodonSite {
+            codon: *b"CTG",
+            offset: 2,
+            reverse: false,
+        };
+        assert_eq!(site.is_synonymous(b'A'), Some(true));
+        // first base, CTG to ATG is leucine to methionine
+        let site = CodonSite {
+            codon: *b"CTG",
+            offset: 0,
+            reverse: false,
+        };
+        assert_eq!(site.is_synonymous(b'A'), Some(false));
+        assert_eq!(site.is_synonymous(b'N'), None);
+        // a forward T is an A on the reverse coding strand, CTG to ATG
+        let site = CodonSite {
+            codon: *b"CTG",
+            offset: 0,
+            reverse: true,
+        };
+        assert_eq!(site.is_synonymous(b'T'), Some(false));
+        assert_eq!(site.is_synonymous(b'C'), Some(false));
+    }
+
+    #[test]
+    fn sites_follow_the_frame_of_each_cds() {
+        let seq = b"xATGGCCTAAxx";
+        let cds = [Cds {
+            start: 0,
+            end: 10,
+            reverse: false,
+            phase: 1,
+        }];
+        let sites = codon_sites(seq, &cds);
+        assert_eq!(sites[0], None);
+        assert_eq!(sites[10], None);
+        let codons: Vec<[u8; 3]> = sites[1..10]
+            .iter()
+            .step_by(3)
+            .map(|x| x.unwrap().codon)
+            .collect();
+        assert_eq!(codons, [*b"ATG", *b"GCC", *b"TAA"]);
+        assert_eq!(sites[5].unwrap().offset, 1);
+
+        // reverse CDS read from its end, CAT reversed and complemented
+        let cds = [Cds {
+            start: 1,
+            end: 4,
+            reverse: true,
+            phase: 0,
+        }];
+        let sites = codon_sites(b"xCATx", &cds);
+        assert_eq!(sites[1].unwrap().codon, *b"ATG");
+        assert_eq!(sites[1].unwrap().offset, 2);
+        assert_eq!(sites[3].unwrap().offset, 0);
+
+        // features too short for a codon have no sites
+        let cds = [Cds {
+            start: 0,
+            end: 4,
+            reverse: false,
+            phase: 2,
+        }];
+        assert!(codon_sites(b"ACGT", &cds).iter().all(Option::is_none));
+    }
+}
@@ -0,0 +1,246 @@
+use crate::error::{OrExit, Result, SonicatError};
+use clap::ArgMatches;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Parameters of muta, as read from a configuration file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct MutationConfig {
+    pub alphabet: Option<String>,
+    pub substitution: Option<f64>,
+    pub insertion: Option<f64>,
+    pub deletion: Option<f64>,
+    pub deletion_mode: Option<String>,
+    pub insert_gc: Option<f64>,
+    pub insert_local_composition: Option<bool>,
+    pub composition_window: Option<usize>,
+    pub cpg_boost: Option<f64>,
+    pub gff: Option<PathBuf>,
+    pub only_cds: Option<bool>,
+    pub dnds: Option<f64>,
+    pub copies: Option<usize>,
+    pub revcomp: Option<bool>,
+    pub density_window: Option<usize>,
+    pub alignment_mode: Option<bool>,
+    pub num_mutations: Option<usize>,
+    pub chunk_size: Option<usize>,
+    pub streaming: Option<bool>,
+    pub line_width: Option<usize>,
+    pub threads: Option<usize>,
+    pub replicate: Option<u64>,
+    pub seed: Option<u64>,
+    pub saturate: Option<bool>,
+    pub preserve_case: Option<bool>,
+    pub verify: Option<bool>,
+    pub strict: Option<bool>,
+}
+
+/// Parameters of sonicat, as read from a configuration file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SonicationConfig {
+    pub vcf: Option<PathBuf>,
+    pub format: Option<String>,
+    pub quality: Option<u8>,
+    pub quality_dist: Option<String>,
+    pub quality_sd: Option<f64>,
+    pub quality_min: Option<u8>,
+    pub quality_max: Option<u8>,
+    pub preset: Option<String>,
+    pub depth: Option<f64>,
+    pub length: Option<usize>,
+    pub step: Option<usize>,
+    pub coverage_dist: Option<String>,
+    pub dispersion: Option<f64>,
+    pub concatenate: Option<bool>,
+    pub spacer: Option<usize>,
+    pub header_params: Option<bool>,
+    pub tagmentation: Option<bool>,
+    pub circular: Option<bool>,
+    pub tail: Option<String>,
+    pub max_n: Option<f64>,
+    pub skip_masked: Option<bool>,
+    pub chimera_rate: Option<f64>,
+    pub dup_rate: Option<f64>,
+    pub resolve_ambiguous: Option<bool>,
+    pub soft_clip: Option<usize>,
+    pub error_rate: Option<f64>,
+    pub error_rate_forward: Option<f64>,
+    pub error_rate_reverse: Option<f64>,
+    pub only_errored: Option<bool>,
+    pub name_prefix: Option<String>,
+    pub name_suffix: Option<String>,
+    pub pool: Option<Vec<String>>,
+    pub coord_format: Option<String>,
+    pub coord_base: Option<usize>,
+    pub read_group: Option<String>,
+    pub platform: Option<String>,
+    pub annotate_difficulty: Option<bool>,
+    pub sorted: Option<bool>,
+    pub max_bases: Option<u64>,
+    pub chunk_reads: Option<u64>,
+    pub strict: Option<bool>,
+    pub replicate: Option<u64>,
+    pub seed: Option<u64>,
+}
+
+/// Load a configuration file, JSON if the extension is `.json`, TOML
+/// otherwise.
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let text = fs::read_to_string(path)?;
+    let invalid = |e: &dyn Display| SonicatError::Parse(format!("{}: {}", path.display(), e));
+    if path.extension().is_some_and(|x| x == "json") {
+        serde_json::from_str(&text).map_err(|e| invalid(&e))
+    } else {
+        toml::from_str(&text).map_err(|e| invalid(&e))
+    }
+}
+
+/// Value of the option `name`, from the command line if given, otherwise
+/// from the configuration file; an error if the command line value does
+/// not parse.
+pub fn try_param<T>(matches: &ArgMatches, name: &str, file: Option<T>) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match matches.value_of(name) {
+        Some(x) => x.parse().map(Some).map_err(|e| {
+            SonicatError::Parameter(format!("invalid value {:?} for --{}: {}", x, name, e))
+        }),
+        None => Ok(file),
+    }
+}
+
+/// Value of the option `name` as [`try_param`], exiting on a command line
+/// value that does not parse.
+pub fn param<T>(matches: &ArgMatches, name: &str, file: Option<T>) -> Option<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    try_param(matches, name, file).or_exit()
+}
+
+/// Whether the flag `name` is set on the command line or in the
+/// configuration file.
+pub fn flag(matches: &ArgMatches, name: &str, file: Option<bool>) -> bool {
+    matches.is_present(name) || file.unwrap_or(false)
+}
+
+/// `value` of the parameter `name` if within [0, 1], a parameter error
+/// otherwise.
+pub fn fraction(name: &str, value: f64) -> Result<f64> {
+    if (0.0..=1.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(SonicatError::Parameter(format!(
+            "{} {} out of [0, 1]",
+            name, value
+        )))
+    }
+}
+
+/// `value` of the parameter `name` if finite and positive, a parameter
+/// error otherwise.
+pub fn positive(name: &str, value: f64) -> Result<f64> {
+    if value.is_finite() && value > 0.0 {
+        Ok(value)
+    } else {
+        Err(SonicatError::Parameter(format!(
+            "{} {} out of (0, inf)",
+            name, value
+        )))
+    }
+}
+
+/// `value` of the parameter `name` if finite and not negative, a parameter
+/// error otherwise.
+pub fn non_negative(name: &str, value: f64) -> Result<f64> {
+    if value.is_finite() && value >= 0.0 {
+        Ok(value)
+    } else {
+        Err(SonicatError::Parameter(format!(
+            "{} {} out of [0, inf)",
+            name, value
+        )))
+    }
+}
+
+/// A parameter error if both options `a` and `b` are set, on the command
+/// line or in the configuration file. clap checks the command line alone.
+pub fn exclusive((a, a_set): (&str, bool), (b, b_set): (&str, bool)) -> Result<()> {
+    if a_set && b_set {
+        Err(SonicatError::Parameter(format!(
+            "--{} and --{} are mutually exclusive",
+            a, b
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, Command};
+    use std::env;
+    use std::process;
+
+    fn write(name: &str, text: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("sonicat-{}-{}", process::id(), name));
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn toml_and_json_load_alike() {
+        let toml = write(
+            "config.toml",
+            "substitution = 0.1\ndeletion-mode = \"mask\"\n",
+        );
+        let json = write(
+            "config.json",
+            r#"{"substitution": 0.1, "deletion-mode": "mask"}"#,
+        );
+        for path in [toml, json] {
+            let config: MutationConfig = load(&path).unwrap();
+            assert_eq!(config.substitution, Some(0.1));
+            assert_eq!(config.deletion_mode.as_deref(), Some("mask"));
+            assert_eq!(config.insertion, None);
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let path = write("typo.toml", "substitutoin = 0.1\n");
+        assert!(matches!(
+            load::<MutationConfig>(&path),
+            Err(SonicatError::Parse(_))
+        ));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn command_line_overrides_the_file() {
+        let command = Command::new("test")
+            .arg(Arg::new("rate").long("rate").takes_value(true))
+            .arg(Arg::new("flag").long("flag"));
+        let matches = command.clone().get_matches_from(["test", "--rate", "0.5"]);
+        assert_eq!(param(&matches, "rate", Some(0.1)), Some(0.5));
+        assert!(!flag(&matches, "flag", None));
+        assert!(flag(&matches, "flag", Some(true)));
+
+        let matches = command.clone().get_matches_from(["test", "--flag"]);
+        assert_eq!(param(&matches, "rate", Some(0.1)), Some(0.1));
+        assert!(flag(&matches, "flag", Some(false)));
+
+        let matches = command.get_matches_from(["test", "--rate", "x"]);
+        assert!(try_param::<f64>(&matches, "rate", None).is_err());
+    }
+}
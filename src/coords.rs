@@ -0,0 +1,135 @@
+use crate::vcf::Variant;
+
+/// Translation of positions between an original and a mutated sequence.
+///
+/// Indels are recorded in order of their original position; substitutions
+/// do not move coordinates and are not recorded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoordinateMap {
+    // original position from which on the offset applies, with the offset
+    shifts: Vec<(usize, isize)>,
+    // deleted ranges of the original sequence, half-open
+    deleted: Vec<(usize, usize)>,
+}
+
+impl CoordinateMap {
+    pub fn new() -> CoordinateMap {
+        CoordinateMap::default()
+    }
+
+    /// The map of the edits recorded while mutating a sequence, or of any
+    /// non-overlapping variants, given in order of position.
+    ///
+    /// The bases an edit adds or removes are taken to follow the bases its
+    /// REF and ALT share, as in VCF.
+    pub fn from_edits(edits: &[Variant]) -> CoordinateMap {
+        let mut map = CoordinateMap::new();
+        for edit in edits {
+            let (r, a) = (edit.reference.len(), edit.alt.len());
+            if a > r {
+                map.insertion(edit.pos + r, a - r);
+            } else if r > a {
+                map.deletion(edit.pos + a, r - a);
+            }
+        }
+        map
+    }
+
+    fn offset(&self) -> isize {
+        self.shifts.last().map_or(0, |&(_, o)| o)
+    }
+
+    /// Record `len` bases inserted before the original position `pos`.
+    pub fn insertion(&mut self, pos: usize, len: usize) {
+        let offset = self.offset() + len as isize;
+        self.shifts.push((pos, offset));
+    }
+
+    /// Record `len` original bases deleted from position `pos` on.
+    pub fn deletion(&mut self, pos: usize, len: usize) {
+        let offset = self.offset() - len as isize;
+        self.deleted.push((pos, pos + len));
+        self.shifts.push((pos + len, offset));
+    }
+
+    /// Position in the mutated sequence of the original base at `pos`,
+    /// `None` if the base was deleted.
+    pub fn map_original_to_mutated(&self, pos: usize) -> Option<usize> {
+        match self.deletion_at(pos) {
+            Some(_) => None,
+            None => Some(self.mutated_before(pos)),
+        }
+    }
+
+    /// Number of mutated bases before the original position `pos`, where
+    /// the base at `pos` lies in the mutated sequence or, if deleted, would
+    /// have. The mutated length for `pos` past the last base.
+    pub fn mutated_before(&self, pos: usize) -> usize {
+        let pos = self.deletion_at(pos).unwrap_or(pos);
+        let i = self.shifts.partition_point(|&(p, _)| p <= pos);
+        let offset = if i > 0 { self.shifts[i - 1].1 } else { 0 };
+        (pos as isize + offset) as usize
+    }
+
+    // start of the deleted range holding `pos`
+    fn deletion_at(&self, pos: usize) -> Option<usize> {
+        let i = self.deleted.partition_point(|&(s, _)| s <= pos);
+        (i > 0 && self.deleted[i - 1].1 > pos).then(|| self.deleted[i - 1].0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ACG[TT]TAC[GT]AC, two bases inserted before 3 and two deleted at 6
+    fn map() -> CoordinateMap {
+        let mut map = CoordinateMap::new();
+        map.insertion(3, 2);
+        map.deletion(6, 2);
+        map
+    }
+
+    #[test]
+    fn positions_shift_across_indels() {
+        let mapped: Vec<Option<usize>> =
+            (0..10).map(|x| map().map_original_to_mutated(x)).collect();
+        assert_eq!(
+            mapped,
+            [
+                Some(0),
+                Some(1),
+                Some(2),
+                Some(5),
+                Some(6),
+                Some(7),
+                None,
+                None,
+                Some(8),
+                Some(9)
+            ]
+        );
+    }
+
+    #[test]
+    fn edits_map_as_their_indels() {
+        let edit = |pos, reference: &[u8], alt: &[u8]| Variant {
+            pos,
+            reference: reference.to_vec(),
+            alt: alt.to_vec(),
+        };
+        let edits = [
+            edit(1, b"C", b"G"),
+            edit(2, b"G", b"GTT"),
+            edit(5, b"CGT", b"C"),
+        ];
+        assert_eq!(CoordinateMap::from_edits(&edits), map());
+        assert_eq!(CoordinateMap::new().map_original_to_mutated(7), Some(7));
+    }
+
+    #[test]
+    fn deleted_bases_lie_between_their_neighbours() {
+        let before: Vec<usize> = (0..=10).map(|x| map().mutated_before(x)).collect();
+        assert_eq!(before, [0, 1, 2, 5, 6, 7, 8, 8, 8, 9, 10]);
+    }
+}
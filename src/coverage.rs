@@ -0,0 +1,135 @@
+use crate::config;
+use crate::error::{Result, SonicatError};
+use rand::Rng;
+use rand_distr::{Distribution, Gamma, Poisson};
+use std::fmt;
+use std::str::FromStr;
+
+/// Kind of distribution of the reads per position, see [`Coverage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverageDist {
+    Poisson,
+    NegativeBinomial,
+}
+
+impl FromStr for CoverageDist {
+    type Err = SonicatError;
+
+    fn from_str(s: &str) -> Result<CoverageDist> {
+        match s {
+            "poisson" => Ok(CoverageDist::Poisson),
+            "nbinom" => Ok(CoverageDist::NegativeBinomial),
+            _ => Err(SonicatError::Parameter(format!(
+                "unknown coverage distribution {}",
+                s
+            ))),
+        }
+    }
+}
+
+// the name parsed, as written to the reports
+impl fmt::Display for CoverageDist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CoverageDist::Poisson => "poisson",
+            CoverageDist::NegativeBinomial => "nbinom",
+        })
+    }
+}
+
+/// Distribution of the number of reads starting at a position.
+pub enum Coverage {
+    Poisson(Poisson<f64>),
+    /// Negative binomial, drawn as a Poisson whose mean follows a gamma
+    /// distribution. The variance is `mean + mean^2 / dispersion`.
+    NegativeBinomial(Gamma<f64>),
+    /// No reads, the distribution of either at a mean of zero.
+    Zero,
+}
+
+impl Coverage {
+    /// Distribution `dist` of mean `mean`, the dispersion used by the
+    /// negative binomial alone.
+    pub fn new(dist: CoverageDist, mean: f64, dispersion: f64) -> Result<Coverage> {
+        match dist {
+            CoverageDist::Poisson => Coverage::poisson(mean),
+            CoverageDist::NegativeBinomial => Coverage::negative_binomial(mean, dispersion),
+        }
+    }
+
+    /// Poisson distribution of mean `mean`, an error unless the mean is
+    /// finite and not negative.
+    pub fn poisson(mean: f64) -> Result<Coverage> {
+        if config::non_negative("depth", mean)? == 0.0 {
+            return Ok(Coverage::Zero);
+        }
+        Poisson::new(mean)
+            .map(Coverage::Poisson)
+            .map_err(|e| SonicatError::Parameter(format!("depth {}: {}", mean, e)))
+    }
+
+    /// Negative binomial distribution of mean `mean`, an error unless the
+    /// mean is finite and not negative and the dispersion positive.
+    pub fn negative_binomial(mean: f64, dispersion: f64) -> Result<Coverage> {
+        let dispersion = config::positive("dispersion", dispersion)?;
+        if config::non_negative("depth", mean)? == 0.0 {
+            return Ok(Coverage::Zero);
+        }
+        Gamma::new(dispersion, mean / dispersion)
+            .map(Coverage::NegativeBinomial)
+            .map_err(|e| SonicatError::Parameter(format!("depth {}: {}", mean, e)))
+    }
+
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> u64 {
+        match self {
+            Coverage::Poisson(poi) => poi.sample(rng) as u64,
+            Coverage::NegativeBinomial(gamma) => {
+                let lambda = gamma.sample(rng);
+                if lambda > 0.0 {
+                    Poisson::new(lambda).unwrap().sample(rng) as u64
+                } else {
+                    0
+                }
+            }
+            Coverage::Zero => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// Mean and variance of `n` draws of `coverage`.
+    fn moments(coverage: &Coverage, n: usize) -> (f64, f64) {
+        let mut rng = StdRng::seed_from_u64(1);
+        let draws: Vec<f64> = (0..n).map(|_| coverage.sample(&mut rng) as f64).collect();
+        let mean = draws.iter().sum::<f64>() / n as f64;
+        let var = draws.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n as f64;
+        (mean, var)
+    }
+
+    #[test]
+    fn negative_binomial_is_overdispersed() {
+        let (mean, var) = moments(&Coverage::poisson(10.0).unwrap(), 100_000);
+        assert!((mean - 10.0).abs() < 0.1);
+        assert!((var - 10.0).abs() < 0.5);
+
+        // variance of 10 + 10^2 / 2
+        let nbinom = Coverage::negative_binomial(10.0, 2.0).unwrap();
+        let (mean, var) = moments(&nbinom, 100_000);
+        assert!((mean - 10.0).abs() < 0.2);
+        assert!((var - 60.0).abs() < 3.0, "{}", var);
+    }
+
+    #[test]
+    fn zero_depth_draws_no_reads() {
+        for dist in [CoverageDist::Poisson, CoverageDist::NegativeBinomial] {
+            let zero = Coverage::new(dist, 0.0, 2.0).unwrap();
+            assert_eq!(moments(&zero, 10), (0.0, 0.0));
+        }
+        assert!(Coverage::poisson(-1.0).is_err());
+        assert!(Coverage::negative_binomial(10.0, 0.0).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+//! Detection of CpG islands, after Gardiner-Garden and Frommer (1987).
+
+/// Length of the windows scanned for CpG islands.
+pub const CPG_WINDOW: usize = 200;
+const MIN_GC: f64 = 0.5;
+const MIN_OBSERVED_EXPECTED: f64 = 0.6;
+
+/// Whether each base lies in a window of [`CPG_WINDOW`] bases with a GC
+/// content of at least 50% and an observed to expected CpG ratio of at
+/// least 0.6.
+pub fn cpg_islands(seq: &[u8]) -> Vec<bool> {
+    let mut island = vec![false; seq.len()];
+    if seq.len() < CPG_WINDOW {
+        return island;
+    }
+
+    let upper: Vec<u8> = seq.iter().map(|b| b.to_ascii_uppercase()).collect();
+    // prefix counts of C, G and CpG dinucleotides starting at each base
+    let mut c = vec![0; seq.len() + 1];
+    let mut g = vec![0; seq.len() + 1];
+    let mut cg = vec![0; seq.len() + 1];
+    for (i, b) in upper.iter().enumerate() {
+        c[i + 1] = c[i] + usize::from(*b == b'C');
+        g[i + 1] = g[i] + usize::from(*b == b'G');
+        cg[i + 1] = cg[i] + usize::from(*b == b'C' && upper.get(i + 1) == Some(&b'G'));
+    }
+
+    // bases up to which islands have been marked
+    let mut marked = 0;
+    for start in 0..=seq.len() - CPG_WINDOW {
+        let end = start + CPG_WINDOW;
+        let nc = (c[end] - c[start]) as f64;
+        let ng = (g[end] - g[start]) as f64;
+        // a CpG starting at the last base runs out of the window
+        let ncg = (cg[end - 1] - cg[start]) as f64;
+
+        let gc = (nc + ng) / CPG_WINDOW as f64;
+        let expected = nc * ng / CPG_WINDOW as f64;
+        if gc >= MIN_GC && expected > 0.0 && ncg / expected >= MIN_OBSERVED_EXPECTED {
+            for x in island[start.max(marked)..end].iter_mut() {
+                *x = true;
+            }
+            marked = end;
+        }
+    }
+
+    island
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn islands_are_found_between_at_rich_flanks() {
+        let flank = b"AATTATAT".repeat(50);
+        let mut seq = flank.clone();
+        seq.extend_from_slice(&b"CGCAGGCT".repeat(50));
+        seq.extend_from_slice(&flank);
+        let island = cpg_islands(&seq);
+
+        assert!(island[..400 - CPG_WINDOW].iter().all(|&x| !x));
+        assert!(island[400..800].iter().all(|&x| x));
+        assert!(island[800 + CPG_WINDOW..].iter().all(|&x| !x));
+    }
+
+    #[test]
+    fn gc_rich_windows_without_cpg_are_not_islands() {
+        assert!(cpg_islands(&b"GGCCA".repeat(100)).iter().all(|&x| !x));
+        assert!(cpg_islands(&b"CG".repeat(50)).iter().all(|&x| !x));
+    }
+}
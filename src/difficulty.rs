@@ -0,0 +1,57 @@
+//! Repeat content of reads, as a proxy of how hard they are to map.
+
+use std::collections::HashMap;
+
+/// Length of the k-mers used to score repeat content.
+pub const DIFFICULTY_K: usize = 15;
+
+/// Counts of the k-mers of a sequence, to score how repetitive a read
+/// drawn from it is.
+pub struct KmerIndex<'a> {
+    k: usize,
+    counts: HashMap<&'a [u8], u32>,
+}
+
+impl<'a> KmerIndex<'a> {
+    pub fn new(seq: &'a [u8], k: usize) -> KmerIndex<'a> {
+        let mut counts = HashMap::new();
+        for kmer in seq.windows(k) {
+            *counts.entry(kmer).or_insert(0) += 1;
+        }
+        KmerIndex { k, counts }
+    }
+
+    /// Fraction of the k-mers of `read` occurring more than once in the
+    /// sequence, from 0 for unique reads to 1 for reads entirely within
+    /// repeats.
+    pub fn difficulty(&self, read: &[u8]) -> f64 {
+        if read.len() < self.k {
+            return 0.0;
+        }
+        let repeated = read
+            .windows(self.k)
+            .filter(|kmer| self.counts.get(kmer).is_some_and(|&c| c > 1))
+            .count();
+        repeated as f64 / (read.len() - self.k + 1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats_score_higher_than_unique_sequence() {
+        let repeat = b"ACGTTGCAAGCTTCGATCGG";
+        let unique = b"TTAGGCATCCGATAAGCTGTACCGTAGCATGCAATC";
+        let mut seq = Vec::new();
+        seq.extend_from_slice(repeat);
+        seq.extend_from_slice(unique);
+        seq.extend_from_slice(repeat);
+        let kmers = KmerIndex::new(&seq, 8);
+
+        assert_eq!(kmers.difficulty(repeat), 1.0);
+        assert_eq!(kmers.difficulty(unique), 0.0);
+        assert_eq!(kmers.difficulty(b"ACG"), 0.0);
+    }
+}
@@ -0,0 +1,137 @@
+//! Errors of the library, and their reporting by the binaries.
+
+use crate::signal;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::process;
+
+/// Error of the sonicat tools.
+#[derive(Debug)]
+pub enum SonicatError {
+    /// Reading or writing a file or stream failed.
+    Io(io::Error),
+    /// An input file is malformed.
+    Parse(String),
+    /// A parameter is missing, malformed or out of range.
+    Parameter(String),
+    /// A sequence holds a symbol outside its alphabet.
+    Alphabet { id: String, symbol: u8 },
+}
+
+pub type Result<T> = std::result::Result<T, SonicatError>;
+
+impl SonicatError {
+    /// Exit status of the error, after sysexits.h.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SonicatError::Io(_) => 74,
+            SonicatError::Parse(_) | SonicatError::Alphabet { .. } => 65,
+            SonicatError::Parameter(_) => 64,
+        }
+    }
+}
+
+impl fmt::Display for SonicatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SonicatError::Io(e) => write!(f, "{}", e),
+            SonicatError::Parse(msg) | SonicatError::Parameter(msg) => write!(f, "{}", msg),
+            SonicatError::Alphabet { id, symbol } => write!(
+                f,
+                "record {} contains non-alphabet character {:?}",
+                id, *symbol as char
+            ),
+        }
+    }
+}
+
+impl Error for SonicatError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SonicatError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SonicatError {
+    fn from(e: io::Error) -> SonicatError {
+        SonicatError::Io(e)
+    }
+}
+
+/// Value of a result, for the binaries: on an error, log it and exit with
+/// its status. A pipe or FIFO closed by its reader, as `head` does, exits
+/// quietly with the status of SIGPIPE.
+pub trait OrExit<T> {
+    fn or_exit(self) -> T;
+}
+
+impl<T, E: Into<SonicatError>> OrExit<T> for std::result::Result<T, E> {
+    fn or_exit(self) -> T {
+        self.unwrap_or_else(|e| {
+            let e = e.into();
+            if matches!(&e, SonicatError::Io(x) if x.kind() == io::ErrorKind::BrokenPipe) {
+                process::exit(signal::BROKEN_PIPE_STATUS);
+            }
+            // logged as the binary rather than this module
+            let program = env::args_os()
+                .next()
+                .and_then(|x| Some(Path::new(&x).file_stem()?.to_string_lossy().into_owned()))
+                .unwrap_or_default();
+            log::error!(target: &program, "{}", e);
+            process::exit(e.exit_code())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::{open_input, record_error};
+    use crate::mutator::Mutator;
+    use crate::vcf::read_variants;
+    use bio::io::fasta;
+    use rand::rngs::mock::StepRng;
+
+    #[test]
+    fn bad_inputs_give_their_variant() {
+        let e = open_input(Some(Path::new("/nonexistent/genome.fa"))).map(|_| ());
+        let e = SonicatError::from(e.unwrap_err());
+        assert!(matches!(e, SonicatError::Io(_)));
+        assert_eq!(e.exit_code(), 74);
+        assert!(e.source().is_some());
+
+        let e = read_variants(&b"chr1\tone\t.\tA\tC\n"[..]).unwrap_err();
+        assert!(matches!(e, SonicatError::Parse(_)));
+        assert_eq!(e.exit_code(), 65);
+        assert!(e.source().is_none());
+
+        let record = fasta::Reader::new(&b"ACGT\n"[..]).records().next().unwrap();
+        let e = record_error(record.unwrap_err());
+        assert!(matches!(e, SonicatError::Parse(_)));
+        assert_eq!(e.exit_code(), 65);
+
+        let e = Mutator::new(2.0, 0.0, 0.0, StepRng::new(0, 1))
+            .err()
+            .unwrap();
+        assert!(matches!(e, SonicatError::Parameter(_)));
+        assert_eq!(e.exit_code(), 64);
+    }
+
+    #[test]
+    fn alphabet_errors_name_the_record_and_symbol() {
+        let e = SonicatError::Alphabet {
+            id: "chr1".to_string(),
+            symbol: b'X',
+        };
+        assert_eq!(e.exit_code(), 65);
+        assert_eq!(
+            e.to_string(),
+            "record chr1 contains non-alphabet character 'X'"
+        );
+    }
+}
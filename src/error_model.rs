@@ -0,0 +1,98 @@
+//! Sequencing errors of simulated reads.
+
+use crate::config;
+use crate::error::Result;
+use crate::mutator::{self, MutationOutcome};
+use rand::Rng;
+
+/// Substitution errors introduced by the sequencer, independently at each
+/// base with a fixed rate.
+pub struct ErrorModel {
+    rate: f64,
+}
+
+impl ErrorModel {
+    /// Errors at `rate` per base, an error unless within [0, 1].
+    pub fn new(rate: f64) -> Result<ErrorModel> {
+        let rate = config::fraction("error rate", rate)?;
+        Ok(ErrorModel { rate })
+    }
+
+    /// Introduce errors into `read`, returning the number of bases changed.
+    ///
+    /// An erroneous base is replaced by one of the three other bases, in its
+    /// case; an ambiguous base is replaced by any concrete base.
+    pub fn apply<R: Rng>(&self, read: &mut [u8], rng: &mut R) -> usize {
+        self.apply_with(read, rng, |_, _, _| {})
+    }
+
+    /// Introduce errors into `read` as `apply` does, calling `f` with the
+    /// position, original base and new base of every error.
+    pub fn apply_with<R: Rng, F: FnMut(usize, u8, u8)>(
+        &self,
+        read: &mut [u8],
+        rng: &mut R,
+        mut f: F,
+    ) -> usize {
+        let mut errors = 0;
+        for (i, b) in read.iter_mut().enumerate() {
+            if !rng.gen_bool(self.rate) {
+                continue;
+            }
+            let outcome = MutationOutcome::Substitute(mutator::saturate(rng, *b));
+            let MutationOutcome::Substitute(new) = mutator::match_case(outcome, *b) else {
+                unreachable!("a substitution keeps to a substitution")
+            };
+            f(i, *b, new);
+            *b = new;
+            errors += 1;
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn rates_of_0_and_1_change_no_base_and_every_base() {
+        let read = b"ACGTNacgtn".to_vec();
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let mut kept = read.clone();
+        assert_eq!(ErrorModel::new(0.0).unwrap().apply(&mut kept, &mut rng), 0);
+        assert_eq!(kept, read);
+
+        let mut changed = read.clone();
+        let n = ErrorModel::new(1.0).unwrap().apply(&mut changed, &mut rng);
+        assert_eq!(n, read.len());
+        for (a, b) in read.iter().zip(&changed) {
+            assert_ne!(a.to_ascii_uppercase(), b.to_ascii_uppercase());
+            assert_eq!(a.is_ascii_lowercase(), b.is_ascii_lowercase());
+        }
+
+        assert!(ErrorModel::new(1.5).is_err());
+    }
+
+    #[test]
+    fn errors_never_keep_the_original_base() {
+        let model = ErrorModel::new(0.5).unwrap();
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..100 {
+            let mut read = b"ACGTacgtNRY".to_vec();
+            let original = read.clone();
+            let mut errors = 0;
+            let n = model.apply_with(&mut read, &mut rng, |i, from, to| {
+                assert_eq!(from, original[i]);
+                assert_ne!(from.to_ascii_uppercase(), to.to_ascii_uppercase());
+                assert!(b"ACGTacgt".contains(&to));
+                errors += 1;
+            });
+            assert_eq!(n, errors);
+            let changed = original.iter().zip(&read).filter(|(a, b)| a != b).count();
+            assert_eq!(changed, n);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Expected outcomes of a simulation, the reference the counts of a run
+//! are reported against.
+
+use crate::sonicator::Tail;
+
+/// Expected number of reads sonicated from a sequence of `genome_len`
+/// bases, `depth * ceil(starts / step)`.
+///
+/// Every `step`-th window of `read_len` bases starts a number of reads with
+/// mean `depth`, so `depth` is the mean number of reads per start rather
+/// than the mean coverage per base. A window starts at every base unless
+/// `tail` drops those running past the end, in which case there are
+/// `genome_len - read_len + 1` starts and a sequence shorter than the reads
+/// yields none.
+pub fn expected_reads(
+    genome_len: usize,
+    depth: f64,
+    read_len: usize,
+    step: usize,
+    tail: Tail,
+) -> f64 {
+    let starts = if tail == Tail::Drop {
+        (genome_len + 1).saturating_sub(read_len)
+    } else {
+        genome_len
+    };
+    depth * starts.div_ceil(step) as f64
+}
+
+/// Expected number of mutations in a sequence of `genome_len` bases,
+/// `genome_len * rate`, as each base is mutated independently with
+/// probability `rate`.
+pub fn expected_mutations(genome_len: usize, rate: f64) -> f64 {
+    genome_len as f64 * rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_are_expected_at_every_window() {
+        assert_eq!(expected_reads(1000, 50.0, 150, 1, Tail::Drop), 42550.0);
+        assert_eq!(expected_reads(150, 2.0, 150, 1, Tail::Drop), 2.0);
+        assert_eq!(expected_reads(100, 50.0, 150, 1, Tail::Drop), 0.0);
+        assert_eq!(expected_reads(1000, 0.0, 150, 1, Tail::Drop), 0.0);
+    }
+
+    #[test]
+    fn reads_are_expected_at_every_step_and_tail() {
+        // starts 0, 10, .., 850
+        assert_eq!(expected_reads(1000, 2.0, 150, 10, Tail::Drop), 172.0);
+        assert_eq!(expected_reads(1000, 2.0, 150, 1, Tail::Shrink), 2000.0);
+        assert_eq!(expected_reads(1000, 2.0, 150, 3, Tail::Wrap), 668.0);
+        assert_eq!(expected_reads(100, 1.0, 150, 1, Tail::Wrap), 100.0);
+    }
+
+    #[test]
+    fn mutations_are_expected_at_every_base() {
+        assert_eq!(expected_mutations(1000, 0.01), 10.0);
+        assert_eq!(expected_mutations(0, 0.5), 0.0);
+        assert_eq!(expected_mutations(1_000_000, 0.0016), 1600.0);
+    }
+}
@@ -0,0 +1,707 @@
+use crate::error::SonicatError;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::str::FromStr;
+#[cfg(feature = "remote")]
+use std::time::Duration;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const BZIP2_MAGIC: [u8; 3] = *b"BZh";
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Error for a codec or source sonicat was built without.
+#[cfg(not(all(
+    feature = "gzip",
+    feature = "zstd",
+    feature = "bzip2",
+    feature = "bgzf",
+    feature = "remote"
+)))]
+fn unsupported(what: &str, feature: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "{} requires sonicat built with the {} feature",
+            what, feature
+        ),
+    )
+}
+
+fn is_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|x| x.starts_with("http://") || x.starts_with("https://"))
+}
+
+/// Stream the body of `url`, failing on error statuses.
+#[cfg(feature = "remote")]
+fn open_url(url: &str) -> io::Result<Box<dyn Read>> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(30))
+        .timeout_read(Duration::from_secs(60))
+        .build();
+    match agent.get(url).call() {
+        Ok(response) => Ok(Box::new(response.into_reader())),
+        Err(ureq::Error::Status(code, _)) => Err(io::Error::other(format!(
+            "{} returned HTTP status {}",
+            url, code
+        ))),
+        Err(e) => Err(io::Error::other(e)),
+    }
+}
+
+#[cfg(not(feature = "remote"))]
+fn open_url(_url: &str) -> io::Result<Box<dyn Read>> {
+    Err(unsupported("URL input", "remote"))
+}
+
+/// Open the input at `path`, stdin if `None` or `-`, streamed over HTTP if
+/// an `http://` or `https://` URL.
+///
+/// Gzip, zstd and bzip2 input, from a file or a pipe, is detected from its
+/// magic bytes and decompressed as a stream, so records are read one by one
+/// without inflating the whole file.
+pub fn open_input(path: Option<&Path>) -> io::Result<Box<dyn BufRead>> {
+    let fin: Box<dyn Read> = match path {
+        Some(f) if is_url(f) => open_url(f.to_str().unwrap())?,
+        Some(f) if f != Path::new("-") => Box::new(
+            File::open(f)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", f.display(), e)))?,
+        ),
+        _ => Box::new(io::stdin()),
+    };
+    let mut fin = BufReader::new(fin);
+    let magic = fin.fill_buf()?;
+
+    if magic.starts_with(&GZIP_MAGIC) {
+        #[cfg(feature = "gzip")]
+        return Ok(Box::new(BufReader::new(
+            flate2::bufread::MultiGzDecoder::new(fin),
+        )));
+        #[cfg(not(feature = "gzip"))]
+        return Err(unsupported("gzip compression", "gzip"));
+    }
+    if magic.starts_with(&ZSTD_MAGIC) {
+        #[cfg(feature = "zstd")]
+        return Ok(Box::new(BufReader::new(zstd::Decoder::with_buffer(fin)?)));
+        #[cfg(not(feature = "zstd"))]
+        return Err(unsupported("zstd compression", "zstd"));
+    }
+    if magic.starts_with(&BZIP2_MAGIC) {
+        #[cfg(feature = "bzip2")]
+        return Ok(Box::new(BufReader::new(
+            bzip2::bufread::MultiBzDecoder::new(fin),
+        )));
+        #[cfg(not(feature = "bzip2"))]
+        return Err(unsupported("bzip2 compression", "bzip2"));
+    }
+    Ok(Box::new(fin))
+}
+
+/// Error of reading a FASTA record. bio reports a record it cannot parse,
+/// and the decoders a corrupt stream, as errors of their own kind; these
+/// are malformed input rather than failed I/O.
+pub fn record_error(e: io::Error) -> SonicatError {
+    match e.kind() {
+        io::ErrorKind::Other | io::ErrorKind::InvalidData => {
+            SonicatError::Parse(format!("malformed FASTA record: {}", e))
+        }
+        _ => SonicatError::Io(e),
+    }
+}
+
+/// Paths listed in the file at `path`, one per line. Blank lines and lines
+/// starting with `#` are skipped.
+///
+/// A line is taken as is but for its line ending, spaces included. Relative
+/// paths are relative to the directory of a local list, and `-` and URLs are
+/// kept as they are.
+pub fn read_list(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let dir = path.parent().filter(|_| !is_url(path));
+    let mut paths = Vec::new();
+    for line in open_input(Some(path))?.lines() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let listed = Path::new(&line);
+        match dir {
+            Some(dir) if listed != Path::new("-") && !is_url(listed) => {
+                paths.push(dir.join(listed))
+            }
+            _ => paths.push(listed.to_path_buf()),
+        }
+    }
+    Ok(paths)
+}
+
+/// Inputs read one after the other, each opened by [`open_input`] once the
+/// previous one is exhausted.
+struct Inputs {
+    paths: VecDeque<PathBuf>,
+    current: Option<Box<dyn BufRead>>,
+}
+
+impl Read for Inputs {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let current = match &mut self.current {
+                Some(x) => x,
+                None => match self.paths.pop_front() {
+                    Some(path) => self.current.insert(open_input(Some(&path))?),
+                    None => return Ok(0),
+                },
+            };
+            let n = current.read(buf)?;
+            if n > 0 {
+                return Ok(n);
+            }
+            self.current = None;
+            // a newline ends a last line without one, so that the next
+            // input starts a record
+            if !buf.is_empty() && !self.paths.is_empty() {
+                buf[0] = b'\n';
+                return Ok(1);
+            }
+        }
+    }
+}
+
+/// Open the inputs at `paths` as a single stream of their contents in
+/// order, each decompressed as by [`open_input`]. They are opened one at a
+/// time, so that a long list holds a single file open.
+pub fn open_inputs(paths: Vec<PathBuf>) -> io::Result<Box<dyn BufRead>> {
+    Ok(Box::new(BufReader::new(Inputs {
+        paths: paths.into(),
+        current: None,
+    })))
+}
+
+/// Format of the reads written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Fasta,
+    /// Unwrapped four-line FASTQ records.
+    Fastq,
+    /// Unaligned BAM, written only with the bam feature.
+    Ubam,
+}
+
+impl Format {
+    /// Format and compression of a format optionally followed by a
+    /// compression extension, as `fastq.gz`.
+    pub fn parse_compressed(s: &str) -> Result<(Format, Option<Compression>), SonicatError> {
+        match s.split_once('.') {
+            Some((format, ext)) => match (format.parse()?, Compression::from_extension(ext)) {
+                (Format::Ubam, _) | (_, None) => Err(SonicatError::Parameter(format!(
+                    "unknown compressed format {}",
+                    s
+                ))),
+                (format, compression) => Ok((format, compression)),
+            },
+            None => Ok((s.parse()?, None)),
+        }
+    }
+
+    /// Extension of files of the format, with its dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Fasta => ".fa",
+            Format::Fastq => ".fq",
+            Format::Ubam => ".bam",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = SonicatError;
+
+    fn from_str(s: &str) -> Result<Format, SonicatError> {
+        match s {
+            "fasta" => Ok(Format::Fasta),
+            "fastq" => Ok(Format::Fastq),
+            "ubam" => Ok(Format::Ubam),
+            _ => Err(SonicatError::Parameter(format!("unknown format {}", s))),
+        }
+    }
+}
+
+/// Compression of an output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+    /// Blocked gzip, readable as gzip and indexable by htslib.
+    Bgzf,
+}
+
+// extensions of compressed files, both recognized and written
+const COMPRESSION_EXTENSIONS: [(Compression, &str); 4] = [
+    (Compression::Gzip, ".gz"),
+    (Compression::Zstd, ".zst"),
+    (Compression::Bzip2, ".bz2"),
+    (Compression::Bgzf, ".bgz"),
+];
+
+impl Compression {
+    /// Compression of the extension `ext`, without its dot.
+    pub fn from_extension(ext: &str) -> Option<Compression> {
+        COMPRESSION_EXTENSIONS
+            .iter()
+            .find(|x| x.1.strip_prefix('.') == Some(ext))
+            .map(|x| x.0)
+    }
+
+    /// Compression implied by the extension of `path`, none for stdout.
+    pub fn from_path(path: Option<&Path>) -> Compression {
+        path.and_then(Path::extension)
+            .and_then(|x| x.to_str())
+            .and_then(Compression::from_extension)
+            .unwrap_or(Compression::None)
+    }
+
+    /// Extension of files of the compression, with its dot, empty for none.
+    pub fn extension(self) -> &'static str {
+        COMPRESSION_EXTENSIONS
+            .iter()
+            .find(|x| x.0 == self)
+            .map_or("", |x| x.1)
+    }
+
+    /// File name `name` without its compression extension, and the
+    /// compression the extension implies.
+    pub fn split_extension(name: &str) -> (&str, Compression) {
+        name.rsplit_once('.')
+            .and_then(|(rest, ext)| Some((rest, Compression::from_extension(ext)?)))
+            .unwrap_or((name, Compression::None))
+    }
+}
+
+impl FromStr for Compression {
+    type Err = SonicatError;
+
+    fn from_str(s: &str) -> Result<Compression, SonicatError> {
+        match s {
+            "none" => Ok(Compression::None),
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            "bzip2" => Ok(Compression::Bzip2),
+            "bgzf" => Ok(Compression::Bgzf),
+            _ => Err(SonicatError::Parameter(format!(
+                "unknown compression {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Output created by [`create_output`], a file or stdout compressed as
+/// asked.
+///
+/// The compressed stream is only complete once finished by
+/// [`Output::finish`], which reports the errors of the last writes. An
+/// output dropped unfinished is finished then, its errors ignored.
+pub struct Output {
+    // none once finished
+    encoder: Option<Encoder>,
+}
+
+enum Encoder {
+    Plain(Box<dyn Write>),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<Box<dyn Write>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, Box<dyn Write>>),
+    #[cfg(feature = "bzip2")]
+    Bzip2(bzip2::write::BzEncoder<Box<dyn Write>>),
+    #[cfg(feature = "bgzf")]
+    Bgzf(noodles_bgzf::io::Writer<Box<dyn Write>>),
+}
+
+impl Encoder {
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Encoder::Plain(w) => w,
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(w) => w,
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(w) => w,
+            #[cfg(feature = "bzip2")]
+            Encoder::Bzip2(w) => w,
+            #[cfg(feature = "bgzf")]
+            Encoder::Bgzf(w) => w,
+        }
+    }
+
+    /// End the compressed stream and flush the output under it.
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Encoder::Plain(mut w) => w.flush(),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(w) => w.finish()?.flush(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(w) => w.finish()?.flush(),
+            #[cfg(feature = "bzip2")]
+            Encoder::Bzip2(w) => w.finish()?.flush(),
+            #[cfg(feature = "bgzf")]
+            Encoder::Bgzf(w) => w.finish()?.flush(),
+        }
+    }
+}
+
+impl Output {
+    /// Finish the output; writing to it afterwards is an error.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.encoder.take().map_or(Ok(()), Encoder::finish)
+    }
+
+    fn writer(&mut self) -> io::Result<&mut dyn Write> {
+        match &mut self.encoder {
+            Some(x) => Ok(x.writer()),
+            None => Err(io::Error::other("output written to once finished")),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer()?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer()?.flush()
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+/// An [`Output`] shared with a writer that takes ownership of its output,
+/// as those of bio do, to be finished once the writer is flushed.
+#[derive(Clone)]
+pub struct SharedOutput(Rc<RefCell<Output>>);
+
+impl SharedOutput {
+    pub fn new(output: Output) -> SharedOutput {
+        SharedOutput(Rc::new(RefCell::new(output)))
+    }
+
+    /// Finish the output, see [`Output::finish`].
+    pub fn finish(&self) -> io::Result<()> {
+        self.0.borrow_mut().finish()
+    }
+}
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().flush()
+    }
+}
+
+/// Flush the buffer of `w` and finish the output under it.
+pub fn finish_buffered(w: io::BufWriter<Output>) -> io::Result<()> {
+    w.into_inner()
+        .map_err(io::IntoInnerError::into_error)?
+        .finish()
+}
+
+/// Create the output at `path`, stdout if `None` or `-`. An existing
+/// regular file is only replaced if `overwrite` is set.
+///
+/// The compressed stream is ended by [`Output::finish`]. Codecs sonicat
+/// was built without are reported before the output is created.
+pub fn create_output(
+    path: Option<&Path>,
+    compression: Compression,
+    overwrite: bool,
+) -> io::Result<Output> {
+    let open = || -> io::Result<Box<dyn Write>> {
+        match path {
+            Some(f) if f != Path::new("-") => {
+                let mut options = OpenOptions::new();
+                // devices and pipes are written to, not replaced
+                let special = fs::metadata(f).is_ok_and(|m| !m.is_file());
+                if overwrite || special {
+                    options.write(true).create(true).truncate(true);
+                } else {
+                    options.write(true).create_new(true);
+                }
+                match options.open(f) {
+                    Ok(fout) => Ok(Box::new(fout)),
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(io::Error::new(
+                        e.kind(),
+                        format!("{} exists, refusing to overwrite it", f.display()),
+                    )),
+                    Err(e) => Err(e),
+                }
+            }
+            _ => Ok(Box::new(io::stdout())),
+        }
+    };
+
+    let encoder = match compression {
+        Compression::None => Encoder::Plain(open()?),
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => Encoder::Gzip(flate2::write::GzEncoder::new(
+            open()?,
+            flate2::Compression::default(),
+        )),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Encoder::Zstd(zstd::Encoder::new(open()?, 0)?),
+        #[cfg(feature = "bzip2")]
+        Compression::Bzip2 => Encoder::Bzip2(bzip2::write::BzEncoder::new(
+            open()?,
+            bzip2::Compression::default(),
+        )),
+        #[cfg(feature = "bgzf")]
+        Compression::Bgzf => Encoder::Bgzf(noodles_bgzf::io::Writer::new(open()?)),
+        #[cfg(not(feature = "gzip"))]
+        Compression::Gzip => return Err(unsupported("gzip compression", "gzip")),
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd => return Err(unsupported("zstd compression", "zstd")),
+        #[cfg(not(feature = "bzip2"))]
+        Compression::Bzip2 => return Err(unsupported("bzip2 compression", "bzip2")),
+        #[cfg(not(feature = "bgzf"))]
+        Compression::Bgzf => return Err(unsupported("bgzf compression", "bgzf")),
+    };
+    Ok(Output {
+        encoder: Some(encoder),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bio::io::fasta;
+    use std::env;
+    use std::process;
+
+    /// Path `name` in the temporary directory, unique to the test process.
+    fn tmp(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("sonicat-{}-{}", process::id(), name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_records_are_streamed() {
+        let path = tmp("streamed.fa.gz");
+        let mut fout = create_output(Some(&path), Compression::Gzip, false).unwrap();
+        fout.write_all(b">a\nACGT\n>b\n").unwrap();
+        // a long second record, little of which is decompressed for the first
+        let mut x: u32 = 1;
+        for _ in 0..1 << 18 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            fout.write_all(&[b"ACGT"[(x >> 30) as usize]]).unwrap();
+        }
+        fout.write_all(b"\n>c\nTT\n").unwrap();
+        fout.finish().unwrap();
+
+        let ids: Vec<String> = fasta::Reader::new(open_input(Some(&path)).unwrap())
+            .records()
+            .map(|x| x.unwrap().id().to_string())
+            .collect();
+        assert_eq!(ids, ["a", "b", "c"]);
+
+        // the first record is read before the truncation is reached
+        let gz = fs::read(&path).unwrap();
+        let truncated = tmp("truncated.fa.gz");
+        fs::write(&truncated, &gz[..gz.len() / 2]).unwrap();
+        let mut records = fasta::Reader::new(open_input(Some(&truncated)).unwrap()).records();
+        assert_eq!(records.next().unwrap().unwrap().seq(), b"ACGT");
+        assert!(records.any(|x| x.is_err()));
+        fs::remove_file(path).unwrap();
+        fs::remove_file(truncated).unwrap();
+    }
+
+    /// Contents written with `compression` and read back through [`open_input`].
+    fn round_trip(compression: Compression) -> io::Result<Vec<u8>> {
+        let path = tmp(&format!("round-trip{}", compression.extension()));
+        let mut fout = create_output(Some(&path), compression, false)?;
+        fout.write_all(b">r\nACGT\n")?;
+        fout.finish()?;
+        let mut contents = Vec::new();
+        open_input(Some(&path))?.read_to_end(&mut contents)?;
+        fs::remove_file(path)?;
+        Ok(contents)
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_round_trips() {
+        assert_eq!(round_trip(Compression::Gzip).unwrap(), b">r\nACGT\n");
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_round_trips() {
+        assert_eq!(round_trip(Compression::Zstd).unwrap(), b">r\nACGT\n");
+    }
+
+    #[cfg(feature = "bzip2")]
+    #[test]
+    fn bzip2_round_trips() {
+        assert_eq!(round_trip(Compression::Bzip2).unwrap(), b">r\nACGT\n");
+    }
+
+    #[cfg(feature = "bgzf")]
+    #[test]
+    fn bgzf_round_trips() {
+        assert_eq!(round_trip(Compression::Bgzf).unwrap(), b">r\nACGT\n");
+    }
+
+    // the encoder holds the record, written only once finished
+    #[cfg(all(target_os = "linux", feature = "zstd"))]
+    #[test]
+    fn finishing_reports_failed_writes() {
+        let path = Path::new("/dev/full");
+        let mut fout = create_output(Some(path), Compression::Zstd, false).unwrap();
+        fout.write_all(b">r\nACGT\n").unwrap();
+        assert!(fout.finish().is_err());
+        // finished, even though it failed
+        assert!(fout.write_all(b">r\nACGT\n").is_err());
+    }
+
+    #[test]
+    fn unbuilt_codecs_are_reported() {
+        let codecs = [
+            (Compression::Gzip, cfg!(feature = "gzip"), "gzip"),
+            (Compression::Zstd, cfg!(feature = "zstd"), "zstd"),
+            (Compression::Bzip2, cfg!(feature = "bzip2"), "bzip2"),
+            (Compression::Bgzf, cfg!(feature = "bgzf"), "bgzf"),
+        ];
+        for (compression, built, feature) in codecs {
+            if !built {
+                let path = tmp("unbuilt");
+                let e = create_output(Some(&path), compression, false)
+                    .err()
+                    .unwrap();
+                assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+                assert!(e.to_string().contains(&format!("the {} feature", feature)));
+                // reported before the output is created
+                assert!(!path.exists());
+            }
+        }
+        assert_eq!(round_trip(Compression::None).unwrap(), b">r\nACGT\n");
+    }
+
+    /// URL of a local server answering a single request with `status` and
+    /// `body`.
+    #[cfg(feature = "remote")]
+    fn serve_once(status: &str, body: &'static [u8]) -> String {
+        use std::net::TcpListener;
+        use std::thread;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/ref.fa", listener.local_addr().unwrap());
+        let status = status.to_string();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // the request is read up to the blank line ending its headers
+            let mut request = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while request.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(body).unwrap();
+        });
+        url
+    }
+
+    #[cfg(feature = "remote")]
+    #[test]
+    fn urls_are_streamed() {
+        let url = serve_once("200 OK", b">r\nACGT\n");
+        let mut contents = Vec::new();
+        open_input(Some(Path::new(&url)))
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert_eq!(contents, b">r\nACGT\n");
+    }
+
+    #[cfg(feature = "remote")]
+    #[test]
+    fn url_errors_are_reported() {
+        let url = serve_once("404 Not Found", b"");
+        let e = open_input(Some(Path::new(&url))).err().unwrap();
+        assert!(e.to_string().contains("returned HTTP status 404"), "{}", e);
+    }
+
+    #[cfg(not(feature = "remote"))]
+    #[test]
+    fn urls_require_the_remote_feature() {
+        let e = open_input(Some(Path::new("https://example.org/ref.fa")))
+            .err()
+            .unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn listed_inputs_are_read_in_order() {
+        let (a, b, list) = (tmp("list-a.fa"), tmp("list-b.fa"), tmp("list.txt"));
+        // a last line without a newline still ends its record
+        fs::write(&a, ">a\nACGT").unwrap();
+        fs::write(&b, ">b\nGGCC\n").unwrap();
+        let listing = format!("# inputs\n{}\r\n\n  \n{}\n", a.display(), b.display());
+        fs::write(&list, listing).unwrap();
+
+        let paths = read_list(&list).unwrap();
+        assert_eq!(paths, [a.clone(), b.clone()]);
+        let ids: Vec<String> = fasta::Reader::new(open_inputs(paths).unwrap())
+            .records()
+            .map(|x| x.unwrap().id().to_string())
+            .collect();
+        assert_eq!(ids, ["a", "b"]);
+
+        let missing = vec![a.clone(), tmp("list-missing.fa")];
+        let mut contents = Vec::new();
+        assert!(open_inputs(missing)
+            .unwrap()
+            .read_to_end(&mut contents)
+            .is_err());
+        for path in [a, b, list] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn listed_paths_keep_their_spaces_and_are_relative_to_the_list() {
+        let list = tmp("relative-list.txt");
+        let url = "https://example.org/ref.fa";
+        fs::write(&list, format!(" a b.fa \nsub/c.fa\n/abs.fa\n-\n{}\n", url)).unwrap();
+
+        let dir = list.parent().unwrap();
+        assert_eq!(
+            read_list(&list).unwrap(),
+            [
+                dir.join(" a b.fa "),
+                dir.join("sub/c.fa"),
+                PathBuf::from("/abs.fa"),
+                PathBuf::from("-"),
+                PathBuf::from(url),
+            ]
+        );
+        fs::remove_file(list).unwrap();
+    }
+}
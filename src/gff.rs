@@ -0,0 +1,126 @@
+//! Coding regions of a GFF3 annotation.
+
+use crate::error::{Result, SonicatError};
+use bio::io::gff::{self, GffType};
+use std::collections::HashMap;
+use std::io::BufRead;
+
+/// A CDS feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cds {
+    /// 0-based start, inclusive.
+    pub start: usize,
+    /// 0-based end, exclusive.
+    pub end: usize,
+    /// On the reverse strand.
+    pub reverse: bool,
+    /// Bases before the first complete codon, read on the coding strand.
+    pub phase: usize,
+}
+
+/// Read the CDS features of a GFF3 file grouped by sequence, in file order.
+pub fn read_cds<R: BufRead>(reader: R) -> Result<HashMap<String, Vec<Cds>>> {
+    let mut cds: HashMap<String, Vec<Cds>> = HashMap::new();
+
+    for record in gff::Reader::new(reader, GffType::GFF3).records() {
+        let record =
+            record.map_err(|e| SonicatError::Parse(format!("malformed GFF record: {}", e)))?;
+        if record.feature_type() != "CDS" || *record.start() == 0 {
+            continue;
+        }
+        cds.entry(record.seqname().to_string())
+            .or_default()
+            .push(Cds {
+                start: *record.start() as usize - 1,
+                end: *record.end() as usize,
+                reverse: record.strand().is_some_and(|x| x.strand_symbol() == "-"),
+                phase: record.frame().parse().unwrap_or(0),
+            });
+    }
+
+    Ok(cds)
+}
+
+/// Whether each of `len` bases lies in one of `cds`.
+pub fn mask(len: usize, cds: &[Cds]) -> Vec<bool> {
+    let mut within = vec![false; len];
+    for x in cds {
+        let end = x.end.min(len);
+        if x.start < end {
+            within[x.start..end].fill(true);
+        }
+    }
+    within
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GFF: &[u8] = b"##gff-version 3
+chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1
+chr1\t.\tCDS\t11\t40\t.\t+\t0\tParent=g1
+chr1\t.\tCDS\t61\t90\t.\t-\t2\tParent=g1
+chr2\t.\tCDS\t1\t9\t.\t+\t.\tID=c
+";
+
+    #[test]
+    fn cds_features_are_read_0_based() {
+        let cds = read_cds(GFF).unwrap();
+        assert_eq!(cds.len(), 2);
+        assert_eq!(
+            cds["chr1"],
+            [
+                Cds {
+                    start: 10,
+                    end: 40,
+                    reverse: false,
+                    phase: 0
+                },
+                Cds {
+                    start: 60,
+                    end: 90,
+                    reverse: true,
+                    phase: 2
+                },
+            ]
+        );
+        // an unset phase is read as 0
+        assert_eq!(
+            cds["chr2"],
+            [Cds {
+                start: 0,
+                end: 9,
+                reverse: false,
+                phase: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn malformed_records_are_parse_errors() {
+        let gff = b"chr1\t.\tCDS\tone\t40\t.\t+\t0\t.\n";
+        assert!(matches!(read_cds(&gff[..]), Err(SonicatError::Parse(_))));
+    }
+
+    #[test]
+    fn masks_cover_the_cds_within_the_sequence() {
+        let cds = [
+            Cds {
+                start: 1,
+                end: 3,
+                reverse: false,
+                phase: 0,
+            },
+            Cds {
+                start: 5,
+                end: 20,
+                reverse: true,
+                phase: 0,
+            },
+        ];
+        let within = mask(7, &cds);
+        assert_eq!(within, [false, true, true, false, false, true, true]);
+        assert_eq!(mask(3, &cds[1..]), [false; 3]);
+    }
+}
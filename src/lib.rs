@@ -0,0 +1,25 @@
+//! Shared routines of the sonicat tools.
+
+pub mod alphabet;
+pub mod codon;
+pub mod config;
+pub mod coords;
+pub mod coverage;
+pub mod cpg;
+pub mod difficulty;
+pub mod error;
+pub mod error_model;
+pub mod expected;
+pub mod file;
+pub mod gff;
+pub mod logging;
+pub mod mutator;
+pub mod protein;
+pub mod provenance;
+pub mod quality;
+pub mod rng;
+pub mod signal;
+pub mod sonicator;
+pub mod tagmentation;
+pub mod validate;
+pub mod vcf;
@@ -0,0 +1,52 @@
+use env_logger::WriteStyle;
+use log::LevelFilter;
+use std::env;
+use std::time::Duration;
+
+/// Set up logging to stderr.
+///
+/// Warnings and errors are shown by default, each `--verbose` shows one
+/// more level and `--quiet` only shows errors. Without either flag,
+/// `RUST_LOG` sets the level; with one, its module directives are ignored
+/// too, as they would override the flag.
+///
+/// Levels are colored when stderr is a terminal, unless `NO_COLOR` is set
+/// to a non-empty value (<https://no-color.org>).
+pub fn init(verbose: u64, quiet: bool) {
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(LevelFilter::Warn);
+
+    if quiet {
+        builder.filter_level(LevelFilter::Error);
+    } else if verbose > 0 {
+        let level = match verbose {
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        };
+        builder.filter_level(level);
+    } else {
+        builder.parse_env("RUST_LOG");
+    }
+
+    let style = if env::var_os("NO_COLOR").is_some_and(|x| !x.is_empty()) {
+        WriteStyle::Never
+    } else {
+        WriteStyle::Auto
+    };
+
+    builder.write_style(style).format_timestamp(None).init();
+}
+
+/// Print the time taken over `bases` bases of `what`, and the throughput, to
+/// stderr.
+pub fn report_timing(what: &str, bases: u64, elapsed: Duration) {
+    let seconds = elapsed.as_secs_f64();
+    eprintln!(
+        "{}\t{}\t{:.3}\t{:.0}",
+        what,
+        bases,
+        seconds,
+        bases as f64 / seconds
+    );
+}
@@ -0,0 +1,1244 @@
+use crate::alphabet::{self, Base};
+use crate::codon::CodonSite;
+use crate::config;
+use crate::error::{Result, SonicatError};
+use crate::vcf::Variant;
+use rand::{
+    distributions::{Uniform, WeightedIndex},
+    rngs::StdRng,
+    Rng, SeedableRng,
+};
+use std::str::FromStr;
+
+// Rounding in `1.0 - s - i - d` can leave the no-change weight slightly
+// negative when the rates sum to one; weights down to this are clamped to zero.
+const WEIGHT_TOLERANCE: f64 = 1e-12;
+
+/// Weights of substitution, insertion, deletion and no change, an error
+/// unless the rates are within [0, 1] and sum to at most 1.
+pub(crate) fn weights(s: f64, i: f64, d: f64) -> Result<[f64; 4]> {
+    let s = config::fraction("substitution rate", s)?;
+    let i = config::fraction("insertion rate", i)?;
+    let d = config::fraction("deletion rate", d)?;
+    let keep = 1.0 - s - i - d;
+    if keep < -WEIGHT_TOLERANCE {
+        return Err(SonicatError::Parameter(format!(
+            "mutation rates {} + {} + {} sum to more than 1",
+            s, i, d
+        )));
+    }
+    Ok([s, i, d, keep.max(0.0)])
+}
+
+/// Fate of a single base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutationOutcome {
+    Keep(u8),
+    Substitute(u8),
+    /// The original base followed by an inserted base.
+    Insert(u8, u8),
+    Delete,
+}
+
+/// Number of bins of the indel length histograms, the last holding all
+/// events at least this long.
+pub const INDEL_BINS: usize = 10;
+
+impl MutationOutcome {
+    /// The edit of base `b` at `pos`, as a variant replacing the base alone,
+    /// `None` if it is kept.
+    pub fn edit(&self, pos: usize, b: u8) -> Option<Variant> {
+        let alt = match *self {
+            MutationOutcome::Keep(_) => return None,
+            MutationOutcome::Substitute(x) => vec![x],
+            MutationOutcome::Insert(x, y) => vec![x, y],
+            MutationOutcome::Delete => Vec::new(),
+        };
+        Some(Variant {
+            pos,
+            reference: vec![b],
+            alt,
+        })
+    }
+}
+
+/// What becomes of deleted bases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeletionMode {
+    /// Deleted bases are removed, shortening the sequence.
+    Remove,
+    /// Deleted bases are replaced by an unknown symbol, keeping the length
+    /// and the coordinates of the sequence.
+    Mask,
+}
+
+impl FromStr for DeletionMode {
+    type Err = SonicatError;
+
+    fn from_str(s: &str) -> Result<DeletionMode> {
+        match s {
+            "remove" => Ok(DeletionMode::Remove),
+            "mask" => Ok(DeletionMode::Mask),
+            _ => Err(SonicatError::Parameter(format!(
+                "unknown deletion mode {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Counts of the edits made to the mutated sequences.
+///
+/// Substitutions that drew the original base leave the sequence unchanged
+/// and are not counted. Adjacent deleted bases form a single deletion event
+/// in the length histograms, whose lengths thus sum to the base counts
+/// unless an event is longer than the last bin.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MutationStats {
+    pub bases: u64,
+    pub substitutions: u64,
+    pub insertions: u64,
+    pub deletions: u64,
+    /// Events of length `i + 1` at index `i`.
+    pub insertion_lengths: [u64; INDEL_BINS],
+    pub deletion_lengths: [u64; INDEL_BINS],
+}
+
+impl MutationStats {
+    pub fn merge(&mut self, other: &MutationStats) {
+        self.bases += other.bases;
+        self.substitutions += other.substitutions;
+        self.insertions += other.insertions;
+        self.deletions += other.deletions;
+        for i in 0..INDEL_BINS {
+            self.insertion_lengths[i] += other.insertion_lengths[i];
+            self.deletion_lengths[i] += other.deletion_lengths[i];
+        }
+    }
+
+    /// Realized substitution, insertion and deletion rates per base.
+    pub fn rates(&self) -> [f64; 3] {
+        let bases = self.bases as f64;
+        [
+            self.substitutions as f64 / bases,
+            self.insertions as f64 / bases,
+            self.deletions as f64 / bases,
+        ]
+    }
+}
+
+/// Fate of base `b` given substitution, insertion and deletion `rates`,
+/// each drawn from its own stream of `rngs`. Substituted bases are drawn by
+/// [`saturate`]. Inserted bases are drawn from `insert`, a distribution
+/// over A/C/G/T, or uniformly without it.
+///
+/// Deletion takes precedence over substitution, which takes precedence
+/// over insertion. Substitution and insertion are thus drawn with their
+/// rates conditional on the outcomes taking precedence not happening, so
+/// that the outcomes happen at the requested rates. Each stream draws
+/// once per base whatever the rates are, and further only for the base of
+/// an outcome it drew, so that varying the insertion rate changes no
+/// substitution or deletion, and the substitution rate no deletion.
+pub fn decide<R: Rng>(
+    rngs: &mut [R; 3],
+    b: u8,
+    rates: &[f64; 3],
+    insert: Option<&WeightedIndex<f64>>,
+) -> MutationOutcome {
+    decide_with(rngs, b, rates, |rng| insert_base(rng, insert))
+}
+
+/// Fate of base `b` as [`decide`], with inserted bases drawn by `insert`
+/// from the insertion stream, only once an insertion is drawn.
+fn decide_with<R: Rng>(
+    rngs: &mut [R; 3],
+    b: u8,
+    rates: &[f64; 3],
+    insert: impl FnOnce(&mut R) -> u8,
+) -> MutationOutcome {
+    let [s, i, d] = *rates;
+    let [s_rng, i_rng, d_rng] = rngs;
+
+    let substitute = happens(s_rng, conditional(s, 1.0 - d)).then(|| saturate(s_rng, b));
+    let insert = happens(i_rng, conditional(i, 1.0 - d - s)).then(|| insert(i_rng));
+    let delete = happens(d_rng, d);
+
+    match (delete, substitute, insert) {
+        (true, _, _) => MutationOutcome::Delete,
+        (_, Some(x), _) => MutationOutcome::Substitute(x),
+        (_, _, Some(y)) => MutationOutcome::Insert(b, y),
+        _ => MutationOutcome::Keep(b),
+    }
+}
+
+/// Whether an outcome of probability `p` happens, drawing from `rng` even
+/// when `p` is 0 or 1, where [`Rng::gen_bool`] draws nothing.
+fn happens<R: Rng>(rng: &mut R, p: f64) -> bool {
+    rng.gen::<f64>() < p
+}
+
+/// Probability of an outcome of rate `p` given that outcomes of total rate
+/// `1 - rest` did not happen, 0 if they always do.
+fn conditional(p: f64, rest: f64) -> f64 {
+    if rest > 0.0 {
+        (p / rest).min(1.0)
+    } else {
+        0.0
+    }
+}
+
+/// Inserted base drawn from `dist`, a distribution over A/C/G/T, or
+/// uniformly without it.
+fn insert_base<R: Rng>(rng: &mut R, dist: Option<&WeightedIndex<f64>>) -> u8 {
+    let x = match dist {
+        Some(dist) => rng.sample(dist),
+        None => rng.sample(Uniform::from(0..4)),
+    };
+    Base::ALL[x].to_u8()
+}
+
+/// Inserted base drawn with the composition around position `pos` of
+/// `local` if given and holding a concrete base, otherwise from `dist` as
+/// [`insert_base`]. The local distribution changes along the sequence and
+/// is built for each insertion.
+fn draw_insert<R: Rng>(
+    rng: &mut R,
+    local: Option<(&mut Composition, usize)>,
+    dist: Option<&WeightedIndex<f64>>,
+) -> u8 {
+    match local.and_then(|(x, pos)| x.at(pos)) {
+        Some(counts) => insert_base(rng, Some(&WeightedIndex::new(counts).unwrap())),
+        None => insert_base(rng, dist),
+    }
+}
+
+/// Weights of A/C/G/T of a GC content of `gc`.
+fn gc_weights(gc: f64) -> [f64; 4] {
+    let at = (1.0 - gc) / 2.0;
+    [at, gc / 2.0, gc / 2.0, at]
+}
+
+/// Counts of the concrete bases within a window sliding along a sequence.
+struct Composition<'a> {
+    seq: &'a [u8],
+    // bases on either side of the position
+    flank: usize,
+    // the window is seq[start..end]
+    start: usize,
+    end: usize,
+    counts: [f64; 4],
+}
+
+impl<'a> Composition<'a> {
+    fn new(seq: &'a [u8], window: usize) -> Composition<'a> {
+        Composition {
+            seq,
+            flank: window / 2,
+            start: 0,
+            end: 0,
+            counts: [0.0; 4],
+        }
+    }
+
+    fn count(&mut self, b: u8, n: f64) {
+        if let Some(x) = Base::from_u8(b) {
+            self.counts[x as usize] += n;
+        }
+    }
+
+    /// Counts of A/C/G/T within the window centered at `pos`, `None`
+    /// without any concrete base. Positions must not decrease, and may
+    /// skip ahead.
+    fn at(&mut self, pos: usize) -> Option<[f64; 4]> {
+        let end = (pos + self.flank + 1).min(self.seq.len());
+        while self.end < end {
+            self.count(self.seq[self.end], 1.0);
+            self.end += 1;
+        }
+        while self.start < pos.saturating_sub(self.flank) {
+            self.count(self.seq[self.start], -1.0);
+            self.start += 1;
+        }
+        self.counts.iter().any(|&x| x > 0.0).then_some(self.counts)
+    }
+}
+
+/// Concrete base other than `b`, drawn uniformly; any of the four when `b`
+/// is not one of them.
+///
+/// This is the replacement of every substitution: N and the other
+/// ambiguity codes are never drawn, so that substitutions resolve them and
+/// introduce none.
+pub fn saturate<R: Rng>(rng: &mut R, b: u8) -> u8 {
+    let x = match Base::from_u8(b) {
+        // the bases after `b` move up to take its place
+        Some(b) => {
+            let x = rng.gen_range(0..3);
+            x + usize::from(x >= b as usize)
+        }
+        None => rng.gen_range(0..4),
+    };
+    Base::ALL[x].to_u8()
+}
+
+/// Concrete base drawn uniformly among those the IUPAC code `b` stands
+/// for, in the case of `b`. Concrete bases and other symbols are returned
+/// as they are and draw nothing.
+pub fn resolve<R: Rng>(rng: &mut R, b: u8) -> u8 {
+    let bases = alphabet::iupac_bases(b);
+    if bases.len() < 2 {
+        return b;
+    }
+    let x = bases[rng.gen_range(0..bases.len())].to_u8();
+    if b.is_ascii_lowercase() {
+        x.to_ascii_lowercase()
+    } else {
+        x
+    }
+}
+
+/// `outcome` with a substituted base in the case of the base `b` it replaces.
+pub(crate) fn match_case(outcome: MutationOutcome, b: u8) -> MutationOutcome {
+    match outcome {
+        MutationOutcome::Substitute(x) if b.is_ascii_lowercase() => {
+            MutationOutcome::Substitute(x.to_ascii_lowercase())
+        }
+        _ => outcome,
+    }
+}
+
+/// Mutator of nucleotide sequences.
+///
+/// Substitution, insertion and deletion each draw from their own RNG
+/// stream, derived from the RNG the mutator is created with, and no stream
+/// is read by another operation. Varying one rate thus leaves the outcomes
+/// taking precedence over it unchanged, see [`decide`]: the insertion rate
+/// changes no substitution or deletion, the substitution rate no deletion.
+///
+/// Gaps of aligned sequences are kept as they are and excluded from the
+/// base counts the realized rates divide by.
+pub struct Mutator {
+    // substitution, insertion and deletion rates
+    rates: [f64; 3],
+    // all rates are zero, sequences are copied verbatim
+    passthrough: bool,
+    // every base is substituted, ignoring the rates
+    saturate: bool,
+    // distribution of A/C/G/T of inserted bases
+    insert: Option<WeightedIndex<f64>>,
+    // window of the local composition inserted bases are drawn with
+    insert_window: Option<usize>,
+    // substituted bases keep the case of the bases they replace
+    preserve_case: bool,
+    // ratio of the rates of nonsynonymous to synonymous substitutions
+    dnds: Option<f64>,
+    // deleted bases are removed or masked by N
+    deletion_mode: DeletionMode,
+    // gaps draw nothing from the streams
+    alignment_mode: bool,
+    // edits made since last taken, if recorded
+    edits: Option<Vec<Variant>>,
+    // length of the deletion running at the end of the last block, if in blocks
+    blocks: Option<usize>,
+    // substitution, insertion and deletion streams
+    rngs: [StdRng; 3],
+    stats: MutationStats,
+}
+
+impl Mutator {
+    /// Create a mutator whose streams derive from `rng`, any RNG so that a
+    /// seeded or mock one makes the outcomes exact. An error unless the
+    /// rates are within [0, 1] and sum to at most 1.
+    pub fn new(s: f64, i: f64, d: f64, mut rng: impl Rng) -> Result<Mutator> {
+        let [s, i, d, _] = weights(s, i, d)?;
+        let mut stream = || StdRng::from_rng(&mut rng).unwrap();
+
+        Ok(Mutator {
+            rates: [s, i, d],
+            passthrough: s == 0.0 && i == 0.0 && d == 0.0,
+            saturate: false,
+            insert: None,
+            insert_window: None,
+            preserve_case: false,
+            dnds: None,
+            deletion_mode: DeletionMode::Remove,
+            alignment_mode: false,
+            edits: None,
+            blocks: None,
+            rngs: [stream(), stream(), stream()],
+            stats: MutationStats::default(),
+        })
+    }
+
+    /// Edits made by the mutator so far.
+    pub fn stats(&self) -> &MutationStats {
+        &self.stats
+    }
+
+    /// Substitute every base by another, ignoring the rates.
+    pub fn set_saturate(&mut self, saturate: bool) {
+        self.saturate = saturate;
+    }
+
+    /// Draw inserted bases with a GC content of `gc` rather than uniformly,
+    /// an error unless `gc` is within [0, 1].
+    pub fn set_insert_gc(&mut self, gc: f64) -> Result<()> {
+        let gc = config::fraction("inserted GC content", gc)?;
+        self.insert = Some(WeightedIndex::new(gc_weights(gc)).unwrap());
+        Ok(())
+    }
+
+    /// Draw inserted bases with the composition of the `window` bases
+    /// around the insertion rather than uniformly or with a fixed GC
+    /// content, falling back to those where the window holds no concrete
+    /// base. Applies to sequences, not to single bases. An error for a
+    /// window of 0 bases.
+    pub fn set_insert_window(&mut self, window: usize) -> Result<()> {
+        if window == 0 {
+            return Err(SonicatError::Parameter(
+                "composition window of 0 bases".to_string(),
+            ));
+        }
+        self.insert_window = Some(window);
+        Ok(())
+    }
+
+    /// Substitute lowercase bases by lowercase bases.
+    pub fn set_preserve_case(&mut self, preserve_case: bool) {
+        self.preserve_case = preserve_case;
+    }
+
+    /// Bias the substitutions of coding bases towards a ratio of
+    /// nonsynonymous to synonymous rates of `ratio`, see
+    /// [`Mutator::mutate_seq_annotated`]. An error unless `ratio` is
+    /// positive.
+    pub fn set_dnds(&mut self, ratio: f64) -> Result<()> {
+        self.dnds = Some(config::positive("dN/dS ratio", ratio)?);
+        Ok(())
+    }
+
+    /// Remove deleted bases or mask them by N.
+    pub fn set_deletion_mode(&mut self, mode: DeletionMode) {
+        self.deletion_mode = mode;
+    }
+
+    /// Mutate aligned sequences base by base, gaps drawing nothing, see
+    /// [`Mutator::mutate_seq`].
+    pub fn set_alignment_mode(&mut self, alignment_mode: bool) {
+        self.alignment_mode = alignment_mode;
+    }
+
+    /// Record the edits made to mutated sequences, see [`Mutator::take_edits`].
+    pub fn set_record_edits(&mut self, record: bool) {
+        self.edits = record.then(Vec::new);
+    }
+
+    /// Edits made since last taken, as variants of single original bases
+    /// in order of position. Empty unless recorded.
+    pub fn take_edits(&mut self) -> Vec<Variant> {
+        self.edits.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Mutate the sequences to come as consecutive blocks of one, a
+    /// deletion running across the end of a block counted as a single
+    /// event, until [`Mutator::end_blocks`].
+    pub fn start_blocks(&mut self) {
+        self.blocks = Some(0);
+    }
+
+    /// End the blocks, counting the deletion running at the end of the
+    /// last one.
+    pub fn end_blocks(&mut self) {
+        if let Some(open) = self.blocks.take() {
+            close_deletion(&mut self.stats, open);
+        }
+    }
+
+    pub fn mutate(&mut self, b: u8) -> MutationOutcome {
+        self.mutate_with(b, self.rates[0], None)
+    }
+
+    /// Fate of base `b` with a substitution rate of `s`, inserting bases
+    /// with the composition around position `pos` of `local` if given.
+    fn mutate_with(
+        &mut self,
+        b: u8,
+        s: f64,
+        local: Option<(&mut Composition, usize)>,
+    ) -> MutationOutcome {
+        let outcome = if self.saturate {
+            MutationOutcome::Substitute(saturate(&mut self.rngs[0], b))
+        } else {
+            let [_, i, d] = self.rates;
+            let insert = &self.insert;
+            decide_with(&mut self.rngs, b, &[s, i, d], |rng| {
+                draw_insert(rng, local, insert.as_ref())
+            })
+        };
+        if self.preserve_case {
+            match_case(outcome, b)
+        } else {
+            outcome
+        }
+    }
+
+    /// Mutate a sequence, returning the mutated sequence. The map from
+    /// original to mutated coordinates is built from the recorded edits by
+    /// [`CoordinateMap::from_edits`](crate::coords::CoordinateMap::from_edits).
+    ///
+    /// Gaps still advance the streams, as any base, unless in alignment
+    /// mode, where the bases of a gapped sequence draw what they would
+    /// without the gaps.
+    pub fn mutate_seq(&mut self, seq: &[u8]) -> Vec<u8> {
+        if self.passthrough && !self.saturate {
+            self.stats.bases += residues(seq);
+            return seq.to_vec();
+        }
+        if self.insert_window.is_some() {
+            return self.mutate_seq_annotated(seq, None, None, None);
+        }
+
+        let mut stats = self.stats;
+        let mut edits = self.edits.take();
+        let mut blocks = self.blocks;
+        let mask = (self.deletion_mode == DeletionMode::Mask).then_some(b'N');
+        let mutated = apply(
+            seq,
+            &mut stats,
+            edits.as_mut(),
+            mask,
+            self.alignment_mode,
+            blocks.as_mut(),
+            |_, b| self.mutate(b),
+        );
+        self.stats = stats;
+        self.edits = edits;
+        self.blocks = blocks;
+        mutated
+    }
+
+    /// Mutate a sequence as [`Mutator::mutate_seq`], with the substitution
+    /// rate of each base scaled by the factor of `track` at its position.
+    pub fn mutate_seq_tracked(&mut self, seq: &[u8], track: &[f64]) -> Vec<u8> {
+        if self.insert_window.is_some() {
+            return self.mutate_seq_annotated(seq, Some(track), None, None);
+        }
+        let s = self.rates[0];
+        let mut stats = self.stats;
+        let mut edits = self.edits.take();
+        let mut blocks = self.blocks;
+        let mask = (self.deletion_mode == DeletionMode::Mask).then_some(b'N');
+        let mutated = apply(
+            seq,
+            &mut stats,
+            edits.as_mut(),
+            mask,
+            self.alignment_mode,
+            blocks.as_mut(),
+            |pos, b| self.mutate_with(b, (s * track[pos]).min(1.0), None),
+        );
+        self.stats = stats;
+        self.edits = edits;
+        self.blocks = blocks;
+        mutated
+    }
+
+    /// Mutate a sequence as [`Mutator::mutate_seq_tracked`], or as
+    /// [`Mutator::mutate_seq`] without a `track`, keeping the bases outside
+    /// `within` as they are and biasing the substitutions of the bases with
+    /// a codon in `sites` by the dN/dS ratio if set. Kept bases draw nothing
+    /// from the streams.
+    pub fn mutate_seq_annotated(
+        &mut self,
+        seq: &[u8],
+        track: Option<&[f64]>,
+        within: Option<&[bool]>,
+        sites: Option<&[Option<CodonSite>]>,
+    ) -> Vec<u8> {
+        let s = self.rates[0];
+        let mut stats = self.stats;
+        let mut edits = self.edits.take();
+        let mut blocks = self.blocks;
+        let mut composition = self.insert_window.map(|w| Composition::new(seq, w));
+        let mask = (self.deletion_mode == DeletionMode::Mask).then_some(b'N');
+        let mutated = apply(
+            seq,
+            &mut stats,
+            edits.as_mut(),
+            mask,
+            self.alignment_mode,
+            blocks.as_mut(),
+            |pos, b| {
+                if within.is_some_and(|x| !x[pos]) {
+                    return MutationOutcome::Keep(b);
+                }
+                let s = track.map_or(s, |x| (s * x[pos]).min(1.0));
+                let local = composition.as_mut().map(|x| (x, pos));
+                let outcome = self.mutate_with(b, s, local);
+                match (outcome, self.dnds, sites.and_then(|x| x[pos])) {
+                    (MutationOutcome::Substitute(x), Some(ratio), Some(site))
+                        if alphabet::normalize(x) != alphabet::normalize(b) =>
+                    {
+                        // rejected substitutions leave the base as it is
+                        let accept = match site.is_synonymous(x) {
+                            Some(true) => (1.0 / ratio).min(1.0),
+                            Some(false) => ratio.min(1.0),
+                            None => 1.0,
+                        };
+                        if self.rngs[0].gen_bool(accept) {
+                            outcome
+                        } else {
+                            MutationOutcome::Keep(b)
+                        }
+                    }
+                    _ => outcome,
+                }
+            },
+        );
+        self.stats = stats;
+        self.edits = edits;
+        self.blocks = blocks;
+        mutated
+    }
+
+    /// Mutate a sequence with exactly one edit at each of the positions
+    /// `at`, in increasing order, and none elsewhere. The edit is a
+    /// substitution, insertion or deletion with odds of the rates, and a
+    /// substitution always changes the base. An error if the rates are all
+    /// zero, as no edit can then be drawn, or unless the positions are
+    /// strictly increasing, within `seq` and off its gaps.
+    pub fn mutate_seq_at(&mut self, seq: &[u8], at: &[usize]) -> Result<Vec<u8>> {
+        let kind = WeightedIndex::new(self.rates).map_err(|_| {
+            SonicatError::Parameter("no edit to make with all mutation rates 0".to_string())
+        })?;
+        if let Some(x) = at.windows(2).find(|x| x[0] >= x[1]) {
+            return Err(SonicatError::Parameter(format!(
+                "edit positions not strictly increasing, {} before {}",
+                x[0], x[1]
+            )));
+        }
+        if let Some(&pos) = at
+            .iter()
+            .find(|&&x| seq.get(x).is_none_or(|&b| alphabet::is_gap(b)))
+        {
+            return Err(SonicatError::Parameter(format!(
+                "edit position {} is not a residue of the sequence of {}",
+                pos,
+                seq.len()
+            )));
+        }
+        let mut stats = self.stats;
+        let mut edits = self.edits.take();
+        let mut blocks = self.blocks;
+        let mut composition = self.insert_window.map(|w| Composition::new(seq, w));
+        let mask = (self.deletion_mode == DeletionMode::Mask).then_some(b'N');
+        let mut at = at.iter().peekable();
+        let mutated = apply(
+            seq,
+            &mut stats,
+            edits.as_mut(),
+            mask,
+            self.alignment_mode,
+            blocks.as_mut(),
+            |pos, b| {
+                if at.next_if_eq(&&pos).is_none() {
+                    return MutationOutcome::Keep(b);
+                }
+                let local = composition.as_mut().map(|x| (x, pos));
+                let outcome = match self.rngs[0].sample(&kind) {
+                    0 => MutationOutcome::Substitute(saturate(&mut self.rngs[0], b)),
+                    1 => MutationOutcome::Insert(
+                        b,
+                        draw_insert(&mut self.rngs[1], local, self.insert.as_ref()),
+                    ),
+                    _ => MutationOutcome::Delete,
+                };
+                if self.preserve_case {
+                    match_case(outcome, b)
+                } else {
+                    outcome
+                }
+            },
+        );
+        self.stats = stats;
+        self.edits = edits;
+        self.blocks = blocks;
+        Ok(mutated)
+    }
+}
+
+/// Number of symbols of `seq` other than gaps.
+pub(crate) fn residues(seq: &[u8]) -> u64 {
+    seq.iter().filter(|&&x| !alphabet::is_gap(x)).count() as u64
+}
+
+/// Build the mutated sequence from the fate `mutate` draws for each base,
+/// given its position and symbol, counting the edits in `stats` and
+/// recording them in `edits` if given. Deleted bases are replaced by `mask`
+/// if given, recorded as substitutions by it but counted as deletions.
+///
+/// Gaps are kept as they are and not counted as bases. Their fates are
+/// still drawn, so that the draws of the other bases do not depend on
+/// them, unless `skip_gaps`, when gaps draw nothing and a gapped sequence
+/// is mutated as it would be without its gaps.
+///
+/// With `open`, `seq` is a block of a longer sequence: the deletion event
+/// running at the end of the block before, of `open` bases, is continued,
+/// and the one running at the end of `seq` left to the next in `open`
+/// rather than counted.
+pub(crate) fn apply<F>(
+    seq: &[u8],
+    stats: &mut MutationStats,
+    mut edits: Option<&mut Vec<Variant>>,
+    mask: Option<u8>,
+    skip_gaps: bool,
+    open: Option<&mut usize>,
+    mut mutate: F,
+) -> Vec<u8>
+where
+    F: FnMut(usize, u8) -> MutationOutcome,
+{
+    let mut buf = Vec::with_capacity(seq.len() * 2);
+
+    stats.bases += residues(seq);
+    // length of the deletion event in progress
+    let mut deleted = open.as_deref().copied().unwrap_or(0);
+
+    for (i, r) in seq.iter().enumerate() {
+        let outcome = if !alphabet::is_gap(*r) {
+            mutate(i, *r)
+        } else {
+            if !skip_gaps {
+                mutate(i, *r);
+            }
+            MutationOutcome::Keep(*r)
+        };
+        if let Some(edits) = edits.as_deref_mut() {
+            let edit = match (outcome, mask) {
+                (MutationOutcome::Delete, Some(x)) => MutationOutcome::Substitute(x).edit(i, *r),
+                _ => outcome.edit(i, *r),
+            };
+            if let Some(edit) = edit {
+                edits.push(edit);
+            }
+        }
+        if outcome != MutationOutcome::Delete {
+            close_deletion(stats, deleted);
+            deleted = 0;
+        }
+        match outcome {
+            MutationOutcome::Keep(x) => buf.push(x),
+            MutationOutcome::Substitute(x) => {
+                if alphabet::normalize(x) != alphabet::normalize(*r) {
+                    stats.substitutions += 1;
+                }
+                buf.push(x);
+            }
+            MutationOutcome::Insert(x, y) => {
+                stats.insertions += 1;
+                stats.insertion_lengths[0] += 1;
+                buf.push(x);
+                buf.push(y);
+            }
+            MutationOutcome::Delete => {
+                stats.deletions += 1;
+                deleted += 1;
+                if let Some(x) = mask {
+                    buf.push(x);
+                }
+            }
+        }
+    }
+    match open {
+        Some(x) => *x = deleted,
+        None => close_deletion(stats, deleted),
+    }
+
+    buf
+}
+
+/// Count a deletion event of `deleted` bases, if any.
+pub(crate) fn close_deletion(stats: &mut MutationStats, deleted: usize) {
+    if deleted > 0 {
+        stats.deletion_lengths[deleted.min(INDEL_BINS) - 1] += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use rand::Rng;
+
+    fn streams(seed: u64) -> [StdRng; 3] {
+        [0, 1, 2].map(|x| StdRng::seed_from_u64(seed.wrapping_add(x)))
+    }
+
+    proptest! {
+        #[test]
+        fn full_deletion_always_deletes(
+            seed: u64,
+            b in prop::sample::select(b"ACGTNacgtn".to_vec()),
+            s in 0.0..=1.0f64,
+            i in 0.0..=1.0f64,
+        ) {
+            let outcome = decide(&mut streams(seed), b, &[s, i, 1.0], None);
+            prop_assert_eq!(outcome, MutationOutcome::Delete);
+        }
+
+        #[test]
+        fn zero_rates_always_keep(
+            seed: u64,
+            b in prop::sample::select(b"ACGTNacgtn".to_vec()),
+        ) {
+            let outcome = decide(&mut streams(seed), b, &[0.0; 3], None);
+            prop_assert_eq!(outcome, MutationOutcome::Keep(b));
+        }
+
+        #[test]
+        fn full_substitution_changes_the_base(
+            seed: u64,
+            b in prop::sample::select(b"ACGT".to_vec()),
+        ) {
+            match decide(&mut streams(seed), b, &[1.0, 0.0, 0.0], None) {
+                MutationOutcome::Substitute(x) => {
+                    prop_assert_ne!(x, b);
+                    prop_assert!(b"ACGT".contains(&x));
+                }
+                x => prop_assert!(false, "{:?}", x),
+            }
+        }
+
+        #[test]
+        fn full_insertion_keeps_the_base(
+            seed: u64,
+            b in prop::sample::select(b"ACGT".to_vec()),
+        ) {
+            match decide(&mut streams(seed), b, &[0.0, 1.0, 0.0], None) {
+                MutationOutcome::Insert(x, y) => {
+                    prop_assert_eq!(x, b);
+                    prop_assert!(b"ACGT".contains(&y));
+                }
+                x => prop_assert!(false, "{:?}", x),
+            }
+        }
+    }
+
+    #[test]
+    fn zero_rates_pass_sequences_through() {
+        let seq = b"ACGTNacgtnRY--ACGU";
+        let mut m = Mutator::new(0.0, 0.0, 0.0, StdRng::seed_from_u64(1)).unwrap();
+        m.set_record_edits(true);
+        assert_eq!(m.mutate_seq(seq), seq);
+        assert!(m.take_edits().is_empty());
+        assert_eq!(m.stats().bases, 16);
+    }
+
+    /// Sequence of `n` bases drawn uniformly.
+    fn random_seq(n: usize, seed: u64) -> Vec<u8> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..n).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect()
+    }
+
+    #[test]
+    fn realized_rates_match_requested() {
+        let requested = [0.1, 0.05, 0.02];
+        let [s, i, d] = requested;
+        let mut m = Mutator::new(s, i, d, StdRng::seed_from_u64(1)).unwrap();
+        m.mutate_seq(&random_seq(200_000, 2));
+        for (realized, requested) in m.stats().rates().iter().zip(requested) {
+            assert!(
+                (realized - requested).abs() < 0.01 * requested + 0.001,
+                "{} {}",
+                realized,
+                requested
+            );
+        }
+    }
+
+    #[test]
+    fn rates_summing_to_one_clamp_the_no_change_weight() {
+        // 1 - 0.3 - 0.2 - 0.5 rounds to -5.6e-17
+        assert_eq!(weights(0.3, 0.2, 0.5).unwrap(), [0.3, 0.2, 0.5, 0.0]);
+        WeightedIndex::new(weights(0.3, 0.2, 0.5).unwrap()).unwrap();
+        assert!(Mutator::new(0.3, 0.2, 0.5, StdRng::seed_from_u64(1)).is_ok());
+
+        assert!(matches!(
+            weights(0.3, 0.2, 0.6),
+            Err(SonicatError::Parameter(_))
+        ));
+        assert!(weights(-0.1, 0.0, 0.0).is_err());
+    }
+
+    #[test]
+    fn insertion_rate_leaves_substitutions_as_they_are() {
+        let seq = random_seq(20_000, 3);
+        let substitutions = |i| {
+            let mut m = Mutator::new(0.05, i, 0.0, StdRng::seed_from_u64(7)).unwrap();
+            m.set_record_edits(true);
+            m.mutate_seq(&seq);
+            let edits = m.take_edits();
+            let substitutions: Vec<Variant> = edits
+                .into_iter()
+                .filter(|x| x.reference.len() == x.alt.len())
+                .collect();
+            substitutions
+        };
+        let without = substitutions(0.0);
+        assert!(without.len() > 500);
+        for i in [0.01, 0.1, 0.5] {
+            assert_eq!(substitutions(i), without);
+        }
+    }
+
+    #[test]
+    fn saturation_changes_every_base() {
+        let seq = random_seq(10_000, 4);
+        let mut m = Mutator::new(0.0, 0.0, 0.0, StdRng::seed_from_u64(1)).unwrap();
+        m.set_saturate(true);
+        let mutated = m.mutate_seq(&seq);
+        assert_eq!(mutated.len(), seq.len());
+        assert!(seq.iter().zip(&mutated).all(|(a, b)| a != b));
+        assert_eq!(m.stats().substitutions, 10_000);
+
+        // ambiguity codes are resolved to concrete bases
+        let mutated = m.mutate_seq(b"NRY");
+        assert!(mutated.iter().all(|x| b"ACGT".contains(x)));
+    }
+
+    #[test]
+    fn inserted_bases_have_the_gc_content_set() {
+        for gc in [0.0, 0.2, 0.8] {
+            let mut m = Mutator::new(0.0, 1.0, 0.0, StdRng::seed_from_u64(1)).unwrap();
+            m.set_insert_gc(gc).unwrap();
+            let mutated = m.mutate_seq(&[b'A'; 20_000]);
+            assert_eq!(mutated.len(), 40_000);
+            let inserted: Vec<u8> = mutated.iter().skip(1).step_by(2).copied().collect();
+            let realized = inserted.iter().filter(|&&x| x == b'G' || x == b'C').count() as f64
+                / inserted.len() as f64;
+            assert!((realized - gc).abs() < 0.01, "{} {}", realized, gc);
+        }
+        let mut m = Mutator::new(0.0, 1.0, 0.0, StdRng::seed_from_u64(1)).unwrap();
+        assert!(m.set_insert_gc(1.5).is_err());
+    }
+
+    #[test]
+    fn preserve_case_keeps_lowercase_substitutions() {
+        let seq = b"acgtACGTacgtACGT".repeat(100);
+        let mut m = Mutator::new(0.0, 0.0, 0.0, StdRng::seed_from_u64(1)).unwrap();
+        m.set_saturate(true);
+        m.set_preserve_case(true);
+        let mutated = m.mutate_seq(&seq);
+        for (a, b) in seq.iter().zip(&mutated) {
+            assert_ne!(a.to_ascii_uppercase(), b.to_ascii_uppercase());
+            assert_eq!(a.is_ascii_lowercase(), b.is_ascii_lowercase());
+        }
+
+        // substitutions are uppercase otherwise
+        m.set_preserve_case(false);
+        assert!(m.mutate_seq(b"acgt").iter().all(u8::is_ascii_uppercase));
+    }
+
+    #[test]
+    fn seeded_rngs_give_exact_outcomes() {
+        let mutate = |seed| {
+            let mut m = Mutator::new(0.2, 0.1, 0.1, StdRng::seed_from_u64(seed)).unwrap();
+            m.mutate_seq(b"ACGTACGTACGTACGTACGT")
+        };
+        // pinned, so that a change to the draws shows up here
+        assert_eq!(mutate(42), b"AGCTACGGGAGCGCAAAGACAGG");
+        assert_eq!(mutate(42), mutate(42));
+        assert_ne!(mutate(42), mutate(43));
+    }
+
+    #[test]
+    fn rates_leave_the_outcomes_taking_precedence_unchanged() {
+        let seq = random_seq(5000, 1);
+        // positions substituted and deleted at the rates given
+        let outcomes = |rates: [f64; 3]| {
+            let mut rngs = streams(7);
+            let mut substituted = Vec::new();
+            let mut deleted = Vec::new();
+            for (i, &b) in seq.iter().enumerate() {
+                match decide(&mut rngs, b, &rates, None) {
+                    MutationOutcome::Substitute(x) => substituted.push((i, x)),
+                    MutationOutcome::Delete => deleted.push(i),
+                    _ => {}
+                }
+            }
+            (substituted, deleted)
+        };
+
+        // insertions at conditional rates short of 1, of 1 and of 0
+        let (substituted, deleted) = outcomes([0.3, 0.1, 0.2]);
+        assert!(!substituted.is_empty() && !deleted.is_empty());
+        for i in [0.0, 0.3, 0.5] {
+            assert_eq!(
+                outcomes([0.3, i, 0.2]),
+                (substituted.clone(), deleted.clone())
+            );
+        }
+        // substitutions at conditional rates of 0, 1 and between
+        for s in [0.0, 0.5, 0.8] {
+            assert_eq!(outcomes([s, 0.0, 0.2]).1, deleted);
+        }
+
+        // a stream draws for every base, even at a rate of 1
+        let mut rngs = streams(7);
+        decide(&mut rngs, b'A', &[0.0, 0.0, 1.0], None);
+        let mut expected = streams(7);
+        expected[2].gen::<f64>();
+        assert_eq!(rngs[2].gen::<u64>(), expected[2].gen::<u64>());
+    }
+
+    #[test]
+    fn deletion_runs_are_binned_by_length() {
+        let seq = [b'A'; 20];
+        let deleted = |i: usize| (1..4).contains(&i) || i == 5 || (7..19).contains(&i);
+        let mut stats = MutationStats::default();
+        let mutated = apply(&seq, &mut stats, None, None, false, None, |i, b| {
+            if deleted(i) {
+                MutationOutcome::Delete
+            } else if i == 4 {
+                MutationOutcome::Insert(b, b'C')
+            } else {
+                MutationOutcome::Keep(b)
+            }
+        });
+        assert_eq!(mutated, b"AACAA");
+        assert_eq!(stats.deletions, 16);
+        assert_eq!(stats.deletion_lengths, [1, 0, 1, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(stats.insertions, 1);
+        assert_eq!(stats.insertion_lengths, [1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn deletion_runs_continue_across_blocks() {
+        let seq = random_seq(10_000, 1);
+        let mutator = || Mutator::new(0.0, 0.0, 0.5, StdRng::seed_from_u64(1)).unwrap();
+        let mut whole = mutator();
+        let expected = whole.mutate_seq(&seq);
+
+        let mut blocks = mutator();
+        blocks.start_blocks();
+        let mutated: Vec<u8> = seq.chunks(7).flat_map(|x| blocks.mutate_seq(x)).collect();
+        blocks.end_blocks();
+        assert_eq!(mutated, expected);
+        assert_eq!(blocks.stats(), whole.stats());
+    }
+
+    #[test]
+    fn dnds_of_the_substitutions_follows_the_ratio() {
+        let seq = random_seq(300_000, 1);
+        let cds = [crate::gff::Cds {
+            start: 0,
+            end: seq.len(),
+            reverse: false,
+            phase: 0,
+        }];
+        let sites = crate::codon::codon_sites(&seq, &cds);
+
+        for ratio in [0.3, 2.0] {
+            let mut m = Mutator::new(0.05, 0.0, 0.0, StdRng::seed_from_u64(1)).unwrap();
+            m.set_dnds(ratio).unwrap();
+            let mutated = m.mutate_seq_annotated(&seq, None, None, Some(&sites));
+
+            // nonsynonymous and synonymous sites and changes, Nei-Gojobori
+            let (mut n, mut s, mut dn, mut ds) = (0.0, 0.0, 0.0, 0.0);
+            for (pos, site) in sites.iter().enumerate() {
+                let site = site.unwrap();
+                let others = b"ACGT".iter().filter(|&&x| x != seq[pos]);
+                let synonymous = others.filter(|&&x| site.is_synonymous(x).unwrap()).count();
+                s += synonymous as f64 / 3.0;
+                n += 1.0 - synonymous as f64 / 3.0;
+                if mutated[pos] != seq[pos] {
+                    match site.is_synonymous(mutated[pos]).unwrap() {
+                        true => ds += 1.0,
+                        false => dn += 1.0,
+                    }
+                }
+            }
+            let realized = (dn / n) / (ds / s);
+            assert!(
+                (realized / ratio - 1.0).abs() < 0.1,
+                "{} for {}",
+                realized,
+                ratio
+            );
+        }
+        assert!(Mutator::new(0.05, 0.0, 0.0, StdRng::seed_from_u64(1))
+            .unwrap()
+            .set_dnds(0.0)
+            .is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn substitution_resolves_ambiguous_bases(
+            seed: u64,
+            b in prop::sample::select(b"NnRYKMSWBDHV".to_vec()),
+        ) {
+            match decide(&mut streams(seed), b, &[1.0, 0.0, 0.0], None) {
+                MutationOutcome::Substitute(x) => prop_assert!(b"ACGTacgt".contains(&x), "{}", x),
+                x => prop_assert!(false, "{:?}", x),
+            }
+        }
+    }
+
+    #[test]
+    fn substituted_bases_are_drawn_evenly_and_never_n() {
+        let mut m = Mutator::new(1.0, 0.0, 0.0, StdRng::seed_from_u64(1)).unwrap();
+        for (b, others) in [(b'A', &b"CGT"[..]), (b'N', &b"ACGT"[..])] {
+            let seq = vec![b; 40_000];
+            let mutated = m.mutate_seq(&seq);
+            assert_eq!(mutated.len(), seq.len());
+            for x in b"ACGTN" {
+                let share = mutated.iter().filter(|&y| y == x).count() as f64 / seq.len() as f64;
+                let expected = if others.contains(x) {
+                    1.0 / others.len() as f64
+                } else {
+                    0.0
+                };
+                assert!(
+                    (share - expected).abs() < 0.015,
+                    "{} of {}: {}",
+                    *x as char,
+                    b as char,
+                    share
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn inserted_bases_track_the_local_composition() {
+        // an A-rich half, then a GC-rich one
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut draw = |bases: &[u8], weights: [u32; 4]| {
+            let dist = WeightedIndex::new(weights).unwrap();
+            (0..5000)
+                .map(|_| bases[rng.sample(&dist)])
+                .collect::<Vec<u8>>()
+        };
+        let mut seq = draw(b"ACGT", [70, 10, 10, 10]);
+        seq.extend(draw(b"ACGT", [5, 45, 45, 5]));
+
+        let mut m = Mutator::new(0.0, 0.05, 0.0, StdRng::seed_from_u64(1)).unwrap();
+        m.set_insert_window(100).unwrap();
+        m.set_record_edits(true);
+        m.mutate_seq(&seq);
+        let edits = m.take_edits();
+        // shares of A and of GC among the bases inserted in each half, away
+        // from where the windows straddle both
+        let share = |range: std::ops::Range<usize>, of: &[u8]| {
+            let inserted: Vec<u8> = edits
+                .iter()
+                .filter(|x| range.contains(&x.pos))
+                .flat_map(|x| x.alt[1..].to_vec())
+                .collect();
+            inserted.iter().filter(|x| of.contains(x)).count() as f64 / inserted.len() as f64
+        };
+        // 0.25 and 0.5 if drawn uniformly
+        assert!((share(0..4900, b"A") - 0.7).abs() < 0.1);
+        assert!((share(5100..10_000, b"GC") - 0.9).abs() < 0.1);
+        assert!(m.set_insert_window(0).is_err());
+    }
+
+    #[test]
+    fn deletion_modes_remove_or_mask_the_bases() {
+        let seq = random_seq(5000, 1);
+        for (mode, same_length) in [(DeletionMode::Remove, false), (DeletionMode::Mask, true)] {
+            let mut m = Mutator::new(0.0, 0.0, 0.1, StdRng::seed_from_u64(1)).unwrap();
+            m.set_deletion_mode(mode);
+            let mutated = m.mutate_seq(&seq);
+            let deletions = m.stats().deletions as usize;
+            assert!(deletions > 0);
+            if same_length {
+                assert_eq!(mutated.len(), seq.len());
+                for (a, b) in seq.iter().zip(&mutated) {
+                    assert!(b == a || *b == b'N');
+                }
+                assert_eq!(mutated.iter().filter(|&&x| x == b'N').count(), deletions);
+            } else {
+                assert_eq!(mutated.len(), seq.len() - deletions);
+                assert!(!mutated.contains(&b'N'));
+            }
+        }
+        assert_eq!("mask".parse::<DeletionMode>().unwrap(), DeletionMode::Mask);
+        assert!("shift".parse::<DeletionMode>().is_err());
+    }
+
+    #[test]
+    fn alignment_mode_mutates_the_residues_as_if_ungapped() {
+        let ungapped = random_seq(2000, 1);
+        // a gap every fifth column
+        let gapped: Vec<u8> = ungapped
+            .chunks(4)
+            .flat_map(|x| [x, b"-"].concat())
+            .collect();
+        let mutator = || {
+            let mut m = Mutator::new(0.1, 0.0, 0.0, StdRng::seed_from_u64(1)).unwrap();
+            m.set_alignment_mode(true);
+            m
+        };
+
+        let mut m = mutator();
+        let mutated = m.mutate_seq(&gapped);
+        assert_eq!(mutated.len(), gapped.len());
+        for (a, b) in gapped.iter().zip(&mutated) {
+            assert_eq!(*a == b'-', *b == b'-');
+        }
+        // gaps draw nothing and count toward no rate
+        assert_eq!(m.stats().bases, 2000);
+        let residues: Vec<u8> = mutated.iter().copied().filter(|&x| x != b'-').collect();
+        assert_eq!(residues, mutator().mutate_seq(&ungapped));
+    }
+
+    #[test]
+    fn edits_at_positions_need_a_positive_rate() {
+        let seq = random_seq(100, 1);
+        let mut m = Mutator::new(0.0, 0.0, 0.0, StdRng::seed_from_u64(1)).unwrap();
+        assert!(matches!(
+            m.mutate_seq_at(&seq, &[10, 20]),
+            Err(SonicatError::Parameter(_))
+        ));
+
+        let mut m = Mutator::new(0.1, 0.0, 0.0, StdRng::seed_from_u64(1)).unwrap();
+        let mutated = m.mutate_seq_at(&seq, &[10, 20]).unwrap();
+        let changed: Vec<usize> = (0..seq.len()).filter(|&i| seq[i] != mutated[i]).collect();
+        assert_eq!(changed, [10, 20]);
+    }
+
+    #[test]
+    fn edits_at_positions_make_one_edit_each() {
+        let seq = random_seq(1000, 1);
+        let at: Vec<usize> = (0..1000).step_by(7).collect();
+        let mut m = Mutator::new(0.4, 0.3, 0.3, StdRng::seed_from_u64(1)).unwrap();
+        m.set_record_edits(true);
+        m.mutate_seq_at(&seq, &at).unwrap();
+        let edits = m.take_edits();
+        assert_eq!(edits.len(), at.len());
+        let stats = m.stats();
+        assert_eq!(
+            stats.substitutions + stats.insertions + stats.deletions,
+            at.len() as u64
+        );
+
+        // positions out of order, past the end or on a gap are rejected
+        let mut gapped = seq.clone();
+        gapped[5] = b'-';
+        for (seq, at) in [
+            (&seq, &[20, 10][..]),
+            (&seq, &[10, 10]),
+            (&seq, &[10, 1000]),
+            (&gapped, &[5]),
+        ] {
+            let mut m = Mutator::new(0.1, 0.0, 0.0, StdRng::seed_from_u64(1)).unwrap();
+            m.set_alignment_mode(true);
+            assert!(matches!(
+                m.mutate_seq_at(seq, at),
+                Err(SonicatError::Parameter(_))
+            ));
+        }
+    }
+}
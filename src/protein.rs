@@ -0,0 +1,243 @@
+//! Mutation of protein sequences.
+
+use crate::error::Result;
+use crate::mutator::{self, DeletionMode, MutationOutcome, MutationStats};
+use crate::vcf::Variant;
+use bio::scores::blosum62;
+use rand::{
+    distributions::{Uniform, WeightedIndex},
+    rngs::StdRng,
+    Rng, SeedableRng,
+};
+
+/// The 20 standard amino acids.
+pub const AMINO_ACIDS: &[u8; 20] = b"ARNDCQEGHILKMFPSTWYV";
+
+// BLOSUM62 scores are log-odds in half-bit units
+const LAMBDA: f64 = std::f64::consts::LN_2 / 2.0;
+
+/// Mutator of protein sequences.
+///
+/// A substituted amino acid is replaced by another with probability
+/// proportional to `exp(lambda * BLOSUM62(a, b))`, so that conservative
+/// replacements are favored. Symbols other than the 20 standard amino
+/// acids are never substituted.
+pub struct ProteinMutator {
+    // distribution of substitution, insertion, deletion and no change
+    fates: WeightedIndex<f64>,
+    // replacement distribution of each amino acid, indexed as AMINO_ACIDS
+    substitutions: Vec<WeightedIndex<f64>>,
+    // distribution of inserted amino acids, indexed as AMINO_ACIDS
+    insertions: Uniform<usize>,
+    rng: StdRng,
+    // every amino acid is substituted, ignoring the rates
+    saturate: bool,
+    // substituted amino acids keep the case of the ones they replace
+    preserve_case: bool,
+    // deleted amino acids are removed or masked by X
+    deletion_mode: DeletionMode,
+    // gaps draw nothing from the stream
+    alignment_mode: bool,
+    // edits made since last taken, if recorded
+    edits: Option<Vec<Variant>>,
+    // length of the deletion running at the end of the last block, if in blocks
+    blocks: Option<usize>,
+    stats: MutationStats,
+}
+
+impl ProteinMutator {
+    /// Create a mutator whose stream derives from `rng`, as
+    /// [`Mutator::new`](crate::mutator::Mutator::new) does, with the same
+    /// errors.
+    pub fn new(s: f64, i: f64, d: f64, rng: impl Rng) -> Result<ProteinMutator> {
+        let fates = WeightedIndex::new(mutator::weights(s, i, d)?).unwrap();
+        let substitutions = AMINO_ACIDS
+            .iter()
+            .map(|&a| {
+                let w = AMINO_ACIDS.iter().map(|&b| {
+                    if a == b {
+                        0.0
+                    } else {
+                        (LAMBDA * f64::from(blosum62(a, b))).exp()
+                    }
+                });
+                WeightedIndex::new(w).unwrap()
+            })
+            .collect();
+
+        Ok(ProteinMutator {
+            fates,
+            substitutions,
+            insertions: Uniform::from(0..AMINO_ACIDS.len()),
+            rng: StdRng::from_rng(rng).unwrap(),
+            saturate: false,
+            preserve_case: false,
+            deletion_mode: DeletionMode::Remove,
+            alignment_mode: false,
+            edits: None,
+            blocks: None,
+            stats: MutationStats::default(),
+        })
+    }
+
+    /// Edits made by the mutator so far.
+    pub fn stats(&self) -> &MutationStats {
+        &self.stats
+    }
+
+    /// Substitute every standard amino acid by another, ignoring the rates.
+    pub fn set_saturate(&mut self, saturate: bool) {
+        self.saturate = saturate;
+    }
+
+    /// Substitute lowercase amino acids by lowercase amino acids.
+    pub fn set_preserve_case(&mut self, preserve_case: bool) {
+        self.preserve_case = preserve_case;
+    }
+
+    /// Remove deleted amino acids or mask them by X.
+    pub fn set_deletion_mode(&mut self, mode: DeletionMode) {
+        self.deletion_mode = mode;
+    }
+
+    /// Mutate aligned sequences residue by residue, gaps drawing nothing.
+    pub fn set_alignment_mode(&mut self, alignment_mode: bool) {
+        self.alignment_mode = alignment_mode;
+    }
+
+    /// Record the edits made to mutated sequences, see
+    /// [`ProteinMutator::take_edits`].
+    pub fn set_record_edits(&mut self, record: bool) {
+        self.edits = record.then(Vec::new);
+    }
+
+    /// Edits made since last taken, as variants of single original amino
+    /// acids in order of position. Empty unless recorded.
+    pub fn take_edits(&mut self) -> Vec<Variant> {
+        self.edits.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Mutate the sequences to come as consecutive blocks of one, as
+    /// [`Mutator::start_blocks`](crate::mutator::Mutator::start_blocks).
+    pub fn start_blocks(&mut self) {
+        self.blocks = Some(0);
+    }
+
+    /// End the blocks, as
+    /// [`Mutator::end_blocks`](crate::mutator::Mutator::end_blocks).
+    pub fn end_blocks(&mut self) {
+        if let Some(open) = self.blocks.take() {
+            mutator::close_deletion(&mut self.stats, open);
+        }
+    }
+
+    /// Draw the fate of the amino acid `b`: substituted, followed by an
+    /// inserted amino acid, deleted or kept. Symbols other than the 20
+    /// standard amino acids are kept rather than substituted.
+    pub fn mutate(&mut self, b: u8) -> MutationOutcome {
+        let outcome = self.draw(b);
+        if self.preserve_case {
+            mutator::match_case(outcome, b)
+        } else {
+            outcome
+        }
+    }
+
+    fn draw(&mut self, b: u8) -> MutationOutcome {
+        let fate = if self.saturate {
+            0
+        } else {
+            self.rng.sample(&self.fates)
+        };
+        let upper = b.to_ascii_uppercase();
+        match fate {
+            0 => match AMINO_ACIDS.iter().position(|&a| a == upper) {
+                Some(a) => MutationOutcome::Substitute(
+                    AMINO_ACIDS[self.rng.sample(&self.substitutions[a])],
+                ),
+                None => MutationOutcome::Keep(b),
+            },
+            1 => MutationOutcome::Insert(b, AMINO_ACIDS[self.rng.sample(self.insertions)]),
+            2 => MutationOutcome::Delete,
+            _ => MutationOutcome::Keep(b),
+        }
+    }
+
+    /// Mutate a sequence, returning the mutated sequence.
+    pub fn mutate_seq(&mut self, seq: &[u8]) -> Vec<u8> {
+        let mut stats = self.stats;
+        let mut edits = self.edits.take();
+        let mut blocks = self.blocks;
+        let mask = (self.deletion_mode == DeletionMode::Mask).then_some(b'X');
+        let mutated = mutator::apply(
+            seq,
+            &mut stats,
+            edits.as_mut(),
+            mask,
+            self.alignment_mode,
+            blocks.as_mut(),
+            |_, b| self.mutate(b),
+        );
+        self.stats = stats;
+        self.edits = edits;
+        self.blocks = blocks;
+        mutated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitutions_follow_blosum62() {
+        let mut m = ProteinMutator::new(0.0, 0.0, 0.0, StdRng::seed_from_u64(1)).unwrap();
+        m.set_saturate(true);
+        let n = 200_000;
+        let mut counts = [0usize; 20];
+        for _ in 0..n {
+            match m.mutate(b'W') {
+                MutationOutcome::Substitute(x) => {
+                    counts[AMINO_ACIDS.iter().position(|&a| a == x).unwrap()] += 1
+                }
+                x => panic!("{:?}", x),
+            }
+        }
+
+        let weights: Vec<f64> = AMINO_ACIDS
+            .iter()
+            .map(|&b| {
+                if b == b'W' {
+                    0.0
+                } else {
+                    (LAMBDA * f64::from(blosum62(b'W', b))).exp()
+                }
+            })
+            .collect();
+        let total: f64 = weights.iter().sum();
+        for (count, weight) in counts.iter().zip(&weights) {
+            let expected = weight / total;
+            let observed = *count as f64 / n as f64;
+            assert!(
+                (observed - expected).abs() < 0.005,
+                "{} {}",
+                observed,
+                expected
+            );
+        }
+        // F and Y score highest against W, G among the lowest
+        let count = |a: u8| counts[AMINO_ACIDS.iter().position(|&x| x == a).unwrap()];
+        assert!(count(b'Y') > count(b'G') && count(b'F') > count(b'G'));
+    }
+
+    #[test]
+    fn non_standard_symbols_are_kept() {
+        let mut m = ProteinMutator::new(0.0, 0.0, 0.0, StdRng::seed_from_u64(1)).unwrap();
+        m.set_saturate(true);
+        assert_eq!(m.mutate_seq(b"X*-"), b"X*-");
+        let mut m = ProteinMutator::new(0.0, 1.0, 0.0, StdRng::seed_from_u64(1)).unwrap();
+        let mutated = m.mutate_seq(b"MKV");
+        assert_eq!(mutated.len(), 6);
+        assert!(mutated.iter().all(|x| AMINO_ACIDS.contains(x)));
+    }
+}
@@ -0,0 +1,73 @@
+//! Record of the invocation that produced an output.
+
+use crate::file::{create_output, Compression};
+use serde_json::{json, Value};
+use std::env;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Version of the tools.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Argument quoted for a POSIX shell, unless it needs no quoting.
+fn quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// The command line of the running process, quoted to be pasted into a
+/// shell.
+pub fn command_line() -> String {
+    env::args_os()
+        .map(|x| quote(&x.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Write the program, its version and the command line to `path`, one
+/// comment line and one command line.
+pub fn write_command(path: &Path, program: &str, overwrite: bool) -> io::Result<()> {
+    let mut fout = create_output(Some(path), Compression::None, overwrite)?;
+    writeln!(fout, "# {} {}", program, VERSION)?;
+    writeln!(fout, "{}", command_line())?;
+    fout.finish()
+}
+
+/// Write the object `report` to `path` as JSON, along with the program, its
+/// version and the command line.
+pub fn write_report(path: &Path, program: &str, report: Value, overwrite: bool) -> io::Result<()> {
+    let mut object = json!({
+        "program": program,
+        "version": VERSION,
+        "command": command_line(),
+    });
+    if let (Some(object), Value::Object(report)) = (object.as_object_mut(), report) {
+        object.extend(report);
+    }
+    let mut fout = create_output(Some(path), Compression::from_path(Some(path)), overwrite)?;
+    serde_json::to_writer_pretty(&mut fout, &object)?;
+    writeln!(fout)?;
+    fout.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arguments_are_quoted_for_a_shell() {
+        assert_eq!(quote("--seed=1"), "--seed=1");
+        assert_eq!(quote("reads/out.fa"), "reads/out.fa");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("{contig}"), "'{contig}'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote(""), "''");
+    }
+}
@@ -0,0 +1,184 @@
+//! Per-base quality scores of simulated reads.
+
+use crate::config;
+use crate::error::Result;
+use crate::error::SonicatError;
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+use std::str::FromStr;
+
+/// Highest Phred score written, `~` in Phred+33.
+pub const MAX_PHRED: u8 = 93;
+
+/// Kind of distribution of the base qualities, see [`QualityModel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityDist {
+    Fixed,
+    Uniform,
+    Normal,
+}
+
+impl FromStr for QualityDist {
+    type Err = SonicatError;
+
+    fn from_str(s: &str) -> Result<QualityDist> {
+        match s {
+            "fixed" => Ok(QualityDist::Fixed),
+            "uniform" => Ok(QualityDist::Uniform),
+            "normal" => Ok(QualityDist::Normal),
+            _ => Err(SonicatError::Parameter(format!(
+                "unknown quality distribution {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Distribution of the Phred quality of a base, independent of its position.
+pub enum QualityModel {
+    Fixed(u8),
+    /// Uniform over `mean ± spread`.
+    Uniform {
+        mean: f64,
+        spread: f64,
+    },
+    Normal(Normal<f64>),
+}
+
+impl QualityModel {
+    /// Distribution `dist` of mean `quality` and standard deviation `sd`,
+    /// the latter unused by fixed qualities.
+    pub fn new(dist: QualityDist, quality: u8, sd: f64) -> Result<QualityModel> {
+        match dist {
+            QualityDist::Fixed => Ok(QualityModel::fixed(quality)),
+            QualityDist::Uniform => QualityModel::uniform(f64::from(quality), sd),
+            QualityDist::Normal => QualityModel::normal(f64::from(quality), sd),
+        }
+    }
+
+    pub fn fixed(quality: u8) -> QualityModel {
+        QualityModel::Fixed(quality)
+    }
+
+    /// Uniform distribution of mean `mean` and standard deviation `sd`, an
+    /// error for a negative `sd`.
+    pub fn uniform(mean: f64, sd: f64) -> Result<QualityModel> {
+        let sd = config::non_negative("quality standard deviation", sd)?;
+        Ok(QualityModel::Uniform {
+            mean,
+            spread: sd * 3f64.sqrt(),
+        })
+    }
+
+    /// Normal distribution of mean `mean` and standard deviation `sd`, an
+    /// error for a negative `sd`.
+    pub fn normal(mean: f64, sd: f64) -> Result<QualityModel> {
+        let sd = config::non_negative("quality standard deviation", sd)?;
+        Ok(QualityModel::Normal(Normal::new(mean, sd).unwrap()))
+    }
+
+    /// Qualities of a read of `length` bases, rounded and clamped to
+    /// `0..=MAX_PHRED`. Fixed qualities draw nothing from `rng`.
+    pub fn sample<R: Rng>(&self, rng: &mut R, length: usize) -> Vec<u8> {
+        let draw = |x: f64| x.round().clamp(0.0, f64::from(MAX_PHRED)) as u8;
+        match self {
+            QualityModel::Fixed(q) => vec![(*q).min(MAX_PHRED); length],
+            QualityModel::Uniform { mean, spread } => (0..length)
+                .map(|_| {
+                    if *spread > 0.0 {
+                        draw(rng.gen_range(mean - spread..=mean + spread))
+                    } else {
+                        draw(*mean)
+                    }
+                })
+                .collect(),
+            QualityModel::Normal(normal) => (0..length).map(|_| draw(normal.sample(rng))).collect(),
+        }
+    }
+
+    /// Qualities of a read of `length` bases as [`QualityModel::sample`],
+    /// clamped to `min..=max`.
+    pub fn sample_within<R: Rng>(&self, rng: &mut R, length: usize, min: u8, max: u8) -> Vec<u8> {
+        let mut qual = self.sample(rng, length);
+        for q in qual.iter_mut() {
+            *q = (*q).clamp(min, max);
+        }
+        qual
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn moments(qual: &[u8]) -> (f64, f64) {
+        let n = qual.len() as f64;
+        let mean = qual.iter().map(|&q| f64::from(q)).sum::<f64>() / n;
+        let var = qual
+            .iter()
+            .map(|&q| (f64::from(q) - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        (mean, var.sqrt())
+    }
+
+    #[test]
+    fn fixed_qualities_are_constant() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let model = QualityModel::new(QualityDist::Fixed, 30, 5.0).unwrap();
+        assert_eq!(model.sample(&mut rng, 10), vec![30; 10]);
+        assert_eq!(
+            QualityModel::fixed(120).sample(&mut rng, 2),
+            vec![MAX_PHRED; 2]
+        );
+    }
+
+    #[test]
+    fn uniform_qualities_are_flat_within_the_spread() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let model = QualityModel::new(QualityDist::Uniform, 30, 4.0).unwrap();
+        let qual = model.sample(&mut rng, 100_000);
+        let (mean, sd) = moments(&qual);
+        assert!((mean - 30.0).abs() < 0.1, "mean {}", mean);
+        assert!((sd - 4.0).abs() < 0.2, "sd {}", sd);
+        // 4√3 ≈ 6.93 either side of the mean.
+        assert!(qual.iter().all(|&q| (23..=37).contains(&q)));
+        let inner = qual.iter().filter(|&&q| (27..=33).contains(&q)).count();
+        let outer = qual.len() - inner;
+        assert!((inner as f64 / outer as f64 - 1.0).abs() < 0.15);
+    }
+
+    #[test]
+    fn normal_qualities_concentrate_around_the_mean() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let model = QualityModel::new(QualityDist::Normal, 30, 4.0).unwrap();
+        let qual = model.sample(&mut rng, 100_000);
+        let (mean, sd) = moments(&qual);
+        assert!((mean - 30.0).abs() < 0.1, "mean {}", mean);
+        assert!((sd - 4.0).abs() < 0.2, "sd {}", sd);
+        let within = qual.iter().filter(|&&q| (26..=34).contains(&q)).count();
+        assert!(within as f64 / qual.len() as f64 > 0.7);
+        assert!(qual.iter().any(|&q| !(19..=41).contains(&q)));
+    }
+
+    #[test]
+    fn qualities_are_clamped() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let model = QualityModel::normal(2.0, 10.0).unwrap();
+        assert!(model.sample(&mut rng, 1000).iter().all(|&q| q <= MAX_PHRED));
+        let within = model.sample_within(&mut rng, 1000, 5, 10);
+        assert!(within.iter().all(|&q| (5..=10).contains(&q)));
+    }
+
+    #[test]
+    fn parameters_are_checked() {
+        assert!("gamma".parse::<QualityDist>().is_err());
+        assert_eq!(
+            "normal".parse::<QualityDist>().unwrap(),
+            QualityDist::Normal
+        );
+        assert!(QualityModel::uniform(30.0, -1.0).is_err());
+        assert!(QualityModel::normal(30.0, -1.0).is_err());
+    }
+}
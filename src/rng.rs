@@ -0,0 +1,45 @@
+use rand::{rngs::StdRng, SeedableRng};
+
+// FNV-1a, stable across platforms and releases unlike `DefaultHasher`
+fn fnv1a<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in bytes {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/// Derive the RNG of a record from the master seed and the record id.
+///
+/// The output of a record thus does not depend on the records processed
+/// before it, and a single record can be reproduced on its own.
+pub fn record_rng(seed: u64, id: &str) -> StdRng {
+    StdRng::seed_from_u64(fnv1a(seed.to_le_bytes().iter().chain(id.as_bytes())))
+}
+
+/// Derive the master seed of replicate `replicate` of a simulation, so that
+/// a series of independent datasets follows from a single seed.
+pub fn replicate_seed(seed: u64, replicate: u64) -> u64 {
+    fnv1a(seed.to_le_bytes().iter().chain(&replicate.to_le_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn record_rng_depends_on_the_seed_and_id_only() {
+        let draw = |seed, id| record_rng(seed, id).gen::<u64>();
+        assert_eq!(draw(1, "chr7"), draw(1, "chr7"));
+        assert_ne!(draw(1, "chr7"), draw(1, "chr8"));
+        assert_ne!(draw(1, "chr7"), draw(2, "chr7"));
+    }
+
+    #[test]
+    fn replicates_have_distinct_seeds() {
+        assert_eq!(replicate_seed(1, 0), replicate_seed(1, 0));
+        assert_ne!(replicate_seed(1, 0), replicate_seed(1, 1));
+    }
+}
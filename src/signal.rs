@@ -0,0 +1,30 @@
+//! Interruption by SIGINT, so that a run stops between records or reads
+//! with its output flushed rather than cut off mid-record.
+
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Exit status of a run stopped by SIGINT, 128 + SIGINT.
+pub const INTERRUPTED_STATUS: i32 = 130;
+
+/// Exit status of a run whose reader closed the output pipe early, 128 +
+/// SIGPIPE as if killed by the signal Rust ignores.
+pub const BROKEN_PIPE_STATUS: i32 = 141;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Handle SIGINT by raising the flag [`interrupted`] reads; a second SIGINT
+/// exits at once.
+pub fn install() {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            process::exit(INTERRUPTED_STATUS);
+        }
+    })
+    .unwrap();
+}
+
+/// Whether SIGINT was received.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
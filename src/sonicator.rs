@@ -0,0 +1,464 @@
+//! Sonication of a sequence into reads.
+
+use crate::coverage::Coverage;
+use crate::error::SonicatError;
+use crate::quality::MAX_PHRED;
+use bio::io::{fasta, fastq};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    Rng,
+};
+use std::borrow::Cow;
+use std::str::FromStr;
+
+/// A simulated read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Read {
+    pub id: String,
+    pub desc: Option<String>,
+    pub seq: Vec<u8>,
+    /// Phred quality scores, not ASCII encoded.
+    pub qual: Option<Vec<u8>>,
+    /// Source coordinates, 0-based and half open. The end of a read lies
+    /// past the sequence when the read wraps around its origin.
+    pub start: usize,
+    pub end: usize,
+}
+
+/// ASCII offset of FASTQ qualities.
+pub const PHRED_OFFSET: u8 = 33;
+
+impl From<Read> for fasta::Record {
+    fn from(read: Read) -> fasta::Record {
+        fasta::Record::with_attrs(&read.id, read.desc.as_deref(), &read.seq)
+    }
+}
+
+/// Reads without qualities are written with a quality of 0 at every base,
+/// qualities above `MAX_PHRED` with `MAX_PHRED`.
+impl From<Read> for fastq::Record {
+    fn from(read: Read) -> fastq::Record {
+        let qual: Vec<u8> = match read.qual {
+            Some(qual) => qual
+                .iter()
+                .map(|q| q.min(&MAX_PHRED) + PHRED_OFFSET)
+                .collect(),
+            None => vec![PHRED_OFFSET; read.seq.len()],
+        };
+        fastq::Record::with_attrs(&read.id, read.desc.as_deref(), &read.seq, &qual)
+    }
+}
+
+/// A read of a whole record, without qualities.
+impl From<&fasta::Record> for Read {
+    fn from(record: &fasta::Record) -> Read {
+        Read {
+            id: record.id().to_string(),
+            desc: record.desc().map(String::from),
+            seq: record.seq().to_vec(),
+            qual: None,
+            start: 0,
+            end: record.seq().len(),
+        }
+    }
+}
+
+/// A read of a whole record.
+impl From<&fastq::Record> for Read {
+    fn from(record: &fastq::Record) -> Read {
+        Read {
+            id: record.id().to_string(),
+            desc: record.desc().map(String::from),
+            seq: record.seq().to_vec(),
+            qual: Some(
+                record
+                    .qual()
+                    .iter()
+                    .map(|q| q.saturating_sub(PHRED_OFFSET))
+                    .collect(),
+            ),
+            start: 0,
+            end: record.seq().len(),
+        }
+    }
+}
+
+/// Number of the read after read `count`, an error rather than a wrap
+/// past `u64::MAX` so that read names stay unique.
+pub fn next_read(count: u64) -> Result<u64, SonicatError> {
+    count
+        .checked_add(1)
+        .ok_or_else(|| SonicatError::Parameter("read count overflowed u64".to_string()))
+}
+
+/// Policy for the windows that would run past the end of a sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tail {
+    /// No window starts within `length - 1` bases of the end.
+    Drop,
+    /// Windows at the end are cut short.
+    Shrink,
+    /// Windows at the end wrap around the origin, as in a circular sequence.
+    Wrap,
+}
+
+impl FromStr for Tail {
+    type Err = SonicatError;
+
+    fn from_str(s: &str) -> Result<Tail, SonicatError> {
+        match s {
+            "drop" => Ok(Tail::Drop),
+            "shrink" => Ok(Tail::Shrink),
+            "wrap" => Ok(Tail::Wrap),
+            _ => Err(SonicatError::Parameter(format!(
+                "unknown tail policy {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Window of `length` bases from `start`. Past the end, the window is cut
+/// short under [`Tail::Shrink`] and wraps around the origin under
+/// [`Tail::Wrap`], as many times as needed when `length` exceeds the
+/// sequence.
+pub fn window(seq: &[u8], start: usize, length: usize, tail: Tail) -> Cow<'_, [u8]> {
+    if start + length <= seq.len() {
+        Cow::Borrowed(&seq[start..start + length])
+    } else if tail == Tail::Shrink {
+        Cow::Borrowed(&seq[start..])
+    } else {
+        debug_assert_eq!(tail, Tail::Wrap);
+        Cow::Owned(
+            seq.iter()
+                .cycle()
+                .skip(start)
+                .take(length)
+                .copied()
+                .collect(),
+        )
+    }
+}
+
+/// Iterator over the reads sonicated from a sequence.
+///
+/// Every `step`-th window of `length` bases, every window by default,
+/// starts a number of reads drawn from the coverage distribution, and reads
+/// are yielded in order of their starts. The mean depth per base thus
+/// falls with the step, as `depth * length / step`.
+/// Reads are yielded without qualities, for the caller to score, and are
+/// named `seq_1`, `seq_2` and so on after the prefix set.
+pub struct Sonicator<'a> {
+    seq: &'a [u8],
+    length: usize,
+    tail: Tail,
+    step: usize,
+    coverage: &'a Coverage,
+    // probability of keeping each read drawn at a start
+    weights: Option<&'a [f64]>,
+    rng: StdRng,
+    // prefix of read names, and the number of reads yielded
+    prefix: String,
+    count: u64,
+    // next start to draw the reads of, and the start and reads left of the current one
+    next: usize,
+    start: usize,
+    remaining: u64,
+}
+
+impl<'a> Sonicator<'a> {
+    pub fn new(seq: &'a [u8], length: usize, coverage: &'a Coverage, rng: StdRng) -> Sonicator<'a> {
+        assert!(length > 0, "read length must be positive");
+        Sonicator {
+            seq,
+            length,
+            tail: Tail::Drop,
+            step: 1,
+            coverage,
+            weights: None,
+            rng,
+            prefix: String::new(),
+            count: 0,
+            next: 0,
+            start: 0,
+            remaining: 0,
+        }
+    }
+
+    /// Set the policy for the windows that would run past the end; a window
+    /// starts at every base unless they are dropped.
+    pub fn set_tail(&mut self, tail: Tail) {
+        self.tail = tail;
+    }
+
+    /// Draw reads from every `step`-th window only.
+    pub fn set_step(&mut self, step: usize) {
+        assert!(step > 0, "step must be positive");
+        self.step = step;
+    }
+
+    /// Prefix the names of reads with `prefix`, as to keep them unique
+    /// across sequences.
+    pub fn set_prefix(&mut self, prefix: &str) {
+        self.prefix = prefix.to_string();
+    }
+
+    /// Keep each read drawn at a start with the probability `weights` has
+    /// at the start, one per base of the sequence and each in [0, 1]. The
+    /// mean depth is scaled by the mean weight.
+    pub fn set_weights(&mut self, weights: &'a [f64]) {
+        assert_eq!(weights.len(), self.seq.len(), "one weight per base");
+        self.weights = Some(weights);
+    }
+
+    /// Number of windows reads start from, whatever the step.
+    pub fn starts(&self) -> usize {
+        if self.tail != Tail::Drop {
+            self.seq.len()
+        } else {
+            (self.seq.len() + 1).saturating_sub(self.length)
+        }
+    }
+
+    /// Distribution of the start of a read drawn afresh, as the reads are
+    /// drawn: over every `step`-th window, weighted by the weights if set.
+    pub fn start_distribution(&self) -> Starts {
+        let windows = self.starts().div_ceil(self.step);
+        let weights = self
+            .weights
+            .and_then(|x| WeightedIndex::new((0..windows).map(|i| x[i * self.step])).ok());
+        Starts {
+            windows,
+            step: self.step,
+            weights,
+        }
+    }
+
+    /// The RNG reads are drawn from, to draw further from between reads.
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+}
+
+/// Distribution of the start of a read, see [`Sonicator::start_distribution`].
+/// Sampling panics if the sequence holds no window.
+pub struct Starts {
+    // windows reads start from, and the stride between them
+    windows: usize,
+    step: usize,
+    // weights of the windows, uniform if none or all zero
+    weights: Option<WeightedIndex<f64>>,
+}
+
+impl Distribution<usize> for Starts {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        let window = match &self.weights {
+            Some(weights) => weights.sample(rng),
+            None => rng.gen_range(0..self.windows),
+        };
+        window * self.step
+    }
+}
+
+impl<'a> Iterator for Sonicator<'a> {
+    type Item = Read;
+
+    fn next(&mut self) -> Option<Read> {
+        if self.seq.is_empty() {
+            return None;
+        }
+        while self.remaining == 0 {
+            if self.next >= self.starts() {
+                return None;
+            }
+            self.start = self.next;
+            self.next += self.step;
+            self.remaining = self.coverage.sample(&mut self.rng);
+            if let Some(weights) = self.weights {
+                let w = weights[self.start];
+                self.remaining =
+                    (0..self.remaining).filter(|_| self.rng.gen_bool(w)).count() as u64;
+            }
+        }
+        self.remaining -= 1;
+        self.count += 1;
+
+        let seq = window(self.seq, self.start, self.length, self.tail).into_owned();
+        Some(Read {
+            id: format!("{}seq_{}", self.prefix, self.count),
+            desc: None,
+            qual: None,
+            start: self.start,
+            end: self.start + seq.len(),
+            seq,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use std::collections::HashSet;
+
+    #[test]
+    fn next_read_stops_at_the_last_count() {
+        assert_eq!(next_read(0).unwrap(), 1);
+        assert_eq!(next_read(u64::MAX - 1).unwrap(), u64::MAX);
+        assert!(matches!(
+            next_read(u64::MAX),
+            Err(SonicatError::Parameter(_))
+        ));
+    }
+
+    #[test]
+    fn windows_past_the_end_follow_the_tail() {
+        let seq = b"ACGTA";
+        assert_eq!(window(seq, 1, 3, Tail::Drop).as_ref(), b"CGT");
+        assert_eq!(window(seq, 3, 3, Tail::Shrink).as_ref(), b"TA");
+        assert_eq!(window(seq, 3, 3, Tail::Wrap).as_ref(), b"TAA");
+        // reads longer than a circular record wrap around it repeatedly
+        assert_eq!(window(seq, 4, 12, Tail::Wrap).as_ref(), b"AACGTAACGTAA");
+    }
+
+    #[test]
+    fn circular_reads_longer_than_the_record_start_at_every_base() {
+        let seq = b"ACGTA";
+        let coverage = Coverage::poisson(3.0).unwrap();
+        let mut sonicator = Sonicator::new(seq, 12, &coverage, StdRng::seed_from_u64(1));
+        sonicator.set_tail(Tail::Wrap);
+        assert_eq!(sonicator.starts(), 5);
+        let reads: Vec<Read> = sonicator.collect();
+        assert!(!reads.is_empty());
+        for read in &reads {
+            assert_eq!(read.seq, window(seq, read.start, 12, Tail::Wrap).as_ref());
+            assert_eq!(read.end, read.start + 12);
+        }
+        let mut starts: Vec<usize> = reads.iter().map(|x| x.start).collect();
+        starts.dedup();
+        assert_eq!(starts, [0, 1, 2, 3, 4]);
+
+        // none of the windows fit otherwise
+        let sonicator = Sonicator::new(seq, 12, &coverage, StdRng::seed_from_u64(1));
+        assert_eq!(sonicator.count(), 0);
+    }
+
+    #[test]
+    fn reads_are_windows_of_the_sequence() {
+        let seq = b"ACGTACGGTTACGATCGGATCCAGT";
+        let coverage = Coverage::poisson(4.0).unwrap();
+        let reads: Vec<Read> =
+            Sonicator::new(seq, 10, &coverage, StdRng::seed_from_u64(1)).collect();
+
+        // 16 windows of 4 reads on average
+        assert!((40..90).contains(&reads.len()), "{}", reads.len());
+        for read in &reads {
+            assert_eq!(read.seq, &seq[read.start..read.end]);
+            assert_eq!(read.end - read.start, 10);
+            assert!(read.desc.is_none() && read.qual.is_none());
+        }
+        let ids: HashSet<&str> = reads.iter().map(|x| x.id.as_str()).collect();
+        assert_eq!(ids.len(), reads.len());
+        assert!(ids.iter().all(|x| x.starts_with("seq_")));
+
+        let mut sonicator = Sonicator::new(seq, 10, &coverage, StdRng::seed_from_u64(1));
+        sonicator.set_prefix("b_");
+        assert_eq!(sonicator.next().unwrap().id, "b_seq_1");
+    }
+
+    #[test]
+    fn steps_thin_the_starts_and_the_reads() {
+        let seq = vec![b'A'; 10_000];
+        let coverage = Coverage::poisson(5.0).unwrap();
+        let reads = |step| -> Vec<Read> {
+            let mut sonicator = Sonicator::new(&seq, 100, &coverage, StdRng::seed_from_u64(1));
+            sonicator.set_step(step);
+            sonicator.collect()
+        };
+        let all = reads(1).len() as f64;
+        let thinned = reads(5);
+        assert!(thinned.iter().all(|x| x.start % 5 == 0));
+        let ratio = all / thinned.len() as f64;
+        assert!((ratio - 5.0).abs() < 0.25, "{}", ratio);
+
+        let mut sonicator = Sonicator::new(&seq, 100, &coverage, StdRng::seed_from_u64(1));
+        sonicator.set_step(7);
+        let starts = sonicator.start_distribution();
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..1000 {
+            let start = rng.sample(&starts);
+            assert!(start % 7 == 0 && start <= 9900);
+        }
+    }
+
+    #[test]
+    fn tails_set_the_coverage_at_the_ends() {
+        let seq = [b'A'; 50];
+        let coverage = Coverage::poisson(20.0).unwrap();
+        // depth of the first, middle and last base relative to the middle
+        let depth = |tail| {
+            let mut sonicator = Sonicator::new(&seq, 10, &coverage, StdRng::seed_from_u64(1));
+            sonicator.set_tail(tail);
+            let mut depth = [0.0; 50];
+            for read in sonicator {
+                for i in read.start..read.end {
+                    depth[i % 50] += 1.0;
+                }
+            }
+            [depth[0] / depth[25], depth[49] / depth[25]]
+        };
+
+        // the ends are covered by a single window but for the wrapped ones
+        let [first, last] = depth(Tail::Drop);
+        assert!(first < 0.3 && last < 0.3);
+        let [first, last] = depth(Tail::Shrink);
+        assert!(first < 0.3 && last > 0.7);
+        let [first, last] = depth(Tail::Wrap);
+        assert!(first > 0.7 && last > 0.7);
+    }
+
+    fn read(qual: Option<Vec<u8>>) -> Read {
+        Read {
+            id: "r1".to_string(),
+            desc: Some("strand=+".to_string()),
+            seq: b"ACGT".to_vec(),
+            qual,
+            start: 0,
+            end: 4,
+        }
+    }
+
+    #[test]
+    fn reads_round_trip_through_fasta_records() {
+        let record = fasta::Record::from(read(None));
+        assert_eq!(record.id(), "r1");
+        assert_eq!(record.desc(), Some("strand=+"));
+        assert_eq!(record.seq(), b"ACGT");
+        assert_eq!(Read::from(&record), read(None));
+        // qualities are not written to FASTA
+        assert_eq!(
+            Read::from(&fasta::Record::from(read(Some(vec![30; 4])))),
+            read(None)
+        );
+    }
+
+    #[test]
+    fn reads_round_trip_through_fastq_records() {
+        let scored = read(Some(vec![0, 20, 40, MAX_PHRED]));
+        let record = fastq::Record::from(scored.clone());
+        assert_eq!(record.qual(), b"!5I~");
+        assert_eq!(record.desc(), Some("strand=+"));
+        assert_eq!(Read::from(&record), scored);
+
+        // a read without qualities is written at quality 0
+        let record = fastq::Record::from(read(None));
+        assert_eq!(record.qual(), b"!!!!");
+        assert_eq!(Read::from(&record).qual, Some(vec![0; 4]));
+
+        // qualities above the highest are written at the highest
+        let record = fastq::Record::from(read(Some(vec![94, 200, 223, 255])));
+        assert_eq!(record.qual(), b"~~~~");
+        assert_eq!(Read::from(&record).qual, Some(vec![MAX_PHRED; 4]));
+    }
+}
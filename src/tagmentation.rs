@@ -0,0 +1,63 @@
+//! Insertion preference of the Tn5 transposase of tagmentation libraries.
+
+use crate::alphabet::{self, Base};
+
+/// Consensus of the 9 bases duplicated at Tn5 insertion sites, after
+/// Goryshin et al. 1998, doi:10.1073/pnas.95.18.10716.
+pub const TN5_MOTIF: &[u8; 9] = b"GNTYWRANC";
+/// Factor of the insertion weight per base matching the consensus.
+pub const TN5_BIAS: f64 = 1.5;
+
+/// Relative preference of Tn5 for inserting at each base of `seq`,
+/// [`TN5_BIAS`] to the power of the bases of [`TN5_MOTIF`] the sequence
+/// from the base on matches, scaled to a maximum of 1. `N` in the motif
+/// matches nothing.
+pub fn start_weights(seq: &[u8]) -> Vec<f64> {
+    let mut weights: Vec<f64> = (0..seq.len())
+        .map(|i| {
+            let matches = TN5_MOTIF
+                .iter()
+                .zip(&seq[i..])
+                .filter(|(&m, &b)| {
+                    m != b'N'
+                        && Base::from_u8(b).is_some_and(|b| alphabet::iupac_bases(m).contains(&b))
+                })
+                .count();
+            TN5_BIAS.powi(matches as i32)
+        })
+        .collect();
+    let max = weights.iter().copied().fold(0.0, f64::max);
+    if max > 0.0 {
+        for w in weights.iter_mut() {
+            *w /= max;
+        }
+    }
+    weights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_grow_with_the_matches_of_the_motif() {
+        // 7 matches at the first base, N matching nothing, and T, Y and W
+        // alone matching a run of T
+        let weights = start_weights(b"GATCAGAACTTTTTTTTTTT");
+        assert_eq!(weights[0], 1.0);
+        assert!((weights[10] - TN5_BIAS.powi(3 - 7)).abs() < 1e-12);
+        assert!(weights.iter().all(|&w| w > 0.0 && w <= 1.0));
+        assert!(weights[1..].iter().all(|&w| w < 1.0));
+    }
+
+    #[test]
+    fn motifs_past_the_end_match_partially() {
+        let weights = start_weights(b"GATC");
+        // GATC matches G, T and C of the first four bases of the motif
+        assert_eq!(weights[0], 1.0);
+        assert_eq!(weights.len(), 4);
+        assert!(start_weights(b"").is_empty());
+        // bases other than ACGT match nothing
+        assert_eq!(start_weights(b"NNNN"), [1.0; 4]);
+    }
+}
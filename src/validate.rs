@@ -0,0 +1,138 @@
+//! Preflight checks of FASTA input.
+
+use bio::io::fasta;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::BufRead;
+
+/// Length statistics and problems of the records of a FASTA file.
+#[derive(Debug, Default)]
+pub struct Validation {
+    pub records: u64,
+    pub bases: u64,
+    pub min_length: Option<usize>,
+    pub max_length: usize,
+    /// Problems found, in input order.
+    pub problems: Vec<String>,
+}
+
+/// The statistics, a tab separated line each.
+impl fmt::Display for Validation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "records\t{}", self.records)?;
+        writeln!(f, "bases\t{}", self.bases)?;
+        writeln!(f, "min length\t{}", self.min_length.unwrap_or(0))?;
+        writeln!(f, "max length\t{}", self.max_length)?;
+        writeln!(f, "problems\t{}", self.problems.len())
+    }
+}
+
+/// Check every record of `reader` for a missing id, an empty sequence,
+/// symbols that are not `valid` and an id seen before. A malformed record
+/// ends the check, as the records after it cannot be told apart.
+pub fn validate<B: BufRead>(reader: fasta::Reader<B>, valid: impl Fn(u8) -> bool) -> Validation {
+    let mut validation = Validation::default();
+    let mut ids = HashSet::new();
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                validation.problems.push(format!(
+                    "malformed record {}: {}",
+                    validation.records + 1,
+                    e
+                ));
+                break;
+            }
+        };
+        validation.records += 1;
+        let seq = record.seq();
+        validation.bases += seq.len() as u64;
+        validation.min_length = Some(
+            validation
+                .min_length
+                .map_or(seq.len(), |x| x.min(seq.len())),
+        );
+        validation.max_length = validation.max_length.max(seq.len());
+
+        let id = record.id();
+        if id.is_empty() {
+            validation
+                .problems
+                .push(format!("record {} has no id", validation.records));
+        } else if !ids.insert(id.to_string()) {
+            validation
+                .problems
+                .push(format!("duplicate record id {}", id));
+        }
+        if seq.is_empty() {
+            validation.problems.push(format!("record {} is empty", id));
+        }
+        let invalid: Vec<(usize, u8)> = seq
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| !valid(b))
+            .map(|(i, &b)| (i, b))
+            .collect();
+        if let Some(&(pos, b)) = invalid.first() {
+            validation.problems.push(format!(
+                "record {} contains {} non-alphabet characters, the first {:?} at {}",
+                id,
+                invalid.len(),
+                b as char,
+                pos + 1
+            ));
+        }
+    }
+
+    validation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alphabet;
+
+    fn check(fasta: &[u8]) -> Validation {
+        validate(fasta::Reader::new(fasta), alphabet::is_valid)
+    }
+
+    #[test]
+    fn clean_input_has_no_problems() {
+        let validation = check(b">a\nACGT\n>b desc\nAC\nGT\nN\n");
+        assert_eq!(validation.records, 2);
+        assert_eq!(validation.bases, 9);
+        assert_eq!(validation.min_length, Some(4));
+        assert_eq!(validation.max_length, 5);
+        assert!(validation.problems.is_empty());
+        assert_eq!(
+            validation.to_string(),
+            "records\t2\nbases\t9\nmin length\t4\nmax length\t5\nproblems\t0\n"
+        );
+    }
+
+    #[test]
+    fn problems_are_reported_in_input_order() {
+        let validation = check(b">a\nACXTX\n>a\nACGT\n>\nACGT\n>e\n>f\nAC\n");
+        assert_eq!(validation.records, 5);
+        assert_eq!(
+            validation.problems,
+            [
+                "record a contains 2 non-alphabet characters, the first 'X' at 3",
+                "duplicate record id a",
+                "record 3 has no id",
+                "record e is empty",
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_input_ends_the_check() {
+        let validation = check(b"ACGT\n>a\nACGT\n");
+        assert_eq!(validation.records, 0);
+        assert_eq!(validation.min_length, None);
+        assert_eq!(validation.problems.len(), 1);
+        assert!(validation.problems[0].starts_with("malformed record 1"));
+    }
+}
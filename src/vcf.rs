@@ -0,0 +1,205 @@
+//! Minimal reader for the variant records of a VCF file.
+
+use crate::coords::CoordinateMap;
+use crate::error::{Result, SonicatError};
+use std::collections::HashMap;
+use std::io::BufRead;
+
+/// A simple variant, only the first ALT allele is kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    /// 0-based position of the REF allele.
+    pub pos: usize,
+    pub reference: Vec<u8>,
+    pub alt: Vec<u8>,
+}
+
+fn invalid(line: &str) -> SonicatError {
+    SonicatError::Parse(format!("malformed VCF line: {}", line))
+}
+
+/// Read the variants of a VCF file grouped by chromosome, in file order.
+///
+/// Symbolic and missing ALT alleles are skipped.
+pub fn read_variants<R: BufRead>(reader: R) -> Result<HashMap<String, Vec<Variant>>> {
+    let mut variants: HashMap<String, Vec<Variant>> = HashMap::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 5 {
+            return Err(invalid(&line));
+        }
+        let pos: usize = fields[1].parse().map_err(|_| invalid(&line))?;
+        let alt = fields[4].split(',').next().unwrap_or(".");
+        if pos == 0 || alt == "." || alt == "*" || alt.starts_with('<') {
+            continue;
+        }
+
+        variants
+            .entry(fields[0].to_string())
+            .or_default()
+            .push(Variant {
+                pos: pos - 1,
+                reference: fields[3].as_bytes().to_vec(),
+                alt: alt.as_bytes().to_vec(),
+            });
+    }
+
+    Ok(variants)
+}
+
+/// Apply variants to a sequence, returning the variant sequence and the
+/// number of variants applied.
+///
+/// Variants are applied in order of position; those whose REF does not
+/// match the sequence or that overlap an applied variant are skipped.
+pub fn apply(seq: &[u8], variants: &[Variant]) -> (Vec<u8>, usize) {
+    let mut sorted: Vec<&Variant> = variants.iter().collect();
+    sorted.sort_by_key(|v| v.pos);
+
+    let mut buf = Vec::with_capacity(seq.len());
+    let mut last = 0;
+    let mut applied = 0;
+
+    for v in sorted {
+        let end = v.pos + v.reference.len();
+        if v.pos < last || end > seq.len() || !seq[v.pos..end].eq_ignore_ascii_case(&v.reference) {
+            continue;
+        }
+        buf.extend_from_slice(&seq[last..v.pos]);
+        buf.extend_from_slice(&v.alt);
+        last = end;
+        applied += 1;
+    }
+    buf.extend_from_slice(&seq[last..]);
+
+    (buf, applied)
+}
+
+/// Whether applying `edits` to `seq` reproduces `mutated`, every edit
+/// applying.
+pub fn replays(seq: &[u8], edits: &[Variant], mutated: &[u8]) -> bool {
+    let (replayed, applied) = apply(seq, edits);
+    applied == edits.len() && replayed == mutated
+}
+
+/// Index of the first of `edits`, ordered by position, that does not hold:
+/// its REF is not in `seq` at its position, or its ALT not in `mutated`
+/// where the indels before it move it to. `None` if each holds, though an
+/// edit may still be missing.
+pub fn first_unreplayed(seq: &[u8], edits: &[Variant], mutated: &[u8]) -> Option<usize> {
+    let map = CoordinateMap::from_edits(edits);
+    edits.iter().position(|edit| {
+        let at = map.mutated_before(edit.pos);
+        let found = |x: &[u8], at: usize, allele: &[u8]| {
+            x.get(at..at + allele.len())
+                .is_some_and(|x| x.eq_ignore_ascii_case(allele))
+        };
+        !found(seq, edit.pos, &edit.reference) || !found(mutated, at, &edit.alt)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mutator::Mutator;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    const VCF: &str = "##fileformat=VCFv4.2\n\
+        #CHROM\tPOS\tID\tREF\tALT\n\
+        chr1\t2\t.\tC\tG,T\n\
+        chr1\t5\t.\tA\tATT\n\
+        chr1\t7\t.\tG\t<DEL>\n\
+        chr2\t1\t.\tAC\tA\n";
+
+    #[test]
+    fn variants_are_grouped_by_chromosome() {
+        let variants = read_variants(VCF.as_bytes()).unwrap();
+        assert_eq!(variants.len(), 2);
+        let chr1 = &variants["chr1"];
+        assert_eq!(chr1.len(), 2);
+        assert_eq!(
+            chr1[0],
+            Variant {
+                pos: 1,
+                reference: b"C".to_vec(),
+                alt: b"G".to_vec()
+            }
+        );
+        assert_eq!(chr1[1].alt, b"ATT");
+        assert!(matches!(
+            read_variants("chr1\tx\t.\tA\tC\n".as_bytes()),
+            Err(SonicatError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn mismatched_and_overlapping_variants_are_skipped() {
+        let variants = read_variants(VCF.as_bytes()).unwrap();
+        assert_eq!(
+            apply(b"ACGTAcG", &variants["chr1"]),
+            (b"AGGTATTcG".to_vec(), 2)
+        );
+
+        let variant = |pos, reference: &[u8], alt: &[u8]| Variant {
+            pos,
+            reference: reference.to_vec(),
+            alt: alt.to_vec(),
+        };
+        let variants = [
+            variant(1, b"CG", b"C"),
+            variant(2, b"G", b"T"),
+            variant(4, b"T", b"A"),
+            variant(6, b"A", b"C"),
+        ];
+        assert_eq!(apply(b"ACGTA", &variants), (b"ACTA".to_vec(), 1));
+    }
+
+    #[test]
+    fn corrupted_edits_do_not_replay() {
+        let seq = b"ACGTACGT";
+        let mut m = Mutator::new(0.2, 0.2, 0.2, StdRng::seed_from_u64(1)).unwrap();
+        m.set_record_edits(true);
+        let mutated = m.mutate_seq(seq);
+        let edits = m.take_edits();
+        assert!(edits.len() > 1);
+        assert!(replays(seq, &edits, &mutated));
+
+        assert_eq!(first_unreplayed(seq, &edits, &mutated), None);
+
+        let mut alt = edits.clone();
+        alt[0].alt.push(b'A');
+        assert!(!replays(seq, &alt, &mutated));
+        let mut reference = edits.clone();
+        reference[0].reference = b"N".to_vec();
+        assert!(!replays(seq, &reference, &mutated));
+        assert_eq!(first_unreplayed(seq, &reference, &mutated), Some(0));
+        assert!(!replays(seq, &edits[1..], &mutated));
+    }
+
+    #[test]
+    fn the_first_edit_not_holding_is_found() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let seq: Vec<u8> = (0..1000).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect();
+        let mut m = Mutator::new(0.05, 0.05, 0.05, StdRng::seed_from_u64(1)).unwrap();
+        m.set_record_edits(true);
+        let mutated = m.mutate_seq(&seq);
+        let edits = m.take_edits();
+        assert_eq!(first_unreplayed(&seq, &edits, &mutated), None);
+
+        // a substitution past indels changed to another base, the edits
+        // before it holding still
+        let i = (edits.len() / 2..edits.len())
+            .find(|&i| edits[i].alt.len() == 1 && edits[i].reference.len() == 1)
+            .unwrap();
+        let mut corrupted = edits.clone();
+        corrupted[i].alt = if edits[i].alt == b"A" { b"C" } else { b"A" }.to_vec();
+        assert!(!replays(&seq, &corrupted, &mutated));
+        assert_eq!(first_unreplayed(&seq, &corrupted, &mutated), Some(i));
+    }
+}
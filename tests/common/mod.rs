@@ -0,0 +1,79 @@
+//! Helpers of the integration tests, running the binaries on given input.
+
+#![allow(dead_code)]
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Output of `bin` run with `args`, `stdin` piped to it.
+pub fn run(bin: &str, args: &[&str], stdin: &[u8]) -> Output {
+    run_env(bin, args, &[], stdin)
+}
+
+/// Output of `bin` run as [`run`], with the environment variables `env`.
+pub fn run_env(bin: &str, args: &[&str], env: &[(&str, &str)], stdin: &[u8]) -> Output {
+    let mut child = Command::new(bin)
+        .args(args)
+        // the default log level, whatever the environment of the tests
+        .env_remove("RUST_LOG")
+        .envs(env.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // binaries exiting early may not read all of their input
+    let _ = child.stdin.take().unwrap().write_all(stdin);
+    child.wait_with_output().unwrap()
+}
+
+/// Stdout of a successful run of `bin`, failing on a nonzero exit.
+pub fn stdout(bin: &str, args: &[&str], stdin: &[u8]) -> Vec<u8> {
+    let output = run(bin, args, stdin);
+    assert!(
+        output.status.success(),
+        "{} {:?}: {}",
+        bin,
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output.stdout
+}
+
+/// Fresh directory `name` for the files of a test.
+pub fn dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Header and sequence of the records of a FASTA, wrapped or not.
+pub fn records(fasta: &[u8]) -> Vec<(String, String)> {
+    let text = String::from_utf8(fasta.to_vec()).unwrap();
+    let mut records = Vec::new();
+    for line in text.lines() {
+        match line.strip_prefix('>') {
+            Some(header) => records.push((header.to_string(), String::new())),
+            None => records.last_mut().unwrap().1.push_str(line),
+        }
+    }
+    records
+}
+
+/// Sequence of `n` bases drawn from a fixed linear congruential sequence,
+/// with no repeats long enough to matter.
+pub fn random_seq(n: usize, seed: u64) -> String {
+    let mut x = seed
+        .wrapping_mul(6364136223846793005)
+        .wrapping_add(1442695040888963407);
+    (0..n)
+        .map(|_| {
+            x = x
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            b"ACGT"[(x >> 62) as usize] as char
+        })
+        .collect()
+}
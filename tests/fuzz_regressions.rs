@@ -0,0 +1,31 @@
+//! Replay of the committed inputs of the fuzz target, crashes found by the
+//! fuzzer included once copied there.
+
+#[path = "../fuzz/fuzz_targets/harness.rs"]
+mod harness;
+
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn corpus_replays_without_panic() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/mutate");
+    let mut inputs = 0;
+    for entry in fs::read_dir(corpus).unwrap() {
+        let path = entry.unwrap().path();
+        if path
+            .file_name()
+            .and_then(|x| x.to_str())
+            .is_some_and(|x| x.starts_with("seed-"))
+        {
+            harness::run(&fs::read(&path).unwrap());
+            inputs += 1;
+        }
+    }
+    assert!(inputs > 0);
+}
+
+#[test]
+fn short_inputs_are_ignored() {
+    harness::run(&[0; harness::HEADER - 1]);
+}
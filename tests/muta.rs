@@ -0,0 +1,1063 @@
+mod common;
+
+use std::fs;
+
+use common::{random_seq, records, run, stdout};
+
+const MUTA: &str = env!("CARGO_BIN_EXE_muta");
+
+const NO_MUTATION: [&str; 6] = ["-s", "0", "-n", "0", "-d", "0"];
+
+#[test]
+fn whitespace_is_stripped_or_rejected_by_strict() {
+    let fasta = b">r\nAC GT\tAC\r\nGT\r\n";
+
+    let output = run(MUTA, &NO_MUTATION, fasta);
+    assert!(output.status.success());
+    assert_eq!(
+        records(&output.stdout),
+        [("r".to_string(), "ACGTACGT".to_string())]
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("stripped 2 non-alphabet characters"),
+        "{}",
+        stderr
+    );
+
+    let output = run(MUTA, &[&NO_MUTATION[..], &["--strict"]].concat(), fasta);
+    assert_eq!(output.status.code(), Some(65));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn records_mutate_alike_whatever_comes_before() {
+    let b = format!(">b\n{}\n", random_seq(2000, 2));
+    let both = format!(">a\n{}\n{}", random_seq(2000, 1), b);
+    let args = ["-s", "0.05", "-n", "0.05", "-d", "0.05", "--seed", "7"];
+
+    let alone = records(&stdout(MUTA, &args, b.as_bytes()));
+    let after = records(&stdout(MUTA, &args, both.as_bytes()));
+    assert_eq!(after.len(), 2);
+    assert_eq!(alone[0], after[1]);
+}
+
+#[test]
+fn quiet_silences_stats_and_warnings() {
+    let fasta = b">r\nAC GT\n>r\nACGT\n";
+    let args = ["--seed", "1", "--stats", "--timing", "-v"];
+
+    let output = run(MUTA, &args, fasta);
+    assert!(!output.stderr.is_empty());
+    let output = run(MUTA, &[&args[..], &["--quiet"]].concat(), fasta);
+    assert!(output.status.success());
+    assert!(
+        output.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = run(MUTA, &["--quiet", "-s", "2"], fasta);
+    assert_eq!(output.status.code(), Some(64));
+    assert!(!output.stderr.is_empty());
+}
+
+#[test]
+fn dash_is_stdin_and_stdout() {
+    let fasta = b">r\nACGTACGT\n";
+    let args = [&NO_MUTATION[..], &["-i", "-", "-o", "-"]].concat();
+    assert_eq!(records(&stdout(MUTA, &args, fasta)), records(fasta));
+}
+
+#[test]
+fn copies_are_distinct_and_numbered() {
+    let fasta = format!(">a\n{}\n>b\n{}\n", random_seq(500, 1), random_seq(500, 2));
+    let args = ["-s", "0.05", "-c", "3", "--seed", "1"];
+    let copies = records(&stdout(MUTA, &args, fasta.as_bytes()));
+
+    let ids: Vec<&str> = copies.iter().map(|x| x.0.as_str()).collect();
+    assert_eq!(
+        ids,
+        ["a_copy1", "a_copy2", "a_copy3", "b_copy1", "b_copy2", "b_copy3"]
+    );
+    for record in copies.chunks(3) {
+        assert_ne!(record[0].1, record[1].1);
+        assert_ne!(record[1].1, record[2].1);
+        assert_ne!(record[0].1, record[2].1);
+    }
+}
+
+#[test]
+fn zero_rates_copy_the_input() {
+    let fasta = format!(">a desc\n{}\n>b\nacgtNNRY--\n", random_seq(1000, 1));
+    assert_eq!(
+        stdout(MUTA, &NO_MUTATION, fasta.as_bytes()),
+        fasta.as_bytes()
+    );
+}
+
+#[test]
+fn stats_report_requested_and_realized_rates() {
+    let fasta = format!(">r\n{}\n", random_seq(100_000, 1));
+    let args = [
+        "-s", "0.1", "-n", "0.05", "-d", "0.02", "--seed", "1", "--stats",
+    ];
+    let output = run(MUTA, &args, fasta.as_bytes());
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    // the rates, and the mutations expected of them over every base
+    for (name, requested) in [
+        ("substitution", 0.1),
+        ("insertion", 0.05),
+        ("deletion", 0.02),
+        ("mutations", 17_000.0),
+    ] {
+        let line = stderr.lines().find(|x| x.starts_with(name)).unwrap();
+        let fields: Vec<f64> = line
+            .split('\t')
+            .skip(1)
+            .map(|x| x.parse().unwrap())
+            .collect();
+        assert_eq!(fields[0], requested);
+        assert!((fields[1] - requested).abs() < 0.1 * requested, "{}", line);
+    }
+
+    let exact = [&args[..], &["--num-mutations", "60", "--copies", "2"]].concat();
+    let output = run(MUTA, &exact, fasta.as_bytes());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.lines().any(|x| x == "mutations\t120.0\t120"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn config_file_sets_parameters_under_the_command_line() {
+    let dir = common::dir("muta-config");
+    let config = dir.join("muta.toml");
+    fs::write(
+        &config,
+        "substitution = 0\ninsertion = 0\ndeletion = 1\nseed = 1\n",
+    )
+    .unwrap();
+    let config = config.to_str().unwrap();
+    let fasta = b">r\nACGTACGT\n";
+
+    // every base deleted by the file, kept by the command line
+    assert_eq!(
+        records(&stdout(MUTA, &["--config", config], fasta))[0].1,
+        ""
+    );
+    let args = ["--config", config, "-d", "0"];
+    assert_eq!(records(&stdout(MUTA, &args, fasta)), records(fasta));
+}
+
+#[test]
+fn verbose_logs_more_without_changing_the_output() {
+    let fasta = format!(">r\n{}\n", random_seq(100, 1));
+    let args = ["-s", "0.1", "--seed", "1"];
+    let quiet = run(MUTA, &args, fasta.as_bytes());
+    let debug = run(MUTA, &[&args[..], &["-vv"]].concat(), fasta.as_bytes());
+
+    assert!(quiet.stderr.is_empty());
+    let stderr = String::from_utf8(debug.stderr).unwrap();
+    assert!(
+        stderr.contains("DEBUG") && stderr.contains("INFO"),
+        "{}",
+        stderr
+    );
+    assert_eq!(quiet.stdout, debug.stdout);
+}
+
+#[cfg(all(feature = "gzip", feature = "zstd"))]
+#[test]
+fn compress_applies_to_stdout() {
+    let fasta = format!(">r\n{}\n", random_seq(1000, 1));
+    for (compression, magic) in [
+        ("gzip", &[0x1f, 0x8b][..]),
+        ("zstd", &[0x28, 0xb5, 0x2f, 0xfd]),
+    ] {
+        let args = [&NO_MUTATION[..], &["--compress", compression]].concat();
+        let compressed = stdout(MUTA, &args, fasta.as_bytes());
+        assert!(compressed.starts_with(magic), "{}", compression);
+        // decompressed by the input of muta
+        assert_eq!(stdout(MUTA, &NO_MUTATION, &compressed), fasta.as_bytes());
+    }
+}
+
+/// Number of bases at which `a` and `b`, of the same length, differ.
+fn differences(a: &str, b: &str) -> usize {
+    a.bytes().zip(b.bytes()).filter(|(x, y)| x != y).count()
+}
+
+#[test]
+fn cpg_islands_take_more_substitutions_than_their_flanks() {
+    let flank = "AATTATAT".repeat(250);
+    let seq = format!("{}{}{}", flank, "CGCAGGCT".repeat(250), flank);
+    let fasta = format!(">r\n{}\n", seq);
+    let args = [
+        "-s",
+        "0.01",
+        "-n",
+        "0",
+        "-d",
+        "0",
+        "--cpg-boost",
+        "10",
+        "--seed",
+        "1",
+    ];
+    let mutated = &records(&stdout(MUTA, &args, fasta.as_bytes()))[0].1;
+
+    let island = differences(&seq[2000..4000], &mutated[2000..4000]);
+    let flanks =
+        differences(&seq[..2000], &mutated[..2000]) + differences(&seq[4000..], &mutated[4000..]);
+    // about 200 substitutions in the island and 40 in the flanks
+    assert!(island > 2 * flanks, "{} {}", island, flanks);
+
+    let args = ["--cpg-boost", "10", "--alphabet", "protein"];
+    let output = run(MUTA, &args, b">r\nMKV\n");
+    assert_eq!(output.status.code(), Some(64));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--cpg-boost requires nucleotide"));
+}
+
+#[test]
+fn saturate_changes_every_base() {
+    let seq = random_seq(5000, 1);
+    let fasta = format!(">r\n{}\n", seq);
+    let mutated = &records(&stdout(
+        MUTA,
+        &["--saturate", "--seed", "1"],
+        fasta.as_bytes(),
+    ))[0]
+        .1;
+    assert_eq!(differences(&seq, mutated), seq.len());
+}
+
+#[test]
+fn no_color_leaves_diagnostics_plain() {
+    let fasta = b">r\nAC GT\n";
+    let env = [("NO_COLOR", "1"), ("RUST_LOG", "debug")];
+    let output = common::run_env(MUTA, &["--seed", "1"], &env, fasta);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("WARN") && stderr.contains("DEBUG"),
+        "{}",
+        stderr
+    );
+    assert!(!stderr.contains('\x1b'));
+}
+
+#[test]
+fn replicates_differ_and_reproduce() {
+    let fasta = format!(">r\n{}\n", random_seq(1000, 1));
+    let replicate = |n: &str| {
+        stdout(
+            MUTA,
+            &["-s", "0.05", "--seed", "1", "--replicate", n],
+            fasta.as_bytes(),
+        )
+    };
+    assert_eq!(replicate("1"), replicate("1"));
+    assert_ne!(replicate("1"), replicate("2"));
+}
+
+#[test]
+fn paths_with_spaces_and_non_ascii_characters() {
+    let dir = common::dir("unicode paths").join("séquences ünd Ωmega");
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("entrée 1.fa");
+    let output = dir.join("sortie ä.fa");
+    fs::write(&input, ">r\nACGT\n").unwrap();
+    let args = [
+        &NO_MUTATION[..],
+        &[
+            "-i",
+            input.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+        ],
+    ]
+    .concat();
+    assert!(stdout(MUTA, &args, b"").is_empty());
+    assert_eq!(fs::read(&output).unwrap(), b">r\nACGT\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_paths() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::process::Command;
+
+    let dir = common::dir("non-utf8 paths");
+    let input = dir.join(OsStr::from_bytes(b"in\xff.fa"));
+    let output = dir.join(OsStr::from_bytes(b"out\xfe.fa"));
+    fs::write(&input, ">r\nACGT\n").unwrap();
+    let status = Command::new(MUTA)
+        .args(NO_MUTATION)
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(fs::read(&output).unwrap(), b">r\nACGT\n");
+}
+
+#[test]
+fn list_records_prints_ids_and_lengths() {
+    let fasta = b">a desc\nACGT\nAC\n>b\n\n>c\nNNN\n";
+    assert_eq!(
+        stdout(MUTA, &["--list-records"], fasta),
+        b"a\t6\nb\t0\nc\t3\n"
+    );
+}
+
+#[test]
+fn indel_histograms_sum_to_the_indel_counts() {
+    let json = common::dir("muta-histogram").join("stats.json");
+    let fasta = format!(">r\n{}\n", random_seq(20_000, 1));
+    let args = [
+        "-s",
+        "0.01",
+        "-n",
+        "0.01",
+        "-d",
+        "0.2",
+        "--seed",
+        "1",
+        "--stats",
+        "--stats-json",
+        json.to_str().unwrap(),
+    ];
+    let output = run(MUTA, &args, fasta.as_bytes());
+    assert!(output.status.success());
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&json).unwrap()).unwrap();
+    let counts = &stats["counts"];
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    for kind in ["insertion", "deletion"] {
+        let bins: Vec<u64> = counts[format!("{}_lengths", kind)]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| x.as_u64().unwrap())
+            .collect();
+        // no run reaches the open last bin at these rates
+        assert_eq!(bins[9], 0);
+        let bases: u64 = bins
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (i as u64 + 1) * n)
+            .sum();
+        assert_eq!(bases, counts[format!("{}s", kind)].as_u64().unwrap());
+
+        // the printed histogram holds the nonzero bins
+        let printed = stderr
+            .lines()
+            .find_map(|x| x.strip_prefix(&format!("{} lengths\t", kind)))
+            .unwrap();
+        let expected: Vec<String> = bins
+            .iter()
+            .enumerate()
+            .filter(|x| *x.1 > 0)
+            .map(|(i, n)| format!("{}:{}", i + 1, n))
+            .collect();
+        assert_eq!(printed, expected.join(" "));
+    }
+}
+
+#[test]
+fn verify_passes_on_every_path() {
+    let fasta = format!(">r\n{}\n", random_seq(5000, 1));
+    let rates = [
+        "-s", "0.05", "-n", "0.05", "-d", "0.05", "--seed", "1", "--verify",
+    ];
+    for extra in [
+        &[][..],
+        &["--chunk-size", "1000"],
+        &["--streaming"],
+        &["--deletion-mode", "mask"],
+        &["--num-mutations", "20"],
+    ] {
+        let output = run(MUTA, &[&rates[..], extra].concat(), fasta.as_bytes());
+        assert!(
+            output.status.success(),
+            "{:?}: {}",
+            extra,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+#[test]
+fn chunked_mutation_is_the_same_on_any_number_of_threads() {
+    let fasta = format!(
+        ">a\n{}\n>b\n{}\n",
+        random_seq(50_000, 1),
+        random_seq(3000, 2)
+    );
+    let args = [
+        "-s",
+        "0.01",
+        "-n",
+        "0.01",
+        "-d",
+        "0.01",
+        "--seed",
+        "1",
+        "--chunk-size",
+        "4096",
+    ];
+    let single = stdout(
+        MUTA,
+        &[&args[..], &["--threads", "1"]].concat(),
+        fasta.as_bytes(),
+    );
+    for threads in ["2", "8"] {
+        let parallel = stdout(
+            MUTA,
+            &[&args[..], &["--threads", threads]].concat(),
+            fasta.as_bytes(),
+        );
+        assert_eq!(parallel, single);
+    }
+    assert_ne!(records(&single)[0].1, random_seq(50_000, 1));
+}
+
+#[test]
+fn existing_outputs_are_only_replaced_with_force() {
+    let dir = common::dir("muta-overwrite");
+    let out = dir.join("mutated.fa");
+    fs::write(&out, "kept\n").unwrap();
+    let fasta = b">r\nACGT\n";
+    let args = [&NO_MUTATION[..], &["-o", out.to_str().unwrap()]].concat();
+
+    let output = run(MUTA, &args, fasta);
+    assert_eq!(output.status.code(), Some(74));
+    assert_eq!(fs::read_to_string(&out).unwrap(), "kept\n");
+
+    stdout(MUTA, &[&args[..], &["--force"]].concat(), fasta);
+    assert_eq!(fs::read(&out).unwrap(), fasta);
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn gzip_stdin_is_detected() {
+    use std::io::Write;
+
+    let fasta = format!(">r\n{}\n", random_seq(1000, 1));
+    let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gz.write_all(fasta.as_bytes()).unwrap();
+    let piped = gz.finish().unwrap();
+    assert_eq!(stdout(MUTA, &NO_MUTATION, &piped), fasta.as_bytes());
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd_stdin_is_detected() {
+    let fasta = format!(">r\n{}\n", random_seq(1000, 1));
+    let piped = zstd::encode_all(fasta.as_bytes(), 0).unwrap();
+    assert_eq!(&piped[..4], [0x28, 0xb5, 0x2f, 0xfd]);
+    assert_eq!(stdout(MUTA, &NO_MUTATION, &piped), fasta.as_bytes());
+}
+
+#[test]
+fn control_output_is_the_input() {
+    let dir = common::dir("muta-control");
+    let control = dir.join("control.fa");
+    let fasta = format!(
+        ">a desc\n{}\n>b\n{}\n",
+        random_seq(2000, 1),
+        random_seq(500, 2)
+    );
+    let args = [
+        "-s",
+        "0.05",
+        "--seed",
+        "1",
+        "--control",
+        control.to_str().unwrap(),
+    ];
+
+    let mutated = stdout(MUTA, &args, fasta.as_bytes());
+    let control = records(&fs::read(&control).unwrap());
+    assert_eq!(control, records(fasta.as_bytes()));
+    let mutated = records(&mutated);
+    assert_eq!(mutated.len(), control.len());
+    for ((_, x), (_, y)) in mutated.iter().zip(&control) {
+        assert!(differences(x, y) > 0);
+    }
+}
+
+/// Timing lines of the stderr of a --timing run, after the header.
+fn timing(stderr: &[u8], header: &str) -> Vec<Vec<String>> {
+    let text = String::from_utf8_lossy(stderr);
+    let mut lines = text.lines().skip_while(|x| *x != header);
+    assert_eq!(lines.next(), Some(header));
+    lines
+        .map(|x| x.split('\t').map(String::from).collect())
+        .collect()
+}
+
+#[test]
+fn timing_goes_to_stderr_alone() {
+    let fasta = format!(">a\n{}\n>b\n{}\n", random_seq(2000, 1), random_seq(300, 2));
+    let args = ["-s", "0.01", "--seed", "1"];
+    let output = run(MUTA, &[&args[..], &["--timing"]].concat(), fasta.as_bytes());
+    assert!(output.status.success());
+    assert_eq!(output.stdout, stdout(MUTA, &args, fasta.as_bytes()));
+
+    let lines = timing(&output.stderr, "record\tbases\tseconds\tbases/s");
+    let names: Vec<&str> = lines.iter().map(|x| x[0].as_str()).collect();
+    assert_eq!(names, ["a", "b", "total"]);
+    assert_eq!(lines[2][1], "2300");
+}
+
+#[test]
+fn only_cds_mutates_the_cds_alone() {
+    let dir = common::dir("muta-only-cds");
+    let gff = dir.join("genes.gff");
+    fs::write(
+        &gff,
+        "##gff-version 3\nr\t.\tCDS\t101\t400\t.\t+\t0\t.\nr\t.\tCDS\t601\t900\t.\t-\t0\t.\n",
+    )
+    .unwrap();
+    let seq = random_seq(1000, 1);
+    let fasta = format!(">r\n{}\n", seq);
+    let args = [
+        "-s",
+        "0.2",
+        "-n",
+        "0",
+        "-d",
+        "0",
+        "--seed",
+        "1",
+        "--only-cds",
+        "--gff",
+    ];
+
+    let mutated = records(&stdout(
+        MUTA,
+        &[&args[..], &[gff.to_str().unwrap()]].concat(),
+        fasta.as_bytes(),
+    ));
+    let mutated = mutated[0].1.as_bytes();
+    let within = |i: usize| (100..400).contains(&i) || (600..900).contains(&i);
+    let mut inside = 0;
+    for (i, (a, b)) in seq.bytes().zip(mutated).enumerate() {
+        if a != *b {
+            assert!(within(i), "mutation at {}", i);
+            inside += 1;
+        }
+    }
+    assert!(inside > 50);
+
+    let output = run(MUTA, &["--only-cds"], fasta.as_bytes());
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[test]
+fn gff_features_are_refused_on_proteins_and_stripped_records() {
+    let dir = common::dir("muta-gff-refused");
+    let gff = dir.join("genes.gff");
+    fs::write(&gff, "##gff-version 3\nr\t.\tCDS\t1\t6\t.\t+\t0\t.\n").unwrap();
+    let gff = gff.to_str().unwrap();
+
+    for args in [&["--gff", gff][..], &["--only-cds"][..]] {
+        let output = run(
+            MUTA,
+            &[args, &["--alphabet", "protein"]].concat(),
+            b">r\nMKV\n",
+        );
+        assert_eq!(output.status.code(), Some(64));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("nucleotide"));
+    }
+
+    let args = [&NO_MUTATION[..], &["--only-cds", "--gff", gff]].concat();
+    let output = run(MUTA, &args, b">r\nACG TAC\n>s\nAC GT\n");
+    assert_eq!(output.status.code(), Some(65));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("shift"));
+    // records without features may still be stripped
+    let output = run(MUTA, &args, b">r\nACGTAC\n>s\nAC GT\n");
+    assert!(output.status.success());
+}
+
+#[test]
+fn dnds_requires_a_gff_of_nucleotide_records() {
+    let output = run(MUTA, &["--dnds", "0.5"], b">r\nACGT\n");
+    assert_eq!(output.status.code(), Some(64));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--gff"));
+
+    let args = ["--dnds", "0.5", "--alphabet", "protein"];
+    let output = run(MUTA, &args, b">r\nMKV\n");
+    assert_eq!(output.status.code(), Some(64));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--dnds requires nucleotide"));
+}
+
+/// Windows and counts of the data lines of a bedGraph.
+fn bedgraph(path: &std::path::Path) -> Vec<(String, usize, usize, usize)> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .map(|x| {
+            let f: Vec<&str> = x.split('\t').collect();
+            (
+                f[0].to_string(),
+                f[1].parse().unwrap(),
+                f[2].parse().unwrap(),
+                f[3].parse().unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn density_sums_to_the_mutations() {
+    let dir = common::dir("muta-density");
+    let density = dir.join("density.bg");
+    let seq = random_seq(2500, 1);
+    let fasta = format!(">a\n{}\n>b\nACGTACGTAC\n", seq);
+    let args = [
+        "--seed",
+        "1",
+        "--density",
+        density.to_str().unwrap(),
+        "--density-window",
+        "1000",
+        "--force",
+    ];
+
+    // substitutions alone, counted window by window
+    let mutated = stdout(
+        MUTA,
+        &[&args[..], &["-s", "0.02", "-n", "0", "-d", "0"]].concat(),
+        fasta.as_bytes(),
+    );
+    let mutated = records(&mutated);
+    let windows = bedgraph(&density);
+    assert_eq!(
+        windows
+            .iter()
+            .map(|x| (x.0.as_str(), x.1, x.2))
+            .collect::<Vec<_>>(),
+        [
+            ("a", 0, 1000),
+            ("a", 1000, 2000),
+            ("a", 2000, 2500),
+            ("b", 0, 10)
+        ]
+    );
+    for (contig, start, end, count) in &windows[..3] {
+        assert_eq!(contig, "a");
+        assert_eq!(
+            *count,
+            differences(&seq[*start..*end], &mutated[0].1[*start..*end])
+        );
+    }
+    assert!(windows.iter().map(|x| x.3).sum::<usize>() > 0);
+
+    // every kind of edit
+    let exact = [
+        "--num-mutations",
+        "60",
+        "-s",
+        "0.5",
+        "-n",
+        "0.25",
+        "-d",
+        "0.25",
+    ];
+    let mutated = records(&stdout(
+        MUTA,
+        &[&args[..], &exact].concat(),
+        fasta.as_bytes(),
+    ));
+    let windows = bedgraph(&density);
+    assert_eq!(windows.iter().map(|x| x.3).sum::<usize>(), 60);
+    // windows over the mutated copies, moved by their indels
+    for (id, seq) in &mutated {
+        let last = windows.iter().rfind(|x| &x.0 == id).unwrap();
+        assert_eq!(last.2, seq.len(), "{}", id);
+    }
+    assert_ne!(mutated[0].1.len(), 2500);
+
+    // windows of no base
+    let output = run(
+        MUTA,
+        &[
+            &args[..4],
+            &["--density-window", "0", "-s", "0.1", "--force"],
+        ]
+        .concat(),
+        fasta.as_bytes(),
+    );
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[test]
+fn validate_reports_the_problems_of_the_input() {
+    let output = run(MUTA, &["--validate"], b">a\nAC GT\n>\nACGT\n");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("the first ' ' at 3"));
+    assert!(stderr.contains("record 2 has no id"));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("problems\t2\n"));
+}
+
+#[test]
+fn record_command_writes_the_invocation() {
+    let dir = common::dir("muta-record-command");
+    let out = dir.join("out.fa");
+    let args = ["-s", "0.1", "--record-command", "-o", out.to_str().unwrap()];
+    stdout(MUTA, &args, b">r\nACGT\n");
+    let recorded = fs::read_to_string(dir.join("out.fa.cmd")).unwrap();
+    let lines: Vec<&str> = recorded.lines().collect();
+    assert_eq!(lines[0], format!("# muta {}", env!("CARGO_PKG_VERSION")));
+    assert_eq!(lines[1], format!("{} {}", MUTA, args.join(" ")));
+
+    // stdout has no file to record the command line next to
+    let args = ["-s", "0.1", "--record-command", "-o", "-"];
+    let output = run(MUTA, &args, b">r\nACGT\n");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no output file"));
+    assert!(!std::path::Path::new("-.cmd").exists());
+}
+
+#[test]
+fn single_base_records_are_mutated() {
+    let fasta = b">a\nG\n>b\nT\n";
+    let reads = records(&stdout(
+        MUTA,
+        &["-s", "1", "-n", "0", "-d", "0", "--seed", "1"],
+        fasta,
+    ));
+    assert_eq!(reads.len(), 2);
+    assert!(reads.iter().all(|(_, x)| x.len() == 1));
+    assert_ne!(reads[0].1, "G");
+    assert_ne!(reads[1].1, "T");
+    assert_eq!(records(&stdout(MUTA, &NO_MUTATION, fasta)), records(fasta));
+}
+
+#[test]
+fn errors_exit_with_their_status() {
+    let cases: [(&[&str], &[u8], i32, &str); 4] = [
+        (
+            &["--strict"],
+            b">r\nACXT\n",
+            65,
+            "record r contains non-alphabet character 'X'",
+        ),
+        (&["-i", "/nonexistent/genome.fa"], b"", 74, "No such file"),
+        (&["-s", "2"], b">r\nACGT\n", 64, "substitution"),
+        (&[], b"ACGT\n", 65, "malformed FASTA record"),
+    ];
+    for (args, stdin, code, message) in cases {
+        let output = run(MUTA, args, stdin);
+        assert_eq!(output.status.code(), Some(code), "{:?}", args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(message), "{}", stderr);
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+    }
+}
+
+#[test]
+fn revcomp_mutates_the_reverse_complement() {
+    let seq = random_seq(2000, 1);
+    let revcomp = String::from_utf8(sonicat::alphabet::reverse_complement(seq.as_bytes())).unwrap();
+    let args = ["-s", "0.02", "-n", "0.01", "-d", "0.01", "--seed", "1"];
+
+    let mutated = stdout(
+        MUTA,
+        &[&args[..], &["--revcomp"]].concat(),
+        format!(">r\n{}\n", seq).as_bytes(),
+    );
+    let expected = stdout(MUTA, &args, format!(">r\n{}\n", revcomp).as_bytes());
+    assert_eq!(mutated, expected);
+    assert_ne!(records(&mutated)[0].1, revcomp);
+
+    let kept = stdout(
+        MUTA,
+        &[&NO_MUTATION[..], &["--revcomp"]].concat(),
+        b">r\nAACGTn\n",
+    );
+    assert_eq!(records(&kept)[0].1, "nACGTT");
+}
+
+#[test]
+fn insert_local_composition_is_checked() {
+    let fasta = format!(">r\n{}\n", random_seq(1000, 1));
+    let args = ["-s", "0", "-n", "0.05", "-d", "0", "--seed", "1"];
+    let local = stdout(
+        MUTA,
+        &[
+            &args[..],
+            &["--insert-local-composition", "--composition-window", "50"],
+        ]
+        .concat(),
+        fasta.as_bytes(),
+    );
+    assert!(records(&local)[0].1.len() > 1000);
+    let output = run(
+        MUTA,
+        &[
+            &args[..],
+            &["--insert-local-composition", "--composition-window", "0"],
+        ]
+        .concat(),
+        fasta.as_bytes(),
+    );
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[test]
+fn stats_json_holds_the_metrics_of_the_run() {
+    let dir = common::dir("muta-stats-json");
+    let json = dir.join("stats.json");
+    let seq = random_seq(2000, 1);
+    let fasta = format!(">r\n{}\n", seq);
+    let args = [
+        "-s",
+        "0.1",
+        "-n",
+        "0",
+        "-d",
+        "0",
+        "--seed",
+        "7",
+        "--stats-json",
+        json.to_str().unwrap(),
+    ];
+    let mutated = records(&stdout(MUTA, &args, fasta.as_bytes()));
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&json).unwrap()).unwrap();
+
+    assert_eq!(stats["program"], "muta");
+    assert_eq!(stats["command"], format!("{} {}", MUTA, args.join(" ")));
+    let counts = &stats["counts"];
+    let substitutions = differences(&seq, &mutated[0].1) as u64;
+    assert_eq!(counts["records"], 1);
+    assert_eq!(counts["bases"], 2000);
+    assert_eq!(counts["substitutions"], substitutions);
+    assert_eq!(counts["insertions"], 0);
+    assert_eq!(counts["deletions"], 0);
+    let rate = stats["rates"]["substitution"].as_f64().unwrap();
+    assert!((rate - substitutions as f64 / 2000.0).abs() < 1e-12);
+    assert_eq!(stats["parameters"]["substitution"], 0.1);
+    assert_eq!(stats["parameters"]["seed"], 7);
+}
+
+#[test]
+fn input_list_reads_every_listed_input() {
+    let dir = common::dir("muta-input-list");
+    let (a, b, list) = (dir.join("a.fa"), dir.join("b.fa"), dir.join("inputs.txt"));
+    fs::write(&a, ">a\nACGT\n").unwrap();
+    fs::write(&b, ">b\nGGCC\n").unwrap();
+    // relative to the directory of the list, not of muta
+    fs::write(&list, format!("# cohort\n{}\n\nb.fa\n", a.display())).unwrap();
+
+    let args = [&NO_MUTATION[..], &["--input-list", list.to_str().unwrap()]].concat();
+    assert_eq!(stdout(MUTA, &args, b""), b">a\nACGT\n>b\nGGCC\n");
+}
+
+#[test]
+fn deletion_modes_set_the_length_of_the_output() {
+    let seq = random_seq(3000, 1);
+    let fasta = format!(">r\n{}\n", seq);
+    let args = [
+        "-s",
+        "0",
+        "-n",
+        "0",
+        "-d",
+        "0.05",
+        "--seed",
+        "1",
+        "--deletion-mode",
+    ];
+
+    let removed = records(&stdout(
+        MUTA,
+        &[&args[..], &["remove"]].concat(),
+        fasta.as_bytes(),
+    ));
+    let masked = records(&stdout(
+        MUTA,
+        &[&args[..], &["mask"]].concat(),
+        fasta.as_bytes(),
+    ));
+    let (removed, masked) = (&removed[0].1, &masked[0].1);
+    assert!(removed.len() < seq.len());
+    assert!(!removed.contains('N'));
+    assert_eq!(masked.len(), seq.len());
+    let n = masked.bytes().filter(|&x| x == b'N').count();
+    assert_eq!(n, seq.len() - removed.len());
+    // the masked bases are the ones removed, the rest kept in place
+    assert!(seq
+        .bytes()
+        .zip(masked.bytes())
+        .all(|(a, b)| a == b || b == b'N'));
+    let kept: String = masked.chars().filter(|&x| x != 'N').collect();
+    assert_eq!(&kept, removed);
+
+    // a value clap rejects
+    let output = run(MUTA, &[&args[..], &["shift"]].concat(), fasta.as_bytes());
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn num_mutations_makes_exactly_that_many_edits() {
+    let dir = common::dir("muta-num-mutations");
+    let (json, density) = (dir.join("stats.json"), dir.join("density.bg"));
+    let (a, b) = (random_seq(3000, 1), random_seq(1000, 2));
+    let fasta = format!(">a\n{}\n>b\n{}\n", a, b);
+    let args = [
+        "-s",
+        "0.6",
+        "-n",
+        "0.2",
+        "-d",
+        "0.2",
+        "--seed",
+        "1",
+        "--num-mutations",
+        "400",
+        "--stats-json",
+        json.to_str().unwrap(),
+        "--density",
+        density.to_str().unwrap(),
+        "--density-window",
+        "100000",
+    ];
+    let mutated = records(&stdout(MUTA, &args, fasta.as_bytes()));
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&json).unwrap()).unwrap();
+    let counts = &stats["counts"];
+    let edits: u64 = ["substitutions", "insertions", "deletions"]
+        .iter()
+        .map(|x| counts[x].as_u64().unwrap())
+        .sum();
+    assert_eq!(edits, 400);
+    let ins = counts["insertions"].as_u64().unwrap() as usize;
+    let del = counts["deletions"].as_u64().unwrap() as usize;
+    assert_eq!(mutated[0].1.len() + mutated[1].1.len(), 4000 + ins - del);
+
+    // records share the edits by their length
+    let windows = bedgraph(&density);
+    assert_eq!(windows.len(), 2);
+    assert_eq!(windows[0].3 + windows[1].3, 400);
+    assert!(
+        (windows[0].3 as f64 / 300.0 - 1.0).abs() < 0.15,
+        "{:?}",
+        windows
+    );
+
+    // substitutions alone always change the base
+    let exact = [
+        "-s",
+        "1",
+        "-n",
+        "0",
+        "-d",
+        "0",
+        "--seed",
+        "2",
+        "--num-mutations",
+        "50",
+    ];
+    let mutated = records(&stdout(MUTA, &exact, fasta.as_bytes()));
+    assert_eq!(
+        differences(&a, &mutated[0].1) + differences(&b, &mutated[1].1),
+        50
+    );
+}
+
+#[test]
+fn alignment_mode_leaves_gaps_and_excludes_them_from_rates() {
+    let dir = common::dir("muta-alignment-mode");
+    let json = dir.join("stats.json");
+    let row = |seed| -> String {
+        random_seq(1000, seed)
+            .as_bytes()
+            .chunks(4)
+            .map(|x| format!("{}--", String::from_utf8_lossy(x)))
+            .collect()
+    };
+    let (a, b) = (row(1), row(2));
+    let fasta = format!(">a\n{}\n>b\n{}\n", a, b);
+    let args = [
+        "-s",
+        "0.1",
+        "-n",
+        "0",
+        "-d",
+        "0",
+        "--seed",
+        "1",
+        "--alignment-mode",
+        "--stats-json",
+        json.to_str().unwrap(),
+    ];
+    let mutated = records(&stdout(MUTA, &args, fasta.as_bytes()));
+    let mut substitutions = 0;
+    for ((_, x), y) in mutated.iter().zip([&a, &b]) {
+        assert_eq!(x.len(), y.len());
+        assert!(x
+            .bytes()
+            .zip(y.bytes())
+            .all(|(p, q)| (p == b'-') == (q == b'-')));
+        substitutions += differences(x, y);
+    }
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&json).unwrap()).unwrap();
+    assert_eq!(stats["counts"]["bases"], 2000);
+    let rate = stats["rates"]["substitution"].as_f64().unwrap();
+    assert!((rate - substitutions as f64 / 2000.0).abs() < 1e-12);
+}
+
+#[test]
+fn streaming_a_large_record_matches_the_whole_record_output() {
+    let fasta = format!(">chr1 large\n{}\n", random_seq(1_000_000, 1));
+    let args = ["-s", "0.01", "-n", "0.005", "--seed", "1", "--stats"];
+    // deletions at half the bases run across the ends of blocks
+    for extra in [
+        &["-d", "0.005"][..],
+        &["-d", "0.005", "--insert-gc", "0.7"],
+        &["-d", "0.005", "--cpg-boost", "5"],
+        &["-d", "0.5"],
+    ] {
+        let whole = run(MUTA, &[&args[..], extra].concat(), fasta.as_bytes());
+        let streamed = run(
+            MUTA,
+            &[&args[..], extra, &["--streaming"]].concat(),
+            fasta.as_bytes(),
+        );
+        assert!(streamed.stdout == whole.stdout, "{:?}", extra);
+        assert_eq!(
+            String::from_utf8_lossy(&streamed.stderr),
+            String::from_utf8_lossy(&whole.stderr),
+            "{:?}",
+            extra
+        );
+    }
+
+    // the composition around an insertion needs the mutated copy whole
+    let local = ["--streaming", "--insert-local-composition"];
+    assert_eq!(run(MUTA, &local, fasta.as_bytes()).status.code(), Some(2));
+    let dir = common::dir("muta-streaming");
+    let config = dir.join("muta.toml");
+    fs::write(
+        &config,
+        "streaming = true\ninsert-local-composition = true\n",
+    )
+    .unwrap();
+    let output = run(
+        MUTA,
+        &["--config", config.to_str().unwrap()],
+        fasta.as_bytes(),
+    );
+    assert_eq!(output.status.code(), Some(64));
+}
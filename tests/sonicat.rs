@@ -0,0 +1,2253 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{random_seq, records, run, stdout};
+
+const SONICAT: &str = env!("CARGO_BIN_EXE_sonicat");
+
+#[test]
+fn max_n_filters_reads_of_an_n_rich_region() {
+    let seq = format!(
+        "{}{}{}",
+        random_seq(300, 1),
+        "N".repeat(200),
+        random_seq(300, 2)
+    );
+    let fasta = format!(">r\n{}\n", seq);
+    let args = ["-l", "50", "-d", "5", "--seed", "1", "--stats"];
+
+    let reads = stdout(SONICAT, &args, fasta.as_bytes());
+    assert!(records(&reads).iter().any(|(_, x)| x.contains('N')));
+
+    let output = run(
+        SONICAT,
+        &[&args[..], &["--max-n", "0.1"]].concat(),
+        fasta.as_bytes(),
+    );
+    assert!(output.status.success());
+    let reads = records(&output.stdout);
+    assert!(!reads.is_empty());
+    for (_, read) in &reads {
+        assert!(read.bytes().filter(|&x| x == b'N').count() <= 5, "{}", read);
+    }
+    assert!(stat(&output.stderr, "discarded") > 0);
+}
+
+#[test]
+fn fastq_records_are_four_lines() {
+    let fasta = format!(">r\n{}\n", random_seq(1000, 3));
+    for length in ["10", "150", "500"] {
+        let args = ["-f", "fastq", "-l", length, "-d", "2", "--seed", "1"];
+        let fastq = String::from_utf8(stdout(SONICAT, &args, fasta.as_bytes())).unwrap();
+        let lines: Vec<&str> = fastq.lines().collect();
+        assert!(!lines.is_empty());
+        assert_eq!(lines.len() % 4, 0);
+        for record in lines.chunks(4) {
+            assert!(record[0].starts_with('@'));
+            assert_eq!(record[1].len(), length.parse::<usize>().unwrap());
+            assert_eq!(record[2], "+");
+            assert_eq!(record[3].len(), record[1].len());
+        }
+    }
+}
+
+#[test]
+fn records_sonicate_alike_whatever_comes_before() {
+    let b = format!(">b\n{}\n", random_seq(500, 2));
+    let both = format!(">a\n{}\n{}", random_seq(500, 1), b);
+    let args = ["-l", "50", "-d", "3", "-e", "0.05", "--seed", "7"];
+    // read names number the reads of all records
+    let seqs = |fasta: &str| -> Vec<String> {
+        records(&stdout(SONICAT, &args, fasta.as_bytes()))
+            .into_iter()
+            .map(|x| x.1)
+            .collect()
+    };
+
+    let alone = seqs(&b);
+    let after = seqs(&both);
+    assert!(!alone.is_empty());
+    assert_eq!(alone[..], after[after.len() - alone.len()..]);
+}
+
+#[test]
+fn quiet_silences_stats_and_warnings_but_not_errors() {
+    // a duplicate id and a record too short for the reads warn
+    let fasta = format!(">r\n{}\n>r\nACGT\n", random_seq(100, 1));
+    let args = ["-l", "50", "--seed", "1", "--stats", "--timing", "-v"];
+
+    let output = run(SONICAT, &args, fasta.as_bytes());
+    assert!(!output.stderr.is_empty());
+    let output = run(
+        SONICAT,
+        &[&args[..], &["--quiet"]].concat(),
+        fasta.as_bytes(),
+    );
+    assert!(output.status.success());
+    assert!(!output.stdout.is_empty());
+    assert!(
+        output.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // over module directives of RUST_LOG too
+    let env = [("RUST_LOG", "sonicat=debug")];
+    let output = common::run_env(
+        SONICAT,
+        &[&args[..], &["--quiet"]].concat(),
+        &env,
+        fasta.as_bytes(),
+    );
+    assert!(output.status.success());
+    assert!(
+        output.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = run(SONICAT, &["--quiet", "-l", "0"], fasta.as_bytes());
+    assert_eq!(output.status.code(), Some(64));
+    assert!(!output.stderr.is_empty());
+}
+
+#[test]
+fn dash_is_stdin_and_stdout() {
+    let fasta = format!(">r\n{}\n", random_seq(100, 1));
+    let args = ["-l", "50", "--seed", "1"];
+    let dashed = [&args[..], &["-i", "-", "-o", "-"]].concat();
+    let reads = stdout(SONICAT, &dashed, fasta.as_bytes());
+    assert!(!reads.is_empty());
+    assert_eq!(reads, stdout(SONICAT, &args, fasta.as_bytes()));
+}
+
+/// First value of the `name` line of the --stats of a run.
+fn stat(stderr: &[u8], name: &str) -> u64 {
+    String::from_utf8_lossy(stderr)
+        .lines()
+        .find_map(|x| x.strip_prefix(name)?.strip_prefix('\t'))
+        .unwrap()
+        .split('\t')
+        .next()
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+/// Lines of a --truth BED: contig, start, end, name, strand and annotations.
+type Truth = (String, usize, usize, String, String, Vec<String>);
+
+fn truth(path: &Path) -> Vec<Truth> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|x| {
+            let f: Vec<&str> = x.split('\t').collect();
+            assert_eq!(f.len(), 7, "{}", x);
+            assert_eq!(f[4], "0");
+            let marks = match f[6] {
+                "." => Vec::new(),
+                x => x.split(';').map(String::from).collect(),
+            };
+            (
+                f[0].to_string(),
+                f[1].parse().unwrap(),
+                f[2].parse().unwrap(),
+                f[3].to_string(),
+                f[5].to_string(),
+                marks,
+            )
+        })
+        .collect()
+}
+
+/// Value of the annotation `key` of a line of a --truth BED.
+fn mark<'a>(line: &'a Truth, key: &str) -> Option<&'a str> {
+    line.5
+        .iter()
+        .find_map(|x| x.strip_prefix(key)?.strip_prefix('='))
+}
+
+#[test]
+fn chimeras_are_flagged_at_the_chimera_rate() {
+    let fasta = format!(">r\n{}\n", random_seq(2000, 1));
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "5",
+        "--chimera-rate",
+        "0.2",
+        "--seed",
+        "1",
+        "--stats",
+    ];
+    let output = run(SONICAT, &args, fasta.as_bytes());
+    assert!(output.status.success());
+
+    let reads = records(&output.stdout);
+    let flagged = reads.iter().filter(|x| x.0.ends_with(" chimera")).count();
+    let rate = flagged as f64 / reads.len() as f64;
+    assert!((rate - 0.2).abs() < 0.02, "{}", rate);
+    assert_eq!(stat(&output.stderr, "chimeras"), flagged as u64);
+    assert_eq!(stat(&output.stderr, "reads"), reads.len() as u64);
+}
+
+#[test]
+fn read_group_and_platform_tag_every_read() {
+    let fasta = format!(">r\n{}\n", random_seq(200, 1));
+    let args = [
+        "-f",
+        "fastq",
+        "-l",
+        "50",
+        "--seed",
+        "1",
+        "--read-group",
+        "g1",
+        "--platform",
+        "ILLUMINA",
+    ];
+    let fastq = String::from_utf8(stdout(SONICAT, &args, fasta.as_bytes())).unwrap();
+    let headers: Vec<&str> = fastq.lines().step_by(4).collect();
+    assert!(!headers.is_empty());
+    for header in headers {
+        let (_, desc) = header.split_once(' ').unwrap();
+        assert_eq!(desc, "RG:Z:g1\tPL:Z:ILLUMINA");
+    }
+}
+
+#[test]
+fn every_field_of_tagged_descriptions_is_a_sam_tag() {
+    let fasta = format!(">r\n{}\n", random_seq(2000, 1));
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "2",
+        "--seed",
+        "1",
+        "--read-group",
+        "g1",
+        "--coord-format",
+        "{contig}:{start}-{end}",
+        "--error-rate-reverse",
+        "0.01",
+        "--chimera-rate",
+        "0.2",
+        "--dup-rate",
+        "0.2",
+        "--soft-clip",
+        "3",
+        "--pool",
+        "s1:1",
+        "--annotate-difficulty",
+    ];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+    assert!(reads.iter().any(|x| x.0.contains("chimera")));
+    assert!(reads.iter().any(|x| x.0.contains("duplicate_of=")));
+    for (header, _) in &reads {
+        let (_, desc) = header.split_once(' ').unwrap();
+        // the tags set, then the annotations in a comment
+        assert!(desc.starts_with("RG:Z:g1\tCO:Z:"), "{}", header);
+        assert!(desc.contains("strand="), "{}", header);
+        // TAG:TYPE:VALUE with a letter and an alphanumeric for the tag
+        for tag in desc.split('\t') {
+            let tag = tag.as_bytes();
+            assert!(
+                tag.len() >= 5
+                    && tag[0].is_ascii_alphabetic()
+                    && tag[1].is_ascii_alphanumeric()
+                    && tag[2] == b':'
+                    && b"AifZHB".contains(&tag[3])
+                    && tag[4] == b':',
+                "{}",
+                header
+            );
+        }
+    }
+}
+
+#[cfg(feature = "bam")]
+#[test]
+fn ubam_holds_the_reads_of_fastq() {
+    use noodles_sam::alignment::record::Flags;
+
+    let dir = common::dir("ubam");
+    let fasta = format!(">r\n{}\n", random_seq(300, 1));
+    let bam = dir.join("reads.bam");
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "3",
+        "--quality-dist",
+        "uniform",
+        "--seed",
+        "1",
+    ];
+    let ubam = [&args[..], &["-f", "ubam", "-o", bam.to_str().unwrap()]].concat();
+    stdout(SONICAT, &ubam, fasta.as_bytes());
+    let fastq = String::from_utf8(stdout(
+        SONICAT,
+        &[&args[..], &["-f", "fastq"]].concat(),
+        fasta.as_bytes(),
+    ))
+    .unwrap();
+    let fastq: Vec<&str> = fastq.lines().collect();
+
+    let mut reader = noodles_bam::io::reader::Builder
+        .build_from_path(&bam)
+        .unwrap();
+    let header = reader.read_header().unwrap();
+    let records: Vec<_> = reader.record_bufs(&header).map(|x| x.unwrap()).collect();
+    assert_eq!(records.len(), fastq.len() / 4);
+    for (record, read) in records.iter().zip(fastq.chunks(4)) {
+        assert_eq!(format!("@{}", record.name().unwrap()), read[0]);
+        assert_eq!(record.flags(), Flags::UNMAPPED);
+        assert_eq!(record.sequence().as_ref(), read[1].as_bytes());
+        let qual: Vec<u8> = read[3].bytes().map(|x| x - 33).collect();
+        assert_eq!(record.quality_scores().as_ref(), qual);
+    }
+}
+
+#[test]
+fn ubam_takes_no_further_compression() {
+    let fasta = format!(">r\n{}\n", random_seq(300, 1));
+    for compress in ["gzip", "zstd", "bzip2"] {
+        let args = ["-f", "ubam", "--compress", compress];
+        let output = run(SONICAT, &args, fasta.as_bytes());
+        assert_eq!(output.status.code(), Some(64), "{}", compress);
+        assert!(output.stdout.is_empty());
+    }
+    let output = run(SONICAT, &["-f", "ubam.gz"], fasta.as_bytes());
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[test]
+fn reads_longer_than_a_record_warn() {
+    let fasta = format!(
+        ">short\n{}\n>long\n{}\n",
+        random_seq(40, 1),
+        random_seq(200, 2)
+    );
+    let output = run(SONICAT, &["-l", "50", "--seed", "1"], fasta.as_bytes());
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("skipped short, shorter (40) than the read length"),
+        "{}",
+        stderr
+    );
+    assert!(!records(&output.stdout).is_empty());
+}
+
+#[test]
+fn name_prefix_and_suffix_wrap_every_read_name() {
+    let fasta = format!(">r\n{}\n", random_seq(200, 1));
+    let args = [
+        "-l",
+        "50",
+        "--seed",
+        "1",
+        "--name-prefix",
+        "sim1_",
+        "--name-suffix",
+        "/1",
+    ];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+    assert!(!reads.is_empty());
+    for (i, (name, _)) in reads.iter().enumerate() {
+        assert_eq!(name, &format!("sim1_seq_{}/1", i + 1));
+    }
+}
+
+#[test]
+fn config_file_sets_parameters_under_the_command_line() {
+    let dir = common::dir("sonicat-config");
+    let config = dir.join("sonicat.json");
+    fs::write(&config, r#"{"length": 30, "depth": 2, "seed": 1}"#).unwrap();
+    let config = config.to_str().unwrap();
+    let fasta = format!(">r\n{}\n", random_seq(200, 1));
+
+    let reads = records(&stdout(SONICAT, &["--config", config], fasta.as_bytes()));
+    assert!(!reads.is_empty() && reads.iter().all(|x| x.1.len() == 30));
+    let args = ["--config", config, "-l", "40"];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+    assert!(!reads.is_empty() && reads.iter().all(|x| x.1.len() == 40));
+}
+
+#[test]
+fn reads_carry_the_alleles_of_the_vcf() {
+    let dir = common::dir("sonicat-vcf");
+    let seq = random_seq(300, 1);
+    let (reference, alt) = (
+        &seq[150..151],
+        if &seq[150..151] == "A" { "C" } else { "A" },
+    );
+    let vcf = dir.join("variants.vcf");
+    fs::write(
+        &vcf,
+        format!(
+            "#CHROM\tPOS\tID\tREF\tALT\nr\t151\t.\t{}\t{}\n",
+            reference, alt
+        ),
+    )
+    .unwrap();
+    let fasta = format!(">r\n{}\n", seq);
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "10",
+        "--seed",
+        "1",
+        "--vcf",
+        vcf.to_str().unwrap(),
+    ];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+
+    let with = |allele| format!("{}{}{}", &seq[140..150], allele, &seq[151..161]);
+    assert!(reads.iter().any(|x| x.1.contains(&with(alt))));
+    assert!(!reads.iter().any(|x| x.1.contains(&with(reference))));
+}
+
+#[test]
+fn repeats_are_annotated_harder_than_unique_sequence() {
+    let repeat = random_seq(100, 9);
+    let seq = format!(
+        "{}{}{}{}",
+        random_seq(300, 1),
+        repeat,
+        random_seq(300, 2),
+        repeat
+    );
+    let fasta = format!(">r\n{}\n", seq);
+    // sequencing errors do not change the score of the reference window
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "5",
+        "-e",
+        "0.1",
+        "--seed",
+        "1",
+        "--annotate-difficulty",
+        "--coord-format",
+        "{start}",
+    ];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+
+    let (mut unique, mut repeated) = (0, 0);
+    for (header, _) in &reads {
+        let fields: Vec<&str> = header.split(&[' ', '\t'][..]).collect();
+        let start: usize = fields[1].parse().unwrap();
+        let difficulty: f64 = fields[2]
+            .strip_prefix("difficulty=")
+            .unwrap()
+            .parse()
+            .unwrap();
+        if (300..=350).contains(&start) || start >= 700 {
+            assert_eq!(difficulty, 1.0, "{}", header);
+            repeated += 1;
+        } else if start + 50 <= 300 || (400..=650).contains(&start) {
+            assert_eq!(difficulty, 0.0, "{}", header);
+            unique += 1;
+        }
+    }
+    assert!(unique > 0 && repeated > 0);
+}
+
+#[test]
+fn only_errored_reads_differ_from_their_source() {
+    let dir = common::dir("only-errored");
+    let pairs = dir.join("pairs.tsv");
+    let fasta = format!(">r\n{}\n", random_seq(500, 1));
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "5",
+        "-e",
+        "0.005",
+        "--seed",
+        "1",
+        "--only-errored",
+        "--stats",
+        "--debug-pairs",
+        pairs.to_str().unwrap(),
+    ];
+    let output = run(SONICAT, &args, fasta.as_bytes());
+    assert!(output.status.success());
+
+    let reads = records(&output.stdout);
+    let pairs = fs::read_to_string(&pairs).unwrap();
+    let pairs: Vec<Vec<&str>> = pairs.lines().map(|x| x.split('\t').collect()).collect();
+    assert!(!reads.is_empty());
+    assert_eq!(pairs.len(), reads.len());
+    for ((name, read), pair) in reads.iter().zip(&pairs) {
+        assert_eq!(name, pair[0]);
+        assert_eq!(read, pair[2]);
+        assert_ne!(pair[1], pair[2]);
+    }
+    // about 22% of reads of 50 bases have an error
+    let suppressed = stat(&output.stderr, "suppressed");
+    assert!(suppressed > 2 * reads.len() as u64);
+
+    // without errors no read would be emitted
+    for rates in [&[][..], &["-e", "0"], &["--error-rate-forward", "0"]] {
+        let args = [&["-l", "50", "--only-errored"][..], rates].concat();
+        let output = run(SONICAT, &args, fasta.as_bytes());
+        assert_eq!(output.status.code(), Some(64), "{:?}", rates);
+        assert!(output.stdout.is_empty());
+    }
+    for rates in [
+        &["--error-rate-reverse", "0.1"][..],
+        &["--preset", "pacbio"],
+    ] {
+        let args = [&["-l", "50", "--only-errored"][..], rates].concat();
+        assert!(run(SONICAT, &args, fasta.as_bytes()).status.success());
+    }
+}
+
+#[test]
+fn max_bases_caps_the_emitted_bases() {
+    let fasta = format!(">a\n{}\n>b\n{}\n", random_seq(500, 1), random_seq(500, 2));
+    for (cap, dup) in [("1234", "0"), ("5000", "0.3")] {
+        let args = [
+            "-l",
+            "50",
+            "-d",
+            "5",
+            "--seed",
+            "1",
+            "--dup-rate",
+            dup,
+            "--max-bases",
+            cap,
+        ];
+        let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+        let bases: usize = reads.iter().map(|x| x.1.len()).sum();
+        let cap: usize = cap.parse().unwrap();
+        assert!(bases <= cap && bases > cap - 50, "{}", bases);
+    }
+}
+
+#[test]
+fn verbose_logs_more_without_changing_the_reads() {
+    let fasta = format!(">r\n{}\n", random_seq(100, 1));
+    let args = ["-l", "50", "--seed", "1"];
+    let quiet = run(SONICAT, &args, fasta.as_bytes());
+    let info = run(SONICAT, &[&args[..], &["-v"]].concat(), fasta.as_bytes());
+    let debug = run(SONICAT, &[&args[..], &["-vv"]].concat(), fasta.as_bytes());
+
+    assert!(quiet.stderr.is_empty());
+    assert!(String::from_utf8_lossy(&info.stderr).contains("INFO"));
+    assert!(String::from_utf8_lossy(&debug.stderr).contains("DEBUG"));
+    assert!(debug.stderr.len() > info.stderr.len());
+    assert_eq!(quiet.stdout, info.stdout);
+    assert_eq!(quiet.stdout, debug.stdout);
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn compress_applies_to_stdout() {
+    let fasta = format!(">r\n{}\n", random_seq(200, 1));
+    let args = ["-l", "50", "--seed", "1"];
+    let plain = stdout(SONICAT, &args, fasta.as_bytes());
+    let compressed = stdout(
+        SONICAT,
+        &[&args[..], &["--compress", "gzip"]].concat(),
+        fasta.as_bytes(),
+    );
+    assert!(compressed.starts_with(&[0x1f, 0x8b]));
+    let mut decompressed = Vec::new();
+    let mut decoder = flate2::read::GzDecoder::new(&compressed[..]);
+    std::io::Read::read_to_end(&mut decoder, &mut decompressed).unwrap();
+    assert_eq!(decompressed, plain);
+}
+
+#[test]
+fn circular_reads_wrap_around_short_records() {
+    let args = [
+        "-l",
+        "25",
+        "-d",
+        "3",
+        "--circular",
+        "--seed",
+        "1",
+        "--coord-format",
+        "{start}",
+    ];
+    let reads = records(&stdout(SONICAT, &args, b">c\nACGTACCGTT\n"));
+    assert!(!reads.is_empty());
+    let circle = "ACGTACCGTT".repeat(4);
+    for (header, read) in &reads {
+        let start: usize = header.split_once(' ').unwrap().1.parse().unwrap();
+        assert_eq!(read, &circle[start..start + 25]);
+    }
+
+    // --circular stands for --tail wrap, and for no other tail
+    let wrapped = [&args[..], &["--tail", "wrap"]].concat();
+    assert_eq!(
+        records(&stdout(SONICAT, &wrapped, b">c\nACGTACCGTT\n")),
+        reads
+    );
+    let output = run(
+        SONICAT,
+        &[&args[..], &["--tail", "drop"]].concat(),
+        b">c\nACGTACCGTT\n",
+    );
+    assert_eq!(output.status.code(), Some(64));
+
+    let dir = common::dir("sonicat-circular");
+    let config = dir.join("sonicat.json");
+    fs::write(&config, r#"{"circular": true, "tail": "shrink"}"#).unwrap();
+    let output = run(
+        SONICAT,
+        &["--config", config.to_str().unwrap()],
+        b">c\nACGTACCGTT\n",
+    );
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[test]
+fn split_by_contig_writes_a_file_per_record() {
+    let dir = common::dir("split");
+    let out = dir.join("reads");
+    let fasta = format!(
+        ">chr1\n{}\n>../x/y\n{}\n>.._x_y\n{}\n",
+        random_seq(200, 1),
+        random_seq(200, 2),
+        random_seq(200, 3)
+    );
+    let args = [
+        "-l",
+        "50",
+        "--seed",
+        "1",
+        "--coord-format",
+        "{contig}",
+        "--split-by-contig",
+        out.to_str().unwrap(),
+    ];
+    assert!(stdout(SONICAT, &args, fasta.as_bytes()).is_empty());
+
+    let mut files: Vec<String> = fs::read_dir(&out)
+        .unwrap()
+        .map(|x| x.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    assert_eq!(files, ["_.._x_y-2.fa", "_.._x_y.fa", "chr1.fa"]);
+    for (file, contig) in [
+        ("chr1.fa", "chr1"),
+        ("_.._x_y.fa", "../x/y"),
+        ("_.._x_y-2.fa", ".._x_y"),
+    ] {
+        let reads = records(&fs::read(out.join(file)).unwrap());
+        assert!(!reads.is_empty());
+        assert!(
+            reads.iter().all(|x| x.0.ends_with(&format!(" {}", contig))),
+            "{}",
+            file
+        );
+    }
+}
+
+#[test]
+fn duplicate_ids_warn_and_keep_read_names_unique() {
+    let fasta = format!(">r\n{}\n>r\n{}\n", random_seq(100, 1), random_seq(100, 2));
+    let args = ["-l", "50", "--seed", "1"];
+    let output = run(SONICAT, &args, fasta.as_bytes());
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("duplicate record id r"));
+    let reads = records(&output.stdout);
+    let mut names: Vec<&str> = reads.iter().map(|x| x.0.as_str()).collect();
+    let n = names.len();
+    names.sort();
+    names.dedup();
+    assert_eq!(names.len(), n);
+
+    let output = run(
+        SONICAT,
+        &[&args[..], &["--strict"]].concat(),
+        fasta.as_bytes(),
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn debug_pairs_hold_the_reference_window_of_every_read() {
+    let dir = common::dir("debug-pairs");
+    let pairs = dir.join("pairs.tsv");
+    let seq = random_seq(300, 1);
+    let fasta = format!(">r\n{}\n", seq);
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "5",
+        "-e",
+        "0.05",
+        "--error-rate-reverse",
+        "0.05",
+        "--chimera-rate",
+        "0.3",
+        "--seed",
+        "1",
+        "--coord-format",
+        "{start}",
+        "--debug-pairs",
+        pairs.to_str().unwrap(),
+    ];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+
+    let pairs = fs::read_to_string(&pairs).unwrap();
+    let lines: Vec<&str> = pairs.lines().collect();
+    assert_eq!(lines.len(), reads.len());
+    let mut errored = 0;
+    let mut chimeras = 0;
+    let mut reverse = 0;
+    for ((header, read), line) in reads.iter().zip(lines) {
+        let name = header.split(' ').next().unwrap();
+        let start: usize = header.rsplit(' ').next().unwrap().parse().unwrap();
+        let fields: Vec<&str> = line.split('\t').collect();
+        // the forward window, whatever the chimera and strand of the read
+        assert_eq!(fields, [name, &seq[start..start + 50], read.as_str()]);
+        errored += usize::from(fields[1] != fields[2]);
+        chimeras += usize::from(header.contains(" chimera"));
+        reverse += usize::from(header.contains(" strand=-"));
+    }
+    assert!(errored > 0);
+    assert!(chimeras > 0);
+    assert!(reverse > 0);
+}
+
+#[test]
+fn soft_clipped_ends_diverge_from_the_reference() {
+    let seq = random_seq(300, 1);
+    let fasta = format!(">r\n{}\n", seq);
+    let args = [
+        "-l",
+        "50",
+        "--soft-clip",
+        "5",
+        "--seed",
+        "1",
+        "--coord-format",
+        "{start}",
+    ];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+    assert!(!reads.is_empty());
+    for (header, read) in &reads {
+        let fields: Vec<&str> = header.split(&[' ', '\t'][..]).collect();
+        let start: usize = fields[1].parse().unwrap();
+        assert_eq!(fields[2], "clip=5");
+        let reference = &seq.as_bytes()[start..start + 50];
+        let read = read.as_bytes();
+        for i in 0..50 {
+            assert_eq!(read[i] == reference[i], (5..45).contains(&i), "{}", header);
+        }
+    }
+}
+
+#[test]
+fn replicates_differ_and_reproduce() {
+    let fasta = format!(">r\n{}\n", random_seq(300, 1));
+    let replicate = |n: &str| {
+        let args = ["-l", "50", "-e", "0.05", "--seed", "1", "--replicate", n];
+        stdout(SONICAT, &args, fasta.as_bytes())
+    };
+    assert_eq!(replicate("1"), replicate("1"));
+    assert_ne!(replicate("1"), replicate("2"));
+    assert_ne!(replicate("0"), replicate("1"));
+}
+
+#[test]
+fn skip_masked_draws_no_read_over_a_masked_block() {
+    let masked = random_seq(100, 3).to_lowercase();
+    let fasta = format!(
+        ">r\n{}{}{}\n",
+        random_seq(300, 1),
+        masked,
+        random_seq(300, 2)
+    );
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "3",
+        "--seed",
+        "1",
+        "--coord-format",
+        "{start}",
+    ];
+    let starts = |args: &[&str]| -> Vec<usize> {
+        let reads = records(&stdout(SONICAT, args, fasta.as_bytes()));
+        reads
+            .iter()
+            .map(|x| x.0.split_once(' ').unwrap().1.parse().unwrap())
+            .collect()
+    };
+    let overlapping = |x: &usize| (251..400).contains(x);
+
+    assert!(starts(&args).iter().any(overlapping));
+    let starts = starts(&[&args[..], &["--skip-masked"]].concat());
+    assert!(!starts.is_empty());
+    assert!(!starts.iter().any(overlapping));
+}
+
+#[test]
+fn list_records_prints_ids_and_lengths() {
+    let fasta = b">a desc\nACGT\nAC\n>b\n\n>c\nNNN\n";
+    assert_eq!(
+        stdout(SONICAT, &["--list-records"], fasta),
+        b"a\t6\nb\t0\nc\t3\n"
+    );
+}
+
+/// `parameters` of the --stats-json of a run of sonicat with `args`.
+fn parameters(name: &str, args: &[&str], fasta: &[u8]) -> serde_json::Value {
+    let json = common::dir(name).join("stats.json");
+    stdout(
+        SONICAT,
+        &[args, &["--stats-json", json.to_str().unwrap()]].concat(),
+        fasta,
+    );
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(json).unwrap()).unwrap();
+    stats["parameters"].clone()
+}
+
+#[test]
+fn presets_set_the_length_and_error_rate() {
+    let fasta = format!(">r\n{}\n", random_seq(12_000, 1));
+    for (preset, length, error_rate) in [
+        ("illumina", 150, 0.001),
+        ("pacbio", 10_000, 0.1),
+        ("nanopore", 10_000, 0.05),
+    ] {
+        let args = ["--preset", preset, "-d", "0.001", "--seed", "1"];
+        let p = parameters("preset", &args, fasta.as_bytes());
+        assert_eq!(p["length"], length);
+        assert_eq!(p["error_rate_forward"], error_rate);
+        assert_eq!(p["error_rate_reverse"], error_rate);
+    }
+
+    // explicit options win over the preset
+    let args = [
+        "--preset", "pacbio", "-l", "500", "-e", "0.02", "-d", "0.001",
+    ];
+    let p = parameters("preset", &args, fasta.as_bytes());
+    assert_eq!(p["length"], 500);
+    assert_eq!(p["error_rate_forward"], 0.02);
+}
+
+#[test]
+fn existing_outputs_are_only_replaced_with_force() {
+    let dir = common::dir("sonicat-overwrite");
+    let out = dir.join("reads.fa");
+    fs::write(&out, "kept\n").unwrap();
+    let fasta = format!(">r\n{}\n", random_seq(500, 1));
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "2",
+        "--seed",
+        "1",
+        "-o",
+        out.to_str().unwrap(),
+    ];
+
+    let output = run(SONICAT, &args, fasta.as_bytes());
+    assert_eq!(output.status.code(), Some(74));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("refusing to overwrite"));
+    assert_eq!(fs::read_to_string(&out).unwrap(), "kept\n");
+
+    for flag in ["--force", "--overwrite"] {
+        stdout(SONICAT, &[&args[..], &[flag]].concat(), fasta.as_bytes());
+        assert!(!records(&fs::read(&out).unwrap()).is_empty());
+    }
+    // stdout is never refused
+    stdout(
+        SONICAT,
+        &[&args[..6], &["-o", "-"]].concat(),
+        fasta.as_bytes(),
+    );
+}
+
+#[test]
+fn reverse_reads_have_the_reverse_error_rate() {
+    let seq = random_seq(5000, 1);
+    let fasta = format!(">r\n{}\n", seq);
+    let args = [
+        "-l",
+        "100",
+        "-d",
+        "20",
+        "--seed",
+        "1",
+        "--error-rate-forward",
+        "0.01",
+        "--error-rate-reverse",
+        "0.1",
+        "--coord-format",
+        "{start}",
+    ];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+
+    // mismatches and bases of the forward and reverse reads
+    let mut counts = [(0, 0); 2];
+    for (header, read) in &reads {
+        let (name, start) = header.rsplit_once(' ').unwrap();
+        let start: usize = start.parse().unwrap();
+        let reverse = name.ends_with("strand=-");
+        let window = &seq.as_bytes()[start..start + read.len()];
+        let window: Vec<u8> = if reverse {
+            window
+                .iter()
+                .rev()
+                .map(|&b| match b {
+                    b'A' => b'T',
+                    b'C' => b'G',
+                    b'G' => b'C',
+                    _ => b'A',
+                })
+                .collect()
+        } else {
+            window.to_vec()
+        };
+        let count = &mut counts[reverse as usize];
+        count.0 += read.bytes().zip(window).filter(|(a, b)| a != b).count();
+        count.1 += read.len();
+    }
+    let rate = |(errors, bases): (usize, usize)| errors as f64 / bases as f64;
+    assert!(
+        (rate(counts[0]) - 0.01).abs() < 0.004,
+        "forward {:?}",
+        counts[0]
+    );
+    assert!(
+        (rate(counts[1]) - 0.1).abs() < 0.015,
+        "reverse {:?}",
+        counts[1]
+    );
+}
+
+#[test]
+fn reads_of_the_contigs_add_up_to_the_total() {
+    let lengths = [2000, 6000, 300];
+    let fasta: String = lengths
+        .iter()
+        .enumerate()
+        .map(|(i, &n)| format!(">c{}\n{}\n", i, random_seq(n, i as u64)))
+        .collect();
+    let args = [
+        "-l",
+        "100",
+        "-d",
+        "10",
+        "--seed",
+        "1",
+        "--stats",
+        "--coord-format",
+        "{contig}",
+    ];
+    let output = run(SONICAT, &args, fasta.as_bytes());
+    assert!(output.status.success());
+
+    let reads = records(&output.stdout);
+    let mut total = 0;
+    for (i, n) in lengths.iter().enumerate() {
+        let contig = format!(" c{}", i);
+        let count = reads.iter().filter(|(x, _)| x.ends_with(&contig)).count();
+        // every start of a window draws --depth reads on average
+        let expected = 10.0 * (n - 100 + 1) as f64;
+        assert!(
+            (count as f64 / expected - 1.0).abs() < 0.05,
+            "c{}: {}",
+            i,
+            count
+        );
+        total += count;
+    }
+    assert_eq!(total, reads.len());
+    assert_eq!(stat(&output.stderr, "reads"), total as u64);
+}
+
+#[test]
+fn coordinates_of_wgsim_and_mason_headers_parse_back() {
+    let seq = random_seq(1000, 1);
+    let fasta = format!(">chr1\n{}\n", seq);
+    let args = ["-l", "60", "-d", "1", "--seed", "1"];
+
+    // wgsim: contig_start_end, 1-based and closed
+    let wgsim = [
+        &args[..],
+        &[
+            "--coord-format",
+            "{contig}_{start}_{end}",
+            "--coord-base",
+            "1",
+        ],
+    ]
+    .concat();
+    let reads = records(&stdout(SONICAT, &wgsim, fasta.as_bytes()));
+    assert!(!reads.is_empty());
+    for (header, read) in &reads {
+        let coords = header.rsplit(' ').next().unwrap();
+        let fields: Vec<&str> = coords.rsplitn(3, '_').collect();
+        let (end, start): (usize, usize) = (fields[0].parse().unwrap(), fields[1].parse().unwrap());
+        assert_eq!(fields[2], "chr1");
+        assert_eq!(&seq[start - 1..end], read);
+    }
+
+    // mason: key=value pairs, 0-based and half-open
+    let mason = "contig={contig} orig_begin={start} orig_end={end} strand={strand}";
+    let reads = records(&stdout(
+        SONICAT,
+        &[&args[..], &["--coord-format", mason]].concat(),
+        fasta.as_bytes(),
+    ));
+    assert!(!reads.is_empty());
+    for (header, read) in &reads {
+        let value = |key: &str| {
+            header
+                .split(' ')
+                .find_map(|x| x.strip_prefix(key)?.strip_prefix('='))
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(value("contig"), "chr1");
+        assert_eq!(value("strand"), "+");
+        let start: usize = value("orig_begin").parse().unwrap();
+        let end: usize = value("orig_end").parse().unwrap();
+        assert_eq!(&seq[start..end], read);
+    }
+}
+
+/// Timing lines of the stderr of a --timing run, after the header.
+fn timing(stderr: &[u8], header: &str) -> Vec<Vec<String>> {
+    let text = String::from_utf8_lossy(stderr);
+    let mut lines = text.lines().skip_while(|x| *x != header);
+    assert_eq!(lines.next(), Some(header));
+    lines
+        .map(|x| x.split('\t').map(String::from).collect())
+        .collect()
+}
+
+#[test]
+fn timing_goes_to_stderr_alone() {
+    let fasta = format!(">a\n{}\n>b\n{}\n", random_seq(2000, 1), random_seq(300, 2));
+    let args = ["-l", "50", "-d", "2", "--seed", "1"];
+    let output = run(
+        SONICAT,
+        &[&args[..], &["--timing"]].concat(),
+        fasta.as_bytes(),
+    );
+    assert!(output.status.success());
+    assert_eq!(output.stdout, stdout(SONICAT, &args, fasta.as_bytes()));
+
+    let lines = timing(&output.stderr, "record\tbases\tseconds\tbases/s");
+    let names: Vec<&str> = lines.iter().map(|x| x[0].as_str()).collect();
+    assert_eq!(names, ["a", "b", "total"]);
+    assert_eq!(lines[2][1], "2300");
+    for line in &lines {
+        assert_eq!(line.len(), 4);
+        assert!(line[2].parse::<f64>().unwrap() >= 0.0);
+        assert!(line[3].parse::<f64>().is_ok());
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn sigint_leaves_whole_records() {
+    use std::process::{Command, Stdio};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    let dir = common::dir("sonicat-sigint");
+    let input = dir.join("genome.fa");
+    let out = dir.join("reads.fq");
+    fs::write(&input, format!(">r\n{}\n", random_seq(100_000, 1))).unwrap();
+    let child = Command::new(SONICAT)
+        .args(["-l", "50", "-d", "1000", "--seed", "1", "--format", "fastq"])
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&out)
+        .env_remove("RUST_LOG")
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // interrupt the run once it is under way
+    let begin = Instant::now();
+    while fs::metadata(&out).map_or(0, |m| m.len()) == 0 {
+        assert!(begin.elapsed() < Duration::from_secs(30));
+        thread::sleep(Duration::from_millis(10));
+    }
+    let status = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("interrupted"));
+
+    let text = fs::read_to_string(&out).unwrap();
+    assert!(text.ends_with('\n'));
+    let lines: Vec<&str> = text.lines().collect();
+    assert!(!lines.is_empty());
+    assert_eq!(lines.len() % 4, 0);
+    for read in lines.chunks(4) {
+        assert!(read[0].starts_with('@'));
+        assert_eq!(read[1].len(), 50);
+        assert_eq!(read[2], "+");
+        assert_eq!(read[3].len(), 50);
+    }
+}
+
+#[test]
+fn pooled_samples_follow_their_fractions() {
+    let fasta = format!(">r\n{}\n", random_seq(5000, 1));
+    let args = [
+        "-l", "50", "-d", "4", "--seed", "1", "--pool", "a:1", "--pool", "b:3",
+    ];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+    let count = |sample: &str| {
+        reads
+            .iter()
+            .filter(|(x, _)| x.split(' ').any(|x| x == sample))
+            .count()
+    };
+    let (a, b) = (count("sample=a"), count("sample=b"));
+    assert_eq!(a + b, reads.len());
+    let fraction = a as f64 / reads.len() as f64;
+    assert!((fraction - 0.25).abs() < 0.02, "{}", fraction);
+
+    for pool in ["a", "a:x", "a:-1", "a:0"] {
+        let output = run(SONICAT, &["-l", "50", "--pool", pool], fasta.as_bytes());
+        assert_eq!(output.status.code(), Some(64), "{}", pool);
+    }
+}
+
+#[test]
+fn reads_of_a_record_come_in_order_of_their_start() {
+    let fasta = format!(">a\n{}\n>b\n{}\n", random_seq(3000, 1), random_seq(1000, 2));
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "3",
+        "--seed",
+        "1",
+        "--coord-format",
+        "{contig}:{start}",
+    ];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+    let coords: Vec<(String, usize)> = reads
+        .iter()
+        .map(|(x, _)| {
+            let (contig, start) = x.rsplit(' ').next().unwrap().split_once(':').unwrap();
+            (contig.to_string(), start.parse().unwrap())
+        })
+        .collect();
+    assert!(coords.iter().any(|x| x.0 == "b"));
+    assert!(coords.windows(2).all(|x| x[0] <= x[1]));
+}
+
+#[test]
+fn chunks_hold_the_reads_in_parts_of_the_size_set() {
+    let dir = common::dir("sonicat-chunks");
+    let fasta = format!(">a\n{}\n>b\n{}\n", random_seq(2000, 1), random_seq(500, 2));
+    let out = dir.join("out.fa");
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "1",
+        "--seed",
+        "1",
+        "--stats",
+        "--chunk-reads",
+        "1000",
+        "-o",
+    ];
+    let output = run(
+        SONICAT,
+        &[&args[..], &[out.to_str().unwrap()]].concat(),
+        fasta.as_bytes(),
+    );
+    assert!(output.status.success());
+    let total = stat(&output.stderr, "reads") as usize;
+    assert!(total > 2000 && total < 3000, "{}", total);
+
+    let mut names = std::collections::HashSet::new();
+    let mut count = 0;
+    for part in 1..=3 {
+        let reads = records(&fs::read(dir.join(format!("out.part{:03}.fa", part))).unwrap());
+        assert_eq!(reads.len(), if part < 3 { 1000 } else { total - 2000 });
+        for (name, _) in &reads {
+            assert!(names.insert(name.clone()), "{} repeated", name);
+        }
+        count += reads.len();
+    }
+    assert_eq!(count, total);
+    assert!(!dir.join("out.part004.fa").exists());
+    assert!(!out.exists());
+
+    // parts of no read
+    let empty = dir.join("empty.fa");
+    let output = run(
+        SONICAT,
+        &[&args[..8], &["0", "-o", empty.to_str().unwrap()]].concat(),
+        fasta.as_bytes(),
+    );
+    assert_eq!(output.status.code(), Some(64));
+    assert!(!dir.join("empty.part001.fa").exists());
+
+    // parts are files, not stdout
+    let output = run(SONICAT, &args[..9], fasta.as_bytes());
+    assert_eq!(output.status.code(), Some(2));
+    let output = run(SONICAT, &[&args[..], &["-"]].concat(), fasta.as_bytes());
+    assert_eq!(output.status.code(), Some(64));
+    assert!(output.stdout.is_empty());
+    assert!(!std::path::Path::new("-.part001.fa").exists());
+}
+
+#[test]
+fn validate_reports_the_problems_of_the_input() {
+    let output = run(SONICAT, &["--validate"], b">a\nACGT\n>b\nAC\n");
+    assert!(output.status.success());
+    let stats = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stats,
+        "records\t2\nbases\t6\nmin length\t2\nmax length\t4\nproblems\t0\n"
+    );
+
+    let output = run(SONICAT, &["--validate"], b">a\nACGTX\n>a\nACGT\n>c\n");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("record a contains 1 non-alphabet characters, the first 'X' at 5"));
+    assert!(stderr.contains("duplicate record id a"));
+    assert!(stderr.contains("record c is empty"));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("problems\t3\n"));
+}
+
+#[test]
+fn tagmentation_favours_the_starts_tn5_prefers() {
+    let seq = random_seq(5000, 1);
+    let weights = sonicat::tagmentation::start_weights(seq.as_bytes());
+    let fasta = format!(">r\n{}\n", seq);
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "5",
+        "--seed",
+        "1",
+        "--coord-format",
+        "{start}",
+    ];
+    // mean Tn5 weight of the starts of the reads
+    let mean_weight = |args: &[&str]| {
+        let reads = records(&stdout(SONICAT, args, fasta.as_bytes()));
+        let total: f64 = reads
+            .iter()
+            .map(|(x, _)| weights[x.rsplit(' ').next().unwrap().parse::<usize>().unwrap()])
+            .sum();
+        (total / reads.len() as f64, reads.len())
+    };
+
+    let (uniform, n) = mean_weight(&args);
+    let (biased, m) = mean_weight(&[&args[..], &["--tagmentation"]].concat());
+    assert!(biased > uniform * 1.15, "{} against {}", biased, uniform);
+    // the mean depth is kept
+    assert!(
+        (m as f64 / n as f64 - 1.0).abs() < 0.05,
+        "{} against {}",
+        m,
+        n
+    );
+}
+
+#[test]
+fn record_command_writes_the_invocation() {
+    let dir = common::dir("sonicat-record-command");
+    let out = dir.join("out.fa");
+    let fasta = format!(">r\n{}\n", random_seq(200, 1));
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "1",
+        "--seed",
+        "1",
+        "--coord-format",
+        "{contig} {start}",
+        "--record-command",
+        "-o",
+        out.to_str().unwrap(),
+    ];
+    stdout(SONICAT, &args, fasta.as_bytes());
+    let recorded = fs::read_to_string(dir.join("out.fa.cmd")).unwrap();
+    let expected = format!(
+        "# sonicat {}\n{} -l 50 -d 1 --seed 1 --coord-format '{{contig}} {{start}}' --record-command -o {}\n",
+        env!("CARGO_PKG_VERSION"),
+        SONICAT,
+        out.display()
+    );
+    assert_eq!(recorded, expected);
+}
+
+#[test]
+fn header_params_override_the_defaults_of_tagged_records() {
+    let fasta = format!(
+        ">a depth=10 length=80\n{}\n>b\n{}\n>c length=30\n{}\n",
+        random_seq(2000, 1),
+        random_seq(2000, 2),
+        random_seq(2000, 3)
+    );
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "2",
+        "--seed",
+        "1",
+        "--coord-format",
+        "{contig}",
+        "--header-params",
+    ];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+    let of = |contig: &str| -> Vec<usize> {
+        let contig = format!(" {}", contig);
+        reads
+            .iter()
+            .filter(|(x, _)| x.ends_with(&contig))
+            .map(|(_, x)| x.len())
+            .collect()
+    };
+    let (a, b, c) = (of("a"), of("b"), of("c"));
+    assert!(a.iter().all(|&x| x == 80));
+    assert!(b.iter().all(|&x| x == 50));
+    assert!(c.iter().all(|&x| x == 30));
+    // about depth reads per start
+    assert!(
+        (a.len() as f64 / (10.0 * 1921.0) - 1.0).abs() < 0.05,
+        "{}",
+        a.len()
+    );
+    assert!(
+        (b.len() as f64 / (2.0 * 1951.0) - 1.0).abs() < 0.1,
+        "{}",
+        b.len()
+    );
+    assert!(
+        (c.len() as f64 / (2.0 * 1971.0) - 1.0).abs() < 0.1,
+        "{}",
+        c.len()
+    );
+
+    // the tags are ignored without the flag
+    let reads = records(&stdout(SONICAT, &args[..8], fasta.as_bytes()));
+    assert!(reads.iter().all(|(_, x)| x.len() == 50));
+
+    for bad in ["depth=x", "length=-1"] {
+        let fasta = format!(">a {}\nACGT\n", bad);
+        let output = run(SONICAT, &args, fasta.as_bytes());
+        assert_eq!(output.status.code(), Some(65), "{}", bad);
+    }
+}
+
+#[test]
+fn duplicates_copy_their_original_at_the_rate_set() {
+    let fasta = format!(">r\n{}\n", random_seq(3000, 1));
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "2",
+        "--seed",
+        "1",
+        "--stats",
+        "--dup-rate",
+        "0.2",
+        "--coord-format",
+        "{start}",
+    ];
+    let output = run(SONICAT, &args, fasta.as_bytes());
+    assert!(output.status.success());
+    let reads = records(&output.stdout);
+    let coords = |header: &str| header.split(['\t', ' ']).nth(1).unwrap().to_string();
+
+    // duplicates of a duplicate name the read it copies
+    let mut duplicates = 0;
+    let mut original = &reads[0];
+    for read in &reads[1..] {
+        let (header, copy) = read;
+        match header
+            .split(' ')
+            .find_map(|x| x.strip_prefix("duplicate_of="))
+        {
+            Some(name) => {
+                assert_eq!(name, original.0.split(['\t', ' ']).next().unwrap());
+                assert_eq!(copy, &original.1);
+                assert_eq!(coords(header), coords(&original.0));
+                duplicates += 1;
+            }
+            None => original = read,
+        }
+    }
+    assert_eq!(stat(&output.stderr, "duplicates"), duplicates);
+    let rate = duplicates as f64 / reads.len() as f64;
+    assert!((rate - 0.2).abs() < 0.02, "{}", rate);
+}
+
+#[test]
+fn tiny_records_and_single_base_reads_are_sonicated() {
+    let fasta = b">a\nACGT\n>b\nG\n";
+    let args = [
+        "-d",
+        "20",
+        "--seed",
+        "1",
+        "--stats",
+        "--coord-format",
+        "{contig}:{start}",
+    ];
+
+    // single-base reads, every base its own window
+    let output = run(SONICAT, &[&args[..], &["-l", "1"]].concat(), fasta);
+    assert!(output.status.success());
+    let reads = records(&output.stdout);
+    for (header, read) in &reads {
+        let (contig, start) = header.rsplit(' ').next().unwrap().split_once(':').unwrap();
+        let start: usize = start.parse().unwrap();
+        let seq = if contig == "a" { "ACGT" } else { "G" };
+        assert_eq!(read, &seq[start..start + 1]);
+    }
+    assert!(reads.iter().any(|(x, _)| x.ends_with(" b:0")));
+    assert_eq!(stat(&output.stderr, "bases"), reads.len() as u64);
+
+    // reads as long as the record are the record
+    let reads = records(&stdout(SONICAT, &[&args[..], &["-l", "4"]].concat(), fasta));
+    assert!(!reads.is_empty());
+    assert!(reads
+        .iter()
+        .all(|(x, seq)| x.ends_with(" a:0") && seq == "ACGT"));
+
+    // reads longer than every record
+    let output = run(SONICAT, &[&args[..], &["-l", "5"]].concat(), fasta);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(stat(&output.stderr, "reads"), 0);
+    let reads = records(&stdout(
+        SONICAT,
+        &[&args[..], &["-l", "3", "--tail", "wrap"]].concat(),
+        b">b\nG\n",
+    ));
+    assert!(!reads.is_empty());
+    assert!(reads.iter().all(|(_, x)| x == "GGG"));
+}
+
+#[test]
+fn reads_across_joins_of_concatenated_records_are_annotated() {
+    let (a, b) = (random_seq(300, 1), random_seq(300, 2));
+    let fasta = format!(">a\n{}\n>b\n{}\n", a, b);
+    let joined = format!("{}NNNNN{}", a, b);
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "3",
+        "--seed",
+        "1",
+        "--concatenate",
+        "--spacer",
+        "5",
+        "--coord-format",
+        "{start}",
+    ];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+
+    let mut crossing = 0;
+    for (header, read) in &reads {
+        let fields: Vec<&str> = header.split(['\t', ' ']).collect();
+        let start: usize = fields[1].parse().unwrap();
+        assert_eq!(read, &joined[start..start + 50]);
+        let contigs = fields.iter().find_map(|x| x.strip_prefix("contigs="));
+        let expected = match (start < 300, start + 50 > 305) {
+            (true, true) => Some("a,b"),
+            (true, false) if start + 50 > 300 => Some("a"),
+            (false, true) if start < 305 => Some("b"),
+            _ => None,
+        };
+        assert_eq!(contigs, expected, "{}", header);
+        crossing += usize::from(expected == Some("a,b"));
+    }
+    assert!(crossing > 0);
+}
+
+#[test]
+fn reads_are_cut_short_only_at_record_ends() {
+    let fasta = format!(">r\n{}\n", random_seq(1000, 1));
+    let args = [
+        "-l",
+        "80",
+        "-d",
+        "3",
+        "--seed",
+        "1",
+        "--coord-format",
+        "{start}",
+    ];
+    let lengths = |tail: &str| -> Vec<(usize, usize)> {
+        records(&stdout(
+            SONICAT,
+            &[&args[..], &["--tail", tail]].concat(),
+            fasta.as_bytes(),
+        ))
+        .iter()
+        .map(|(x, seq)| (x.rsplit(' ').next().unwrap().parse().unwrap(), seq.len()))
+        .collect()
+    };
+    assert!(lengths("drop").iter().all(|&(_, n)| n == 80));
+    let shrunk = lengths("shrink");
+    assert!(shrunk.iter().any(|&(_, n)| n < 80));
+    for (start, n) in shrunk {
+        assert_eq!(n, 80.min(1000 - start));
+    }
+}
+
+#[test]
+fn error_log_reconstructs_the_differences_from_the_reference() {
+    let dir = common::dir("sonicat-error-log");
+    let log = dir.join("errors.tsv");
+    let seq = random_seq(2000, 1);
+    let fasta = format!(">r\n{}\n", seq);
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "2",
+        "--seed",
+        "1",
+        "-e",
+        "0.05",
+        "--coord-format",
+        "{start}",
+        "--error-log",
+        log.to_str().unwrap(),
+    ];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+
+    let mut expected = Vec::new();
+    for (header, read) in &reads {
+        let (name, start) = header.split_once(' ').unwrap();
+        let start: usize = start.parse().unwrap();
+        for (i, (a, b)) in seq[start..start + 50].chars().zip(read.chars()).enumerate() {
+            if a != b {
+                expected.push(format!("{}\t{}\t{}\t{}", name, i, a, b));
+            }
+        }
+    }
+    let logged: Vec<String> = fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .map(String::from)
+        .collect();
+    assert!(!logged.is_empty());
+    assert_eq!(logged, expected);
+}
+
+#[test]
+fn stats_json_holds_the_metrics_of_the_run() {
+    let dir = common::dir("sonicat-stats-json");
+    let json = dir.join("stats.json");
+    let fasta = format!(">r\n{}\n", random_seq(2000, 1));
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "2",
+        "--seed",
+        "7",
+        "--stats",
+        "--dup-rate",
+        "0.1",
+        "--stats-json",
+        json.to_str().unwrap(),
+    ];
+    let output = run(SONICAT, &args, fasta.as_bytes());
+    assert!(output.status.success());
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&json).unwrap()).unwrap();
+
+    assert_eq!(stats["program"], "sonicat");
+    assert_eq!(stats["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(stats["command"], format!("{} {}", SONICAT, args.join(" ")));
+    assert!(stats["seconds"].as_f64().unwrap() >= 0.0);
+
+    let counts = &stats["counts"];
+    let reads = records(&output.stdout);
+    assert_eq!(counts["reads"], reads.len() as u64);
+    assert_eq!(counts["bases"], 50 * reads.len() as u64);
+    assert_eq!(counts["input_bases"], 2000);
+    for name in ["reads", "bases", "duplicates"] {
+        assert_eq!(counts[name], stat(&output.stderr, name), "{}", name);
+    }
+    let depth = stats["rates"]["depth"].as_f64().unwrap();
+    assert!((depth - 50.0 * reads.len() as f64 / 2000.0).abs() < 1e-9);
+
+    let parameters = &stats["parameters"];
+    assert_eq!(parameters["length"], 50);
+    assert_eq!(parameters["depth"], 2.0);
+    assert_eq!(parameters["seed"], 7);
+    assert_eq!(parameters["dup_rate"], 0.1);
+}
+
+#[test]
+fn input_list_reads_every_listed_input() {
+    let dir = common::dir("sonicat-input-list");
+    let (a, b, list) = (dir.join("a.fa"), dir.join("b.fa"), dir.join("inputs.txt"));
+    fs::write(&a, format!(">a\n{}\n", random_seq(300, 1))).unwrap();
+    fs::write(&b, format!(">b\n{}\n", random_seq(300, 2))).unwrap();
+    fs::write(
+        &list,
+        format!("{}\n# skipped\n{}\n", a.display(), b.display()),
+    )
+    .unwrap();
+
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "2",
+        "--seed",
+        "1",
+        "--coord-format",
+        "{contig}",
+        "--input-list",
+    ];
+    let reads = records(&stdout(
+        SONICAT,
+        &[&args[..], &[list.to_str().unwrap()]].concat(),
+        b"",
+    ));
+    let contigs: Vec<&str> = reads
+        .iter()
+        .map(|(x, _)| x.rsplit(' ').next().unwrap())
+        .collect();
+    let first_b = contigs.iter().position(|&x| x == "b").unwrap();
+    assert!(first_b > 0);
+    assert!(contigs[..first_b].iter().all(|&x| x == "a"));
+    assert!(contigs[first_b..].iter().all(|&x| x == "b"));
+}
+
+#[test]
+fn qualities_are_clamped_to_the_bounds_set() {
+    let fasta = format!(">r\n{}\n", random_seq(1000, 1));
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "2",
+        "--seed",
+        "1",
+        "--format",
+        "fastq",
+        "--quality-dist",
+        "normal",
+        "--quality-sd",
+        "20",
+    ];
+    // Phred scores of the quality lines
+    let scores = |extra: &[&str]| -> Vec<u8> {
+        let fastq = stdout(SONICAT, &[&args[..], extra].concat(), fasta.as_bytes());
+        String::from_utf8(fastq)
+            .unwrap()
+            .lines()
+            .skip(3)
+            .step_by(4)
+            .flat_map(|x| x.bytes().map(|q| q - 33).collect::<Vec<u8>>())
+            .collect()
+    };
+
+    let unclamped = scores(&[]);
+    assert!(unclamped.iter().any(|&q| q < 20) && unclamped.iter().any(|&q| q > 35));
+    assert!(unclamped.iter().all(|&q| q <= 93));
+    let clamped = scores(&["--quality-min", "20", "--quality-max", "35"]);
+    assert_eq!(clamped.len(), unclamped.len());
+    assert!(clamped.iter().all(|&q| (20..=35).contains(&q)));
+
+    let output = run(
+        SONICAT,
+        &[&args[..], &["--quality-min", "40", "--quality-max", "30"]].concat(),
+        fasta.as_bytes(),
+    );
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[cfg(feature = "bgzf")]
+#[test]
+fn bgzf_output_is_made_of_indexable_blocks() {
+    use std::io::Read;
+
+    // the empty block htslib ends every bgzf file with
+    const EOF_BLOCK: [u8; 28] = [
+        0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff, 0x06, 0, b'B', b'C', 0x02, 0, 0x1b, 0, 0x03,
+        0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    let fasta = format!(">r\n{}\n", random_seq(20_000, 1));
+    let args = ["-l", "100", "-d", "5", "--seed", "1", "--format", "fastq"];
+    let plain = stdout(SONICAT, &args, fasta.as_bytes());
+    let bgzf = stdout(
+        SONICAT,
+        &[&args[..], &["--compress", "bgzf"]].concat(),
+        fasta.as_bytes(),
+    );
+
+    // every block is a gzip member with its size in the BC extra field
+    let mut blocks = 0;
+    let mut rest = &bgzf[..];
+    while !rest.is_empty() {
+        assert_eq!(&rest[..4], [0x1f, 0x8b, 0x08, 0x04]);
+        assert_eq!(&rest[10..16], [6, 0, b'B', b'C', 2, 0]);
+        let size = u16::from_le_bytes([rest[16], rest[17]]) as usize + 1;
+        assert!(size <= 1 << 16);
+        rest = &rest[size..];
+        blocks += 1;
+    }
+    assert!(blocks > 2);
+    assert!(bgzf.ends_with(&EOF_BLOCK));
+
+    let mut decompressed = Vec::new();
+    noodles_bgzf::io::Reader::new(&bgzf[..])
+        .read_to_end(&mut decompressed)
+        .unwrap();
+    assert_eq!(decompressed, plain);
+}
+
+#[test]
+fn resolve_ambiguous_draws_the_bases_each_code_stands_for() {
+    let seq = format!(
+        "{}{}{}{}",
+        random_seq(200, 1),
+        "R".repeat(100),
+        "Y".repeat(100),
+        random_seq(200, 2)
+    );
+    let fasta = format!(">r\n{}\n", seq);
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "2",
+        "--seed",
+        "1",
+        "--coord-format",
+        "{start}",
+    ];
+
+    let reads = records(&stdout(
+        SONICAT,
+        &[&args[..], &["--resolve-ambiguous"]].concat(),
+        fasta.as_bytes(),
+    ));
+    let mut drawn = std::collections::HashSet::new();
+    for (header, read) in &reads {
+        let start: usize = header.rsplit(' ').next().unwrap().parse().unwrap();
+        for (r, x) in seq[start..start + 50].chars().zip(read.chars()) {
+            match r {
+                'R' => assert!("AG".contains(x), "{} for R", x),
+                'Y' => assert!("CT".contains(x), "{} for Y", x),
+                _ => assert_eq!(x, r),
+            }
+            if "RY".contains(r) {
+                drawn.insert(x);
+            }
+        }
+    }
+    assert_eq!(drawn.len(), 4);
+
+    // the codes are kept without the flag
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+    for (header, read) in &reads {
+        let start: usize = header.rsplit(' ').next().unwrap().parse().unwrap();
+        assert_eq!(read, &seq[start..start + 50]);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn fifo_outputs_stream_to_their_reader() {
+    use std::io::Read;
+    use std::process::{Command, Stdio};
+
+    let dir = common::dir("sonicat-fifo");
+    let fifo = dir.join("reads.fa");
+    assert!(Command::new("mkfifo")
+        .arg(&fifo)
+        .status()
+        .unwrap()
+        .success());
+    let fasta = format!(">r\n{}\n", random_seq(5000, 1));
+    let args = ["-l", "50", "-d", "20", "--seed", "1"];
+    let spawn = || {
+        let mut child = Command::new(SONICAT)
+            .args(args)
+            .arg("-o")
+            .arg(&fifo)
+            .env_remove("RUST_LOG")
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        let input = fasta.clone();
+        std::thread::spawn(move || std::io::Write::write_all(&mut stdin, input.as_bytes()));
+        child
+    };
+
+    // an existing FIFO is written to, without --force
+    let child = spawn();
+    let mut streamed = Vec::new();
+    fs::File::open(&fifo)
+        .unwrap()
+        .read_to_end(&mut streamed)
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(streamed, stdout(SONICAT, &args, fasta.as_bytes()));
+
+    // a reader closing the FIFO early stops the run quietly
+    let child = spawn();
+    let mut head = [0; 100];
+    fs::File::open(&fifo)
+        .unwrap()
+        .read_exact(&mut head)
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(141));
+    assert!(
+        output.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn recorded_coordinates_reproduce_under_a_seed() {
+    let fasta = format!(">r\n{}\n", random_seq(3000, 1));
+    let coords = |seed: &str| -> Vec<(usize, usize)> {
+        let args = [
+            "-l",
+            "80",
+            "-d",
+            "0.5",
+            "--seed",
+            seed,
+            "--coord-format",
+            "{start}-{end}",
+        ];
+        records(&stdout(SONICAT, &args, fasta.as_bytes()))
+            .iter()
+            .map(|(x, read)| {
+                let (start, end) = x.rsplit(' ').next().unwrap().split_once('-').unwrap();
+                let (start, end) = (start.parse().unwrap(), end.parse().unwrap());
+                assert_eq!(end - start, read.len());
+                (start, end)
+            })
+            .collect()
+    };
+    let first = coords("3");
+    assert!(!first.is_empty());
+    assert_eq!(coords("3"), first);
+    assert_ne!(coords("4"), first);
+}
+
+#[test]
+fn manifest_lists_every_file_written() {
+    let dir = common::dir("sonicat-manifest");
+    let (split, manifest, log) = (
+        dir.join("split"),
+        dir.join("manifest.json"),
+        dir.join("errors.tsv"),
+    );
+    let fasta = format!(">a\n{}\n>b\n{}\n", random_seq(1000, 1), random_seq(500, 2));
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "1",
+        "--seed",
+        "1",
+        "-e",
+        "0.01",
+        "--record-command",
+        "--split-by-contig",
+        split.to_str().unwrap(),
+        "--error-log",
+        log.to_str().unwrap(),
+        "--manifest",
+        manifest.to_str().unwrap(),
+    ];
+    stdout(SONICAT, &args, fasta.as_bytes());
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&fs::read(&manifest).unwrap()).unwrap();
+
+    let mut listed = Vec::new();
+    let mut total = 0;
+    for file in manifest["files"].as_array().unwrap() {
+        let path = std::path::PathBuf::from(file["path"].as_str().unwrap());
+        if file["kind"] == "reads" {
+            let reads = file["reads"].as_u64().unwrap();
+            assert_eq!(reads, records(&fs::read(&path).unwrap()).len() as u64);
+            total += reads;
+        }
+        listed.push(path);
+    }
+    assert_eq!(manifest["reads"], total);
+    listed.sort();
+
+    let mut written: Vec<std::path::PathBuf> = fs::read_dir(&split)
+        .unwrap()
+        .map(|x| x.unwrap().path())
+        .chain([log])
+        .collect();
+    written.sort();
+    assert_eq!(listed, written);
+}
+
+#[test]
+fn coordinates_index_the_read_under_each_base() {
+    let seq = random_seq(1000, 1);
+    let fasta = format!(">r\n{}\n", seq);
+    for base in ["0", "1"] {
+        let args = [
+            "-l",
+            "60",
+            "-d",
+            "1",
+            "--seed",
+            "1",
+            "--coord-format",
+            "{start} {end}",
+            "--coord-base",
+            base,
+        ];
+        let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+        assert!(!reads.is_empty());
+        for (header, read) in &reads {
+            let fields: Vec<usize> = header
+                .split(' ')
+                .skip(1)
+                .map(|x| x.parse().unwrap())
+                .collect();
+            let (start, end) = (fields[0], fields[1]);
+            // half-open from 0, or closed from 1
+            let window = if base == "0" {
+                &seq[start..end]
+            } else {
+                &seq[start - 1..end]
+            };
+            assert_eq!(window, read);
+        }
+    }
+    let output = run(
+        SONICAT,
+        &["-l", "60", "--coord-base", "2"],
+        fasta.as_bytes(),
+    );
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn sorted_reads_stay_in_order_when_redrawn() {
+    let seq = format!(
+        "{}{}{}",
+        random_seq(1500, 1),
+        "N".repeat(500),
+        random_seq(1500, 2)
+    );
+    let fasta = format!(">a\n{}\n>b\n{}\n", seq, random_seq(1000, 3));
+    let coords = |sorted: bool| {
+        let mut args = vec![
+            "-l",
+            "50",
+            "-d",
+            "3",
+            "--seed",
+            "1",
+            "--max-n",
+            "0.1",
+            "--coord-format",
+            "{contig}:{start}",
+        ];
+        if sorted {
+            args.push("--sorted");
+        }
+        records(&stdout(SONICAT, &args, fasta.as_bytes()))
+            .iter()
+            .map(|(x, _)| {
+                let (contig, start) = x.rsplit(' ').next().unwrap().split_once(':').unwrap();
+                (contig.to_string(), start.parse::<usize>().unwrap())
+            })
+            .collect::<Vec<_>>()
+    };
+    let unsorted = coords(false);
+    assert!(!unsorted.windows(2).all(|x| x[0] <= x[1]));
+    let sorted = coords(true);
+    assert!(sorted.iter().any(|x| x.0 == "b"));
+    assert!(sorted.windows(2).all(|x| x[0] <= x[1]));
+    let mut expected = unsorted;
+    expected.sort();
+    assert_eq!(sorted, expected);
+}
+
+#[test]
+fn stats_report_the_reads_expected() {
+    let fasta = format!(">a\n{}\n>b\n{}\n", random_seq(3000, 1), random_seq(1000, 2));
+    for (step, tail, expected) in [
+        ("1", "drop", 2.0 * (2951.0 + 951.0)),
+        ("3", "drop", 2.0 * (984.0 + 317.0)),
+        ("3", "wrap", 2.0 * (1000.0 + 334.0)),
+    ] {
+        let args = [
+            "-l", "50", "-d", "2", "--step", step, "--tail", tail, "--seed", "1", "--stats",
+        ];
+        let output = run(SONICAT, &args, fasta.as_bytes());
+        assert!(output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reported: f64 = stderr
+            .lines()
+            .find_map(|x| x.strip_prefix("expected reads\t"))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(reported, expected);
+        let reads = stat(&output.stderr, "reads") as f64;
+        assert!(
+            (reads / expected - 1.0).abs() < 0.05,
+            "{} {}",
+            reads,
+            expected
+        );
+    }
+}
+
+#[test]
+fn variants_apply_to_records_before_they_are_joined() {
+    let dir = common::dir("sonicat-concatenate-vcf");
+    let (a, b) = (random_seq(300, 1), random_seq(300, 2));
+    // ten bases deleted from a, five inserted into b
+    let vcf = dir.join("variants.vcf");
+    fs::write(
+        &vcf,
+        format!(
+            "#CHROM\tPOS\tID\tREF\tALT\na\t101\t.\t{}\t{}\nb\t51\t.\t{}\t{}GGGGG\n",
+            &a[100..111],
+            &a[100..101],
+            &b[50..51],
+            &b[50..51]
+        ),
+    )
+    .unwrap();
+    let a = format!("{}{}", &a[..101], &a[111..]);
+    let b = format!("{}GGGGG{}", &b[..51], &b[51..]);
+    let joined = format!("{}NNNNN{}", a, b);
+    let fasta = format!(">a\n{}\n>b\n{}\n", random_seq(300, 1), random_seq(300, 2));
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "3",
+        "--seed",
+        "1",
+        "--concatenate",
+        "--spacer",
+        "5",
+        "--vcf",
+        vcf.to_str().unwrap(),
+        "--coord-format",
+        "{start}",
+    ];
+    let output = run(SONICAT, &args, fasta.as_bytes());
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("skipped"));
+    let reads = records(&output.stdout);
+
+    // the joins move with the indels, to 290 and 295
+    for (header, read) in &reads {
+        let fields: Vec<&str> = header.split(['\t', ' ']).collect();
+        let start: usize = fields[1].parse().unwrap();
+        assert_eq!(read, &joined[start..start + 50]);
+        let contigs = fields.iter().find_map(|x| x.strip_prefix("contigs="));
+        let expected = match (start < 290, start + 50 > 295) {
+            (true, true) => Some("a,b"),
+            (true, false) if start + 50 > 290 => Some("a"),
+            (false, true) if start < 295 => Some("b"),
+            _ => None,
+        };
+        assert_eq!(contigs, expected, "{}", header);
+    }
+    assert!(reads.iter().any(|x| x.1.contains("GGGGG")));
+}
+
+#[test]
+fn truth_holds_the_source_of_both_halves_of_chimeras() {
+    let dir = common::dir("sonicat-truth-chimeras");
+    let path = dir.join("truth.bed");
+    let seq = random_seq(2000, 1);
+    let fasta = format!(">r\n{}\n", seq);
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "2",
+        "--chimera-rate",
+        "0.2",
+        "--seed",
+        "1",
+        "--coord-base",
+        "1",
+        "--truth",
+        path.to_str().unwrap(),
+    ];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+    let lines = truth(&path);
+    assert_eq!(lines.len(), reads.len());
+
+    let mut chimeras = 0;
+    for ((name, read), line) in reads.iter().zip(&lines) {
+        assert_eq!(name.split(' ').next().unwrap(), line.3);
+        assert_eq!((line.0.as_str(), line.4.as_str()), ("r", "+"));
+        // 0-based whatever the base of the descriptions
+        let source = &seq[line.1..line.2];
+        match mark(line, "chimera") {
+            Some(other) => {
+                let (start, end) = other.strip_prefix("r:").unwrap().split_once('-').unwrap();
+                let other = &seq[start.parse().unwrap()..end.parse().unwrap()];
+                assert_eq!(read, &format!("{}{}", source, other));
+                assert!(name.ends_with(" chimera"));
+                chimeras += 1;
+            }
+            None => {
+                assert_eq!(read, source);
+                assert!(!name.contains("chimera"));
+            }
+        }
+    }
+    assert!(chimeras > 0);
+}
+
+#[test]
+fn truth_holds_the_bases_clipped() {
+    let dir = common::dir("sonicat-truth-clip");
+    let path = dir.join("truth.bed");
+    let seq = random_seq(1000, 1);
+    let fasta = format!(">r\n{}\n", seq);
+    let args = [
+        "-l",
+        "50",
+        "--soft-clip",
+        "5",
+        "--seed",
+        "1",
+        "--truth",
+        path.to_str().unwrap(),
+    ];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+    let lines = truth(&path);
+    assert_eq!(lines.len(), reads.len());
+    for ((_, read), line) in reads.iter().zip(&lines) {
+        assert_eq!(mark(line, "clip"), Some("5"));
+        assert_eq!(&read[5..45], &seq[line.1 + 5..line.2 - 5]);
+    }
+}
+
+#[test]
+fn truth_holds_the_original_of_duplicates() {
+    let dir = common::dir("sonicat-truth-duplicates");
+    let path = dir.join("truth.bed");
+    let fasta = format!(">r\n{}\n", random_seq(1000, 1));
+    let args = [
+        "-l",
+        "50",
+        "--dup-rate",
+        "0.3",
+        "--seed",
+        "1",
+        "--truth",
+        path.to_str().unwrap(),
+    ];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+    let lines = truth(&path);
+    assert_eq!(lines.len(), reads.len());
+    let sources: std::collections::HashMap<&str, (usize, usize)> =
+        lines.iter().map(|x| (x.3.as_str(), (x.1, x.2))).collect();
+    let mut duplicates = 0;
+    for ((name, _), line) in reads.iter().zip(&lines) {
+        let marked = name
+            .split(' ')
+            .find_map(|x| x.strip_prefix("duplicate_of="));
+        assert_eq!(mark(line, "duplicate_of"), marked);
+        if let Some(original) = marked {
+            assert_eq!(sources[original], (line.1, line.2));
+            duplicates += 1;
+        }
+    }
+    assert!(duplicates > 0);
+}
+
+#[test]
+fn truth_places_reads_of_joined_records_in_their_record() {
+    let dir = common::dir("sonicat-truth-concatenate");
+    let path = dir.join("truth.bed");
+    let (a, b) = (random_seq(300, 1), random_seq(300, 2));
+    let fasta = format!(">a\n{}\n>b\n{}\n", a, b);
+    let joined = format!("{}NNNNN{}", a, b);
+    let args = [
+        "-l",
+        "50",
+        "-d",
+        "3",
+        "--seed",
+        "1",
+        "--concatenate",
+        "--spacer",
+        "5",
+        "--truth",
+        path.to_str().unwrap(),
+    ];
+    let reads = records(&stdout(SONICAT, &args, fasta.as_bytes()));
+    let lines = truth(&path);
+    assert_eq!(lines.len(), reads.len());
+    let mut crossing = 0;
+    for ((_, read), line) in reads.iter().zip(&lines) {
+        let source = match line.0.as_str() {
+            "a" => &a[line.1..line.2],
+            "b" => &b[line.1..line.2],
+            contig => {
+                assert_eq!(contig, "concatenated");
+                assert!(mark(line, "contigs").is_some());
+                crossing += 1;
+                &joined[line.1..line.2]
+            }
+        };
+        assert_eq!(read, source);
+        assert_eq!(mark(line, "contigs").is_some(), line.0 == "concatenated");
+    }
+    assert!(crossing > 0 && crossing < reads.len());
+}